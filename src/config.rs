@@ -1,20 +1,29 @@
 use anyhow::Result;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
-pub async fn configure_opencode(
-    models: &[crate::ui::ModelSelection],
-    provider_url: &str,
-    is_project: bool,
-) -> Result<()> {
+/// OpenCode's config file, in the project (`./.opencode`) or home directory.
+pub fn opencode_config_path(is_project: bool) -> PathBuf {
     let target_dir = if is_project {
         PathBuf::from(".opencode")
     } else {
         let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
         home_dir.join(".opencode")
     };
+    target_dir.join("config.json")
+}
 
-    let config_path = target_dir.join("config.json");
+/// Point OpenCode's `localcode` provider at `provider_url`, with the chat and
+/// small models chosen like the llama-swap config does (`metadata` as for
+/// [`crate::runner::client_models`]).
+pub async fn configure_opencode(
+    models: &[crate::ui::ModelSelection],
+    metadata: &std::collections::HashMap<String, crate::gguf::GgufMetadata>,
+    provider_url: &str,
+    is_project: bool,
+) -> Result<()> {
+    let config_path = opencode_config_path(is_project);
+    let target_dir = config_path.parent().unwrap_or(std::path::Path::new("."));
 
     if !target_dir.exists() {
         fs::create_dir_all(&target_dir).await?;
@@ -29,18 +38,13 @@ pub async fn configure_opencode(
         serde_json::from_str(template_content).unwrap_or_else(|_| serde_json::json!({}))
     };
 
-    let mut standard_model_name = "default".to_string();
-    let mut autocomplete_model_name = None;
-
-    for m in models {
-        if crate::runner::is_autocomplete_model(&m.name) {
-            if autocomplete_model_name.is_none() {
-                autocomplete_model_name = Some(m.name.clone());
-            }
-        } else if standard_model_name == "default" {
-            standard_model_name = m.name.clone();
-        }
-    }
+    let (standard, autocomplete) = crate::runner::client_models(models, metadata);
+    let standard_model_name = standard.map_or_else(|| "default".to_string(), |m| m.name.clone());
+    let autocomplete_model_name = autocomplete.map(|m| m.name.clone());
+    let embedding_model_name = models
+        .iter()
+        .find(|m| m.role == Some(crate::ui::ModelRole::Embedding))
+        .map(|m| m.name.clone());
 
     if let Some(obj) = config.as_object_mut() {
         // Build the models map with both standard and autocomplete entries
@@ -59,18 +63,25 @@ pub async fn configure_opencode(
                 }),
             );
         }
+        // Base+adapter models can be picked in OpenCode's model list
+        for m in models {
+            for adapter in &m.adapters {
+                let name = adapter.served_name(&m.name);
+                models_map.insert(name.clone(), serde_json::json!({ "name": name }));
+            }
+        }
 
         // Build the provider config
         let mut provider_obj = serde_json::Map::new();
         provider_obj.insert("models".to_string(), serde_json::Value::Object(models_map));
         provider_obj.insert("name".to_string(), serde_json::json!("LocalCode"));
-        provider_obj.insert("npm".to_string(), serde_json::json!("@ai-sdk/openai-compatible"));
+        provider_obj.insert(
+            "npm".to_string(),
+            serde_json::json!("@ai-sdk/openai-compatible"),
+        );
         provider_obj.insert(
             "options".to_string(),
-            serde_json::json!({
-                "provider": "openai",
-                "baseURL": provider_url,
-            }),
+            provider_options(provider_url, embedding_model_name.as_deref()),
         );
 
         // Nest under provider.localcode
@@ -94,15 +105,46 @@ pub async fn configure_opencode(
     );
     fs::write(config_path, serde_json::to_string_pretty(&config)?).await?;
 
+    if let Some(embed_name) = embedding_model_name {
+        println!(
+            "🔎 Embeddings for {} are served at {}/embeddings",
+            embed_name,
+            provider_url.trim_end_matches('/')
+        );
+    }
+
     Ok(())
 }
 
+/// `options` of the `localcode` provider. The embedding model is recorded
+/// here with its endpoint rather than under `models`, which OpenCode lists in
+/// its chat model picker; the OpenAI-compatible SDK ignores keys it doesn't use.
+pub fn provider_options(provider_url: &str, embedding_model: Option<&str>) -> serde_json::Value {
+    let mut options = serde_json::json!({
+        "provider": "openai",
+        "baseURL": provider_url,
+    });
+    if let Some(name) = embedding_model {
+        options["embeddings"] = serde_json::json!({
+            "model": name,
+            "url": format!("{}/embeddings", provider_url.trim_end_matches('/')),
+        });
+    }
+    options
+}
+
+/// The global config directory (`~/.config/localcode`). Native mode also keeps
+/// its PID and log files here.
+pub fn global_config_dir() -> PathBuf {
+    let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home_dir.join(".config").join("localcode")
+}
+
 pub async fn save_localcode_config(config: &crate::ui::InitConfig, is_project: bool) -> Result<()> {
     let target_dir = if is_project {
         PathBuf::from(".")
     } else {
-        let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-        home_dir.join(".config").join("localcode")
+        global_config_dir()
     };
     let config_path = target_dir.join("localcode.json");
 
@@ -110,26 +152,157 @@ pub async fn save_localcode_config(config: &crate::ui::InitConfig, is_project: b
         fs::create_dir_all(&target_dir).await?;
     }
 
-    fs::write(config_path, serde_json::to_string_pretty(config)?).await?;
+    fs::write(&config_path, serde_json::to_string_pretty(config)?).await?;
+    if is_project {
+        // Best effort: only `gc` relies on the registry
+        let _ = register_project_config(&config_path);
+    }
     Ok(())
 }
 
-pub async fn load_localcode_config() -> Result<crate::ui::InitConfig> {
-    let mut config_path = PathBuf::from("localcode.json");
+/// Project configs seen by `init` or any other command, so `gc` can keep
+/// models other projects use. A JSON list of absolute `localcode.json` paths.
+fn project_registry_path() -> PathBuf {
+    global_config_dir().join("projects.json")
+}
 
-    if !config_path.exists() {
-        let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-        config_path = home_dir
-            .join(".config")
-            .join("localcode")
-            .join("localcode.json");
+pub fn register_project_config(config_path: &Path) -> Result<()> {
+    register_in(&project_registry_path(), config_path)
+}
+
+fn read_registry(registry: &Path) -> Vec<PathBuf> {
+    std::fs::read_to_string(registry)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Add `config_path` to the registry, dropping entries whose file is gone.
+fn register_in(registry: &Path, config_path: &Path) -> Result<()> {
+    let config_path = config_path.canonicalize()?;
+    let existing = read_registry(registry);
+    let mut entries: Vec<PathBuf> = existing.iter().filter(|p| p.exists()).cloned().collect();
+    if entries.contains(&config_path) && entries.len() == existing.len() {
+        return Ok(());
+    }
+    if !entries.contains(&config_path) {
+        entries.push(config_path);
+    }
+    if let Some(dir) = registry.parent() {
+        std::fs::create_dir_all(dir)?;
     }
+    std::fs::write(registry, serde_json::to_string_pretty(&entries)?)?;
+    Ok(())
+}
 
-    if !config_path.exists() {
-        anyhow::bail!("Global configuration not found. Please run `localcode init` first.");
+/// Every `localcode.json` LocalCode knows about: the current directory's, the
+/// global one and all registered projects that still exist.
+pub fn known_config_paths() -> Vec<PathBuf> {
+    known_config_paths_in(&project_registry_path())
+}
+
+fn known_config_paths_in(registry: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let candidates = [
+        PathBuf::from("localcode.json"),
+        global_config_dir().join("localcode.json"),
+    ]
+    .into_iter()
+    .chain(read_registry(registry));
+    for path in candidates {
+        if let Ok(path) = path.canonicalize()
+            && !paths.contains(&path)
+        {
+            paths.push(path);
+        }
     }
+    paths
+}
+
+pub fn load_config_file(path: &Path) -> Result<crate::ui::InitConfig> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// The config file `load_localcode_config` reads: `./localcode.json` if present,
+/// otherwise the global one. `None` when neither exists.
+pub fn localcode_config_path() -> Option<PathBuf> {
+    [
+        PathBuf::from("localcode.json"),
+        global_config_dir().join("localcode.json"),
+    ]
+    .into_iter()
+    .find(|p| p.exists())
+}
+
+pub async fn load_localcode_config() -> Result<crate::ui::InitConfig> {
+    let Some(config_path) = localcode_config_path() else {
+        anyhow::bail!("Global configuration not found. Please run `localcode init` first.");
+    };
 
-    let config_content = fs::read_to_string(config_path).await?;
+    let config_content = fs::read_to_string(&config_path).await?;
     let config: crate::ui::InitConfig = serde_json::from_str(&config_content)?;
+    if config_path.is_relative() {
+        // Projects configured before the registry existed become known on first use
+        let _ = register_project_config(&config_path);
+    }
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_options_embeddings() {
+        let url = "http://localhost:8080/v1";
+        let plain = provider_options(url, None);
+        assert_eq!(plain["baseURL"], url);
+        assert!(plain.get("embeddings").is_none());
+
+        let with = provider_options(url, Some("Qwen/Qwen3-Embedding-0.6B"));
+        assert_eq!(with["embeddings"]["model"], "Qwen/Qwen3-Embedding-0.6B");
+        assert_eq!(
+            with["embeddings"]["url"],
+            "http://localhost:8080/v1/embeddings"
+        );
+    }
+
+    #[test]
+    fn test_project_registry() {
+        let dir = std::env::temp_dir().join(format!("localcode-registry-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        let registry = dir.join("projects.json");
+        let a = dir.join("a/localcode.json");
+        let b = dir.join("b/localcode.json");
+        std::fs::write(&a, "{}").unwrap();
+        std::fs::write(&b, "{}").unwrap();
+
+        register_in(&registry, &a).unwrap();
+        register_in(&registry, &b).unwrap();
+        register_in(&registry, &a).unwrap();
+        let known = known_config_paths_in(&registry);
+        assert_eq!(
+            known
+                .iter()
+                .filter(|p| p.ends_with("a/localcode.json"))
+                .count(),
+            1
+        );
+        assert!(known.iter().any(|p| p.ends_with("b/localcode.json")));
+
+        // Deleted projects drop out
+        std::fs::remove_file(&b).unwrap();
+        assert!(
+            !known_config_paths_in(&registry)
+                .iter()
+                .any(|p| p.ends_with("b/localcode.json"))
+        );
+        register_in(&registry, &a).unwrap();
+        assert_eq!(read_registry(&registry).len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,36 @@
+use std::path::Path;
+
+/// Free bytes on the filesystem that holds `path`.
+///
+/// `path` doesn't need to exist yet: the nearest existing ancestor is used, so
+/// this works for a models dir that `start` hasn't created.
+pub fn available_space(path: &Path) -> Option<u64> {
+    let mut existing = path;
+    while !existing.exists() {
+        existing = existing.parent()?;
+    }
+    let canonical = existing.canonicalize().ok()?;
+
+    // The disk with the longest mount point that contains the path.
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|d| canonical.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .map(|d| d.available_space())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_available_space_for_missing_dir() {
+        let existing = available_space(&std::env::temp_dir());
+        let missing = available_space(&std::env::temp_dir().join("localcode-not-created/models"));
+        assert!(existing.is_some());
+        // Resolved through the existing ancestor, so it lands on the same disk
+        assert_eq!(existing.is_some(), missing.is_some());
+    }
+}
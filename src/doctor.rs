@@ -0,0 +1,629 @@
+use crate::runner::ContainerRuntime;
+use crate::ui::InitConfig;
+use console::style;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Below this much free space in the models dir, downloads are likely to fail.
+const DISK_FAIL_BYTES: u64 = 5_000_000_000;
+/// Below this, a second model or a quant upgrade probably won't fit.
+const DISK_WARN_BYTES: u64 = 20_000_000_000;
+
+const DEFAULT_HF_ENDPOINT: &str = "https://huggingface.co";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// One row of the `doctor` report.
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Run every check and print the report. Returns `false` if anything failed.
+pub async fn run_doctor() -> bool {
+    println!(
+        "{}",
+        style("🩺 Checking your LocalCode environment...")
+            .bold()
+            .cyan()
+    );
+
+    let mut checks = Vec::new();
+
+    let (config_check, config) = check_config();
+    checks.push(config_check);
+    let models_dir = PathBuf::from(shellexpand::tilde(&config.models_dir).to_string());
+
+    let server_running = if config.run_in_docker {
+        checks.push(check_container_runtime(config.container_runtime).await);
+        checks.push(check_gpu_runtime(config.container_runtime).await);
+        crate::runner::inspect_container(config.container_runtime)
+            .await
+            .is_some_and(|c| c.status == "running")
+    } else {
+        checks.extend(check_native_binaries(&config));
+        crate::native::running_pid().await.is_some()
+    };
+
+    checks.push(check_port(config.port, server_running));
+    checks.push(check_models_dir(&models_dir));
+    checks.push(check_disk_space(&models_dir));
+
+    if config.offline {
+        checks.push(Check::pass("Hugging Face token", "Skipped (offline mode)"));
+    } else {
+        let endpoint =
+            std::env::var("HF_ENDPOINT").unwrap_or_else(|_| DEFAULT_HF_ENDPOINT.to_string());
+        checks.push(check_hf_token(&endpoint, hf_token()).await);
+    }
+
+    checks.extend(check_models(&config, &models_dir));
+    checks.push(check_opencode(config.port));
+    checks.push(check_claude_code(
+        config.port,
+        std::env::var("ANTHROPIC_BASE_URL").ok().as_deref(),
+    ));
+
+    print_report(&checks);
+    !checks.iter().any(|c| c.status == CheckStatus::Fail)
+}
+
+fn print_report(checks: &[Check]) {
+    println!();
+    println!(
+        "{:<28} | {:<6} | {}",
+        style("Check").bold().cyan(),
+        style("Status").bold().cyan(),
+        style("Details").bold().cyan()
+    );
+    println!("{:-<28}-|-{:-<6}-|-{:-<40}", "", "", "");
+    for c in checks {
+        let status = match c.status {
+            CheckStatus::Pass => style("PASS").green().bold(),
+            CheckStatus::Warn => style("WARN").yellow().bold(),
+            CheckStatus::Fail => style("FAIL").red().bold(),
+        };
+        println!("{:<28} | {:<6} | {}", c.name, status, c.detail);
+        if let Some(hint) = &c.hint {
+            println!(
+                "{:<28} | {:<6} |   {} {}",
+                "",
+                "",
+                style("↳").dim(),
+                style(hint).dim()
+            );
+        }
+    }
+
+    let fails = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    let warns = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Warn)
+        .count();
+    println!();
+    if fails > 0 {
+        println!(
+            "{} {} failed, {} warning(s). Fix the failures above before `localcode start`.",
+            style("✗").red().bold(),
+            fails,
+            warns
+        );
+    } else if warns > 0 {
+        println!(
+            "{} No failures, {} warning(s).",
+            style("⚠️").yellow().bold(),
+            warns
+        );
+    } else {
+        println!("{} Everything looks good.", style("✓").green().bold());
+    }
+}
+
+/// Parse the config file the other commands would use. Falls back to the
+/// defaults so the remaining checks still run.
+fn check_config() -> (Check, InitConfig) {
+    let Some(path) = crate::config::localcode_config_path() else {
+        return (
+            Check::warn(
+                "Config file",
+                "No localcode.json found; checking defaults",
+                "Run `localcode init` (or `localcode init --global`) to create one.",
+            ),
+            InitConfig::default(),
+        );
+    };
+
+    let parsed = std::fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(serde_json::from_str::<InitConfig>(&content)?));
+    match parsed {
+        Ok(config) => (
+            Check::pass("Config file", path.display().to_string()),
+            config,
+        ),
+        Err(e) => (
+            Check::fail(
+                "Config file",
+                format!("{}: {}", path.display(), e),
+                "Fix the JSON by hand or re-run `localcode init` to regenerate it.",
+            ),
+            InitConfig::default(),
+        ),
+    }
+}
+
+async fn check_container_runtime(runtime: ContainerRuntime) -> Check {
+    let name = format!("Container runtime ({})", runtime.binary());
+    let version = match Command::new(runtime.binary())
+        .arg("--version")
+        .output()
+        .await
+    {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).trim().to_string(),
+        _ => {
+            return Check::fail(
+                name,
+                format!("`{}` is not installed or not on PATH", runtime.binary()),
+                "Install it, pick another with `localcode init --runtime`, or use `--no-docker`.",
+            );
+        }
+    };
+
+    // `info` talks to the daemon (or, for Podman, the storage backend), so it
+    // catches a stopped Docker Desktop or a missing docker group membership.
+    match Command::new(runtime.binary()).arg("info").output().await {
+        Ok(o) if o.status.success() => Check::pass(name, version),
+        Ok(o) => Check::fail(
+            name,
+            first_line(&String::from_utf8_lossy(&o.stderr)),
+            match runtime {
+                ContainerRuntime::Docker => {
+                    "Start the Docker daemon/Desktop, and make sure your user is in the `docker` group."
+                }
+                ContainerRuntime::Podman => {
+                    "Run `podman system reset` if storage is corrupt, or `podman machine start` on macOS/Windows."
+                }
+                ContainerRuntime::Nerdctl => {
+                    "Start containerd (`sudo systemctl start containerd`)."
+                }
+            },
+        ),
+        Err(e) => Check::fail(name, e.to_string(), "Reinstall the container runtime."),
+    }
+}
+
+/// GPU passthrough only matters with an NVIDIA GPU; without one the CPU image
+/// is used and there's nothing to check.
+async fn check_gpu_runtime(runtime: ContainerRuntime) -> Check {
+    let name = "NVIDIA container support";
+    let has_nvidia_gpu = Command::new("nvidia-smi")
+        .arg("-L")
+        .output()
+        .await
+        .is_ok_and(|o| o.status.success() && !o.stdout.is_empty());
+    if !has_nvidia_gpu {
+        return Check::pass(name, "No NVIDIA GPU detected; the CPU image will be used");
+    }
+
+    let cdi_spec = ["/etc/cdi", "/var/run/cdi"].iter().any(|dir| {
+        std::fs::read_dir(dir).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|e| e.file_name().to_string_lossy().contains("nvidia"))
+        })
+    });
+
+    match runtime {
+        ContainerRuntime::Podman => {
+            if cdi_spec {
+                Check::pass(name, "NVIDIA CDI spec found")
+            } else {
+                Check::fail(
+                    name,
+                    "No NVIDIA CDI spec in /etc/cdi or /var/run/cdi",
+                    "Run `sudo nvidia-ctk cdi generate --output=/etc/cdi/nvidia.yaml`.",
+                )
+            }
+        }
+        ContainerRuntime::Docker | ContainerRuntime::Nerdctl => {
+            let runtimes = Command::new(runtime.binary())
+                .args(["info", "--format", "{{json .Runtimes}}"])
+                .output()
+                .await
+                .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+                .unwrap_or_default();
+            let toolkit = runtimes.contains("nvidia")
+                || crate::native::find_binary("nvidia-container-cli", None).is_ok();
+            if toolkit {
+                Check::pass(name, "NVIDIA Container Toolkit found")
+            } else {
+                Check::fail(
+                    name,
+                    "NVIDIA GPU present but the NVIDIA Container Toolkit is not installed",
+                    "Install it: https://docs.nvidia.com/datacenter/cloud-native/container-toolkit/latest/install-guide.html",
+                )
+            }
+        }
+    }
+}
+
+fn check_native_binaries(config: &InitConfig) -> Vec<Check> {
+    [
+        ("llama-swap", config.llama_swap_path.as_deref()),
+        ("llama-server", config.llama_server_path.as_deref()),
+    ]
+    .into_iter()
+    .map(
+        |(bin, configured)| match crate::native::find_binary(bin, configured) {
+            Ok(path) => Check::pass(bin, path.display().to_string()),
+            Err(e) => Check::fail(
+                bin,
+                e.to_string(),
+                format!(
+                    "Install {} or set `{}_path` in localcode.json.",
+                    bin,
+                    bin.replace('-', "_")
+                ),
+            ),
+        },
+    )
+    .collect()
+}
+
+/// The port must be free, unless it's our own server holding it.
+fn check_port(port: u16, server_running: bool) -> Check {
+    let name = format!("Port {}", port);
+    if server_running {
+        return Check::pass(name, "In use by the running LocalCode server");
+    }
+    match std::net::TcpListener::bind(("0.0.0.0", port)) {
+        Ok(_) => Check::pass(name, "Available"),
+        Err(e) => Check::fail(
+            name,
+            format!("Cannot bind: {}", e),
+            "Stop whatever is using the port, or change `port` in localcode.json.",
+        ),
+    }
+}
+
+fn check_models_dir(models_dir: &Path) -> Check {
+    let name = "Models directory";
+    if !models_dir.exists() {
+        return Check::warn(
+            name,
+            format!("{} does not exist yet", models_dir.display()),
+            "It will be created by `localcode start`.",
+        );
+    }
+    let probe = models_dir.join(".localcode-doctor");
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::pass(name, format!("{} is writable", models_dir.display()))
+        }
+        Err(e) => Check::fail(
+            name,
+            format!("{} is not writable: {}", models_dir.display(), e),
+            "Fix its permissions or point `models_dir` somewhere writable.",
+        ),
+    }
+}
+
+fn check_disk_space(models_dir: &Path) -> Check {
+    let name = "Free disk space";
+    match crate::disk::available_space(models_dir) {
+        Some(free) => {
+            let detail = format!("{} free", human_bytes::human_bytes(free as f64));
+            if free < DISK_FAIL_BYTES {
+                Check::fail(
+                    name,
+                    detail,
+                    "Free up space or move `models_dir` to a larger disk.",
+                )
+            } else if free < DISK_WARN_BYTES {
+                Check::warn(name, detail, "Larger models or a second model may not fit.")
+            } else {
+                Check::pass(name, detail)
+            }
+        }
+        None => Check::warn(
+            name,
+            "Could not determine free space",
+            "Check manually that the models disk has room for your GGUF files.",
+        ),
+    }
+}
+
+/// Hugging Face token the way `huggingface_hub` finds it: env vars first, then
+/// the token file written by `huggingface-cli login`.
+fn hf_token() -> Option<String> {
+    if let Some(token) = ["HF_TOKEN", "HUGGING_FACE_HUB_TOKEN"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|t| !t.is_empty()))
+    {
+        return Some(token);
+    }
+    let hf_home = std::env::var_os("HF_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|h| h.join(".cache").join("huggingface")))?;
+    std::fs::read_to_string(hf_home.join("token"))
+        .ok()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Validate the token against `<endpoint>/api/whoami-v2`.
+async fn check_hf_token(endpoint: &str, token: Option<String>) -> Check {
+    let name = "Hugging Face token";
+    let Some(token) = token else {
+        return Check::pass(name, "Not set (only needed for gated models)");
+    };
+
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(c) => c,
+        Err(e) => return Check::warn(name, e.to_string(), "Could not create an HTTP client."),
+    };
+    let response = client
+        .get(format!("{}/api/whoami-v2", endpoint.trim_end_matches('/')))
+        .bearer_auth(token)
+        .send()
+        .await;
+
+    match response {
+        Ok(r) if r.status().is_success() => {
+            let user = r
+                .json::<serde_json::Value>()
+                .await
+                .ok()
+                .and_then(|v| v["name"].as_str().map(str::to_string))
+                .unwrap_or_else(|| "unknown user".to_string());
+            Check::pass(name, format!("Valid (logged in as {})", user))
+        }
+        Ok(r) if r.status() == reqwest::StatusCode::UNAUTHORIZED => Check::fail(
+            name,
+            "Token was rejected (401 Unauthorized)",
+            "Create a new token at https://huggingface.co/settings/tokens and update HF_TOKEN.",
+        ),
+        Ok(r) => Check::warn(
+            name,
+            format!("Unexpected response: {}", r.status()),
+            format!("Check that {} is reachable.", endpoint),
+        ),
+        Err(e) => Check::warn(
+            name,
+            format!("Could not reach {}: {}", endpoint, e),
+            "Check your network or proxy settings (and HF_ENDPOINT if you use a mirror).",
+        ),
+    }
+}
+
+fn check_models(config: &InitConfig, models_dir: &Path) -> Vec<Check> {
+    if config.models.is_empty() {
+        return vec![Check::warn(
+            "Models",
+            "No models configured",
+            "Run `localcode init` to pick models.",
+        )];
+    }
+    config
+        .models
+        .iter()
+        .map(|m| {
+            match crate::runner::find_model_file(m, models_dir, &std::collections::HashMap::new()) {
+                Some(path) => Check::pass("Model", format!("{}: {}", m.name, path.display())),
+                None => Check::warn(
+                    "Model",
+                    format!("{}: GGUF not found locally", m.name),
+                    "`localcode start` will download it.",
+                ),
+            }
+        })
+        .collect()
+}
+
+/// OpenCode should point at our `/v1` endpoint. The project config wins over
+/// the home one, the same way OpenCode resolves them.
+fn check_opencode(port: u16) -> Check {
+    let path = [
+        crate::config::opencode_config_path(true),
+        crate::config::opencode_config_path(false),
+    ]
+    .into_iter()
+    .find(|p| p.exists());
+    match path {
+        Some(path) => check_opencode_file(&path, port),
+        None => Check::warn(
+            "OpenCode config",
+            "No .opencode/config.json found",
+            "Run `localcode init` to write it, or `localcode info` for a snippet.",
+        ),
+    }
+}
+
+fn check_opencode_file(path: &Path, port: u16) -> Check {
+    let name = "OpenCode config";
+    let value = match std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|c| Ok(serde_json::from_str::<serde_json::Value>(&c)?))
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Check::fail(
+                name,
+                format!("{}: {}", path.display(), e),
+                "Fix the JSON or delete it and re-run `localcode init`.",
+            );
+        }
+    };
+
+    let expected = format!("http://localhost:{}/v1", port);
+    match value["provider"]["localcode"]["options"]["baseURL"].as_str() {
+        Some(url) if url == expected => Check::pass(name, path.display().to_string()),
+        Some(url) => Check::warn(
+            name,
+            format!(
+                "localcode provider points at {} (expected {})",
+                url, expected
+            ),
+            "Re-run `localcode init` so OpenCode follows the configured port.",
+        ),
+        None => Check::warn(
+            name,
+            format!("{} has no localcode provider", path.display()),
+            "Re-run `localcode init`, or add the snippet from `localcode info`.",
+        ),
+    }
+}
+
+fn check_claude_code(port: u16, base_url: Option<&str>) -> Check {
+    let name = "Claude Code env";
+    let expected = format!("http://localhost:{}", port);
+    match base_url {
+        Some(url)
+            if url.trim_end_matches('/') == expected
+                || url.trim_end_matches('/') == format!("http://127.0.0.1:{}", port) =>
+        {
+            Check::pass(name, format!("ANTHROPIC_BASE_URL={}", url))
+        }
+        Some(url) => Check::warn(
+            name,
+            format!("ANTHROPIC_BASE_URL={} (expected {})", url, expected),
+            "Run `localcode info` for the right exports.",
+        ),
+        None => Check::warn(
+            name,
+            "ANTHROPIC_BASE_URL is not set in this shell",
+            "Only needed for Claude Code; run `localcode info` for the exports.",
+        ),
+    }
+}
+
+fn first_line(s: &str) -> String {
+    s.lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("unknown error")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::tests::serve;
+
+    #[tokio::test]
+    async fn test_check_hf_token() {
+        let base = serve(vec![(
+            "/api/whoami-v2",
+            200,
+            r#"{"name":"octocat"}"#.to_string(),
+        )])
+        .await;
+        let ok = check_hf_token(&base, Some("hf_good".to_string())).await;
+        assert_eq!(ok.status, CheckStatus::Pass);
+        assert!(ok.detail.contains("octocat"));
+
+        let base = serve(vec![(
+            "/api/whoami-v2",
+            401,
+            r#"{"error":"Invalid"}"#.to_string(),
+        )])
+        .await;
+        let rejected = check_hf_token(&base, Some("hf_bad".to_string())).await;
+        assert_eq!(rejected.status, CheckStatus::Fail);
+        assert!(rejected.hint.is_some());
+
+        // No token is fine: only gated models need one
+        let none = check_hf_token(&base, None).await;
+        assert_eq!(none.status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn test_check_port() {
+        let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(check_port(port, false).status, CheckStatus::Fail);
+        // Held by our own server → expected
+        assert_eq!(check_port(port, true).status, CheckStatus::Pass);
+        drop(listener);
+        assert_eq!(check_port(port, false).status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn test_check_opencode_file() {
+        let dir = std::env::temp_dir().join(format!("localcode-doctor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+
+        std::fs::write(
+            &path,
+            r#"{"provider":{"localcode":{"options":{"baseURL":"http://localhost:8080/v1"}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(check_opencode_file(&path, 8080).status, CheckStatus::Pass);
+        assert_eq!(check_opencode_file(&path, 9090).status, CheckStatus::Warn);
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert_eq!(check_opencode_file(&path, 8080).status, CheckStatus::Fail);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_claude_code() {
+        assert_eq!(
+            check_claude_code(8080, Some("http://localhost:8080")).status,
+            CheckStatus::Pass
+        );
+        assert_eq!(
+            check_claude_code(8080, Some("https://api.anthropic.com")).status,
+            CheckStatus::Warn
+        );
+        assert_eq!(check_claude_code(8080, None).status, CheckStatus::Warn);
+    }
+}
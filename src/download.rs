@@ -0,0 +1,681 @@
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+
+/// Attempts per file before giving up. Each retry resumes where the last one
+/// stopped, so this bounds flaky connections, not file size.
+const MAX_ATTEMPTS: u32 = 6;
+
+/// First retry delay; doubled on every further attempt (1s, 2s, 4s, ...).
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Repository listing from `GET /api/models/<repo>/revision/main?blobs=true`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RepoInfo {
+    /// Commit the listing was taken at; downloads are pinned to it.
+    pub sha: String,
+    #[serde(default)]
+    pub siblings: Vec<RepoFile>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RepoFile {
+    pub rfilename: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Git blob hash; only used to name non-LFS files in the cache.
+    #[serde(rename = "blobId", default)]
+    pub blob_id: Option<String>,
+    #[serde(default)]
+    pub lfs: Option<LfsInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LfsInfo {
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl RepoInfo {
+    pub fn file(&self, name: &str) -> Option<&RepoFile> {
+        self.siblings.iter().find(|f| f.rfilename == name)
+    }
+}
+
+impl RepoFile {
+    /// Size in bytes of the actual file (not the LFS pointer), if listed.
+    pub fn size(&self) -> Option<u64> {
+        self.lfs.as_ref().map(|l| l.size).or(self.size)
+    }
+
+    /// Name of the file under `blobs/`: the LFS SHA-256, else the git blob id.
+    fn blob_name(&self) -> Option<&str> {
+        self.lfs
+            .as_ref()
+            .map(|l| l.sha256.as_str())
+            .or(self.blob_id.as_deref())
+    }
+}
+
+/// Not enough free space in the models directory for the pending downloads.
+///
+/// A distinct type so `start` can tell it apart from network failures, which
+/// it tolerates when models are already cached.
+#[derive(Debug)]
+pub struct InsufficientSpace {
+    pub dir: PathBuf,
+    pub needed: u64,
+    pub available: u64,
+}
+
+impl std::fmt::Display for InsufficientSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Not enough disk space in {}: downloads need {}, only {} free ({} short)",
+            self.dir.display(),
+            human_bytes::human_bytes(self.needed as f64),
+            human_bytes::human_bytes(self.available as f64),
+            human_bytes::human_bytes(self.needed.saturating_sub(self.available) as f64)
+        )
+    }
+}
+
+impl std::error::Error for InsufficientSpace {}
+
+/// Compare `needed` bytes against the free space on the filesystem holding
+/// `dir`. Unknown free space (unusual mounts) is not treated as a shortfall.
+pub fn check_space(dir: &Path, needed: u64) -> std::result::Result<(), InsufficientSpace> {
+    match crate::disk::available_space(dir) {
+        Some(available) if available < needed => Err(InsufficientSpace {
+            dir: dir.to_path_buf(),
+            needed,
+            available,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Why a single download attempt failed.
+enum FetchError {
+    /// Network hiccup, truncated body, 5xx or rate limit: worth retrying.
+    Transient(anyhow::Error),
+    /// 401/403/404 and local I/O errors: retrying won't help.
+    Fatal(anyhow::Error),
+}
+
+/// Minimal async Hugging Face Hub client.
+///
+/// Files are stored in the same layout `huggingface_hub` (and the `hf-hub`
+/// crate) use — `models--org--repo/{blobs,snapshots,refs}` under `cache_dir` —
+/// so weights fetched by earlier LocalCode versions or by llama-server's own
+/// `--hf-repo` are reused. `HF_ENDPOINT` overrides the server, which is also
+/// how tests point it at a local stand-in.
+pub struct HubClient {
+    http: reqwest::Client,
+    endpoint: String,
+    token: Option<String>,
+    cache_dir: PathBuf,
+    retry_delay: Duration,
+}
+
+impl HubClient {
+    pub fn new(cache_dir: &Path) -> Result<Self> {
+        let endpoint =
+            std::env::var("HF_ENDPOINT").unwrap_or_else(|_| DEFAULT_ENDPOINT.to_string());
+        let token = std::env::var("HF_TOKEN").ok().filter(|t| !t.is_empty());
+        Self::with_endpoint(cache_dir, &endpoint, token)
+    }
+
+    pub fn with_endpoint(cache_dir: &Path, endpoint: &str, token: Option<String>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(30))
+            // Per-read timeout: a stalled CDN connection is retried instead of hanging forever.
+            .read_timeout(Duration::from_secs(60))
+            .user_agent(concat!("localcode/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(HubClient {
+            http,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            token,
+            cache_dir: cache_dir.to_path_buf(),
+            retry_delay: RETRY_BASE_DELAY,
+        })
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let req = self.http.get(url);
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    fn repo_dir(&self, repo: &str) -> PathBuf {
+        self.cache_dir
+            .join(format!("models--{}", repo.replace('/', "--")))
+    }
+
+    pub async fn repo_info(&self, repo: &str) -> Result<RepoInfo> {
+        let url = format!(
+            "{}/api/models/{}/revision/main?blobs=true",
+            self.endpoint, repo
+        );
+        let info = self
+            .get(&url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", self.endpoint))?
+            .error_for_status()
+            .with_context(|| format!("Repository {} is not available", repo))?
+            .json()
+            .await
+            .with_context(|| format!("Unexpected repository listing for {}", repo))?;
+        Ok(info)
+    }
+
+    /// Download `filename` from `repo` at the commit in `info`, resuming any
+    /// partial download and verifying the LFS SHA-256. Returns the snapshot path.
+    pub async fn download(&self, repo: &str, info: &RepoInfo, filename: &str) -> Result<PathBuf> {
+        let file = info
+            .file(filename)
+            .with_context(|| format!("{} has no file {}", repo, filename))?;
+
+        let repo_dir = self.repo_dir(repo);
+        let snapshot = repo_dir.join("snapshots").join(&info.sha).join(filename);
+        if snapshot.exists() {
+            return Ok(snapshot);
+        }
+
+        let Some(blob_name) = file.blob_name() else {
+            anyhow::bail!(
+                "{} in {} has neither an LFS hash nor a blob id",
+                filename,
+                repo
+            );
+        };
+        let expected_sha = file.lfs.as_ref().map(|l| l.sha256.clone());
+        let size = file.size();
+        let blobs_dir = repo_dir.join("blobs");
+        let blob = blobs_dir.join(blob_name);
+
+        if !blob.exists() {
+            tokio::fs::create_dir_all(&blobs_dir).await?;
+            let partial = blobs_dir.join(format!("{}.incomplete", blob_name));
+            let url = format!(
+                "{}/{}/resolve/{}/{}",
+                self.endpoint, repo, info.sha, filename
+            );
+
+            self.fetch_with_retries(&url, &partial, size, filename)
+                .await?;
+
+            if let Some(expected) = expected_sha {
+                let actual = sha256_file(&partial).await?;
+                if !actual.eq_ignore_ascii_case(&expected) {
+                    let _ = tokio::fs::remove_file(&partial).await;
+                    anyhow::bail!(
+                        "Checksum mismatch for {}: expected sha256 {}, got {}. The partial file was removed; run the command again to re-download.",
+                        filename,
+                        expected,
+                        actual
+                    );
+                }
+            }
+            tokio::fs::rename(&partial, &blob).await?;
+        }
+
+        link_snapshot(&blob, &snapshot, filename)?;
+        let refs_dir = repo_dir.join("refs");
+        tokio::fs::create_dir_all(&refs_dir).await?;
+        tokio::fs::write(refs_dir.join("main"), &info.sha).await?;
+
+        Ok(snapshot)
+    }
+
+    /// Bytes still to be written to disk to have `filename` in the cache:
+    /// zero if it's already there, minus any partial download that will be resumed.
+    pub fn remaining_bytes(&self, repo: &str, info: &RepoInfo, filename: &str) -> u64 {
+        let Some(file) = info.file(filename) else {
+            return 0;
+        };
+        let repo_dir = self.repo_dir(repo);
+        if repo_dir
+            .join("snapshots")
+            .join(&info.sha)
+            .join(filename)
+            .exists()
+        {
+            return 0;
+        }
+        let size = file.size().unwrap_or(0);
+        let Some(blob_name) = file.blob_name() else {
+            return size;
+        };
+        let blobs_dir = repo_dir.join("blobs");
+        if blobs_dir.join(blob_name).exists() {
+            return 0;
+        }
+        let partial = std::fs::metadata(blobs_dir.join(format!("{}.incomplete", blob_name)))
+            .map(|m| m.len())
+            .unwrap_or(0);
+        size.saturating_sub(partial)
+    }
+
+    async fn fetch_with_retries(
+        &self,
+        url: &str,
+        partial: &Path,
+        size: Option<u64>,
+        label: &str,
+    ) -> Result<()> {
+        let pb = ProgressBar::new(size.unwrap_or(0));
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "  [{bar:40.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} ETA {eta} {msg}",
+                )
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        pb.set_message(label.to_string());
+
+        let mut attempt = 1;
+        loop {
+            match self.fetch_once(url, partial, size, &pb).await {
+                Ok(()) => {
+                    pb.finish_and_clear();
+                    return Ok(());
+                }
+                Err(FetchError::Transient(e)) if attempt < MAX_ATTEMPTS => {
+                    let delay = self.retry_delay * 2u32.pow(attempt - 1);
+                    pb.println(format!(
+                        "  ⚠️  {} (attempt {}/{}), retrying in {}s...",
+                        e,
+                        attempt,
+                        MAX_ATTEMPTS,
+                        delay.as_secs()
+                    ));
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(FetchError::Transient(e)) | Err(FetchError::Fatal(e)) => {
+                    pb.abandon();
+                    return Err(e.context(format!("Failed to download {}", label)));
+                }
+            }
+        }
+    }
+
+    /// One HTTP request, appending to `partial` from where it currently ends.
+    async fn fetch_once(
+        &self,
+        url: &str,
+        partial: &Path,
+        size: Option<u64>,
+        pb: &ProgressBar,
+    ) -> std::result::Result<(), FetchError> {
+        let mut offset = tokio::fs::metadata(partial)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+        match size {
+            Some(size) if offset == size => return Ok(()),
+            // Stale partial from a different version of the file.
+            Some(size) if offset > size => offset = 0,
+            _ => {}
+        }
+
+        let mut req = self.get(url);
+        if offset > 0 {
+            req = req.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let mut resp = req
+            .send()
+            .await
+            .map_err(|e| FetchError::Transient(e.into()))?;
+
+        let status = resp.status();
+        if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            // Partial doesn't line up with the remote file; start over.
+            let _ = tokio::fs::remove_file(partial).await;
+            return Err(FetchError::Transient(anyhow::anyhow!(
+                "server rejected resume range"
+            )));
+        }
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(FetchError::Transient(anyhow::anyhow!("HTTP {}", status)));
+        }
+        if !status.is_success() {
+            return Err(FetchError::Fatal(anyhow::anyhow!(
+                "HTTP {} for {}",
+                status,
+                url
+            )));
+        }
+
+        // 206 continues the partial file; a plain 200 means the server ignored
+        // the Range header, so the partial is discarded.
+        let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+        let start = if resumed { offset } else { 0 };
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(partial)
+            .await
+            .map_err(|e| FetchError::Fatal(e.into()))?;
+        pb.set_position(start);
+
+        let mut written = start;
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .map_err(|e| FetchError::Transient(e.into()))?
+        {
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| FetchError::Fatal(e.into()))?;
+            written += chunk.len() as u64;
+            pb.set_position(written);
+        }
+        file.flush()
+            .await
+            .map_err(|e| FetchError::Fatal(e.into()))?;
+
+        if let Some(size) = size
+            && written != size
+        {
+            return Err(FetchError::Transient(anyhow::anyhow!(
+                "connection closed after {} of {} bytes",
+                written,
+                size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// SHA-256 of a file as lowercase hex. Runs on the blocking pool; hashing a
+/// 40 GB file takes a while.
+async fn sha256_file(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<String> {
+        use std::io::Read;
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 1 << 20];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    })
+    .await?
+}
+
+/// Point `snapshots/<commit>/<file>` at the blob, like huggingface_hub does.
+/// Falls back to a hard link (or a copy) where symlinks aren't allowed.
+fn link_snapshot(blob: &Path, snapshot: &Path, filename: &str) -> Result<()> {
+    if let Some(dir) = snapshot.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Relative, so the link survives the models dir being mounted elsewhere
+    // (e.g. at /models inside the container).
+    let depth = filename.matches('/').count() + 2;
+    let target = PathBuf::from("../".repeat(depth))
+        .join("blobs")
+        .join(blob.file_name().unwrap_or_default());
+
+    #[cfg(unix)]
+    if std::os::unix::fs::symlink(&target, snapshot).is_ok() {
+        return Ok(());
+    }
+    #[cfg(windows)]
+    if std::os::windows::fs::symlink_file(&target, snapshot).is_ok() {
+        return Ok(());
+    }
+    let _ = target;
+    if std::fs::hard_link(blob, snapshot).is_err() {
+        std::fs::copy(blob, snapshot)
+            .with_context(|| format!("Failed to place {} in the cache", filename))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const REPO: &str = "org/model-GGUF";
+    const FILE: &str = "model-Q4_K_M.gguf";
+    const COMMIT: &str = "0123abcd";
+
+    /// Local stand-in for the Hub: serves the repo listing and the file
+    /// (honouring `Range`). The first `drop_first` file responses are cut off
+    /// halfway. Returns the base URL and a log of request heads.
+    async fn hub_stand_in(
+        content: Vec<u8>,
+        sha256: String,
+        drop_first: usize,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let log_c = log.clone();
+        let drops = Arc::new(Mutex::new(drop_first));
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let content = content.clone();
+                let sha256 = sha256.clone();
+                let log = log_c.clone();
+                let drops = drops.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                    log.lock().unwrap().push(head.clone());
+                    let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+                    if path
+                        == format!(
+                            "/api/models/{}/revision/main?blobs=true",
+                            REPO.to_lowercase()
+                        )
+                    {
+                        let body = serde_json::json!({
+                            "sha": COMMIT,
+                            "siblings": [
+                                {"rfilename": "README.md", "size": 10, "blobId": "f00d"},
+                                {"rfilename": FILE, "size": content.len(), "blobId": "beef",
+                                 "lfs": {"sha256": sha256, "size": content.len(), "pointerSize": 130}}
+                            ]
+                        })
+                        .to_string();
+                        let resp = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        let _ = socket.write_all(resp.as_bytes()).await;
+                        return;
+                    }
+                    if path != format!("/{}/resolve/{}/{}", REPO, COMMIT, FILE).to_lowercase() {
+                        let _ = socket.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+                        return;
+                    }
+
+                    let start: usize = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("range: bytes="))
+                        .and_then(|r| r.trim().trim_end_matches('-').parse().ok())
+                        .unwrap_or(0);
+                    let body = &content[start..];
+                    let status = if start > 0 {
+                        "206 Partial Content"
+                    } else {
+                        "200 OK"
+                    };
+                    let resp_head = format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        status,
+                        body.len()
+                    );
+                    let _ = socket.write_all(resp_head.as_bytes()).await;
+
+                    let drop_this = {
+                        let mut d = drops.lock().unwrap();
+                        let hit = *d > 0;
+                        *d = d.saturating_sub(1);
+                        hit
+                    };
+                    let end = if drop_this {
+                        body.len() / 2
+                    } else {
+                        body.len()
+                    };
+                    let _ = socket.write_all(&body[..end]).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        (base, log)
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn temp_cache(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("localcode-dl-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_download_resumes_and_verifies() {
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let (base, log) = hub_stand_in(content.clone(), sha256_hex(&content), 1).await;
+        let cache = temp_cache("resume");
+
+        let mut client = HubClient::with_endpoint(&cache, &base, None).unwrap();
+        client.retry_delay = Duration::ZERO;
+        let info = client.repo_info(REPO).await.unwrap();
+        let path = client.download(REPO, &info, FILE).await.unwrap();
+
+        // huggingface_hub cache layout
+        let repo_dir = cache.join("models--org--model-GGUF");
+        assert_eq!(path, repo_dir.join("snapshots").join(COMMIT).join(FILE));
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+        assert_eq!(
+            std::fs::read_to_string(repo_dir.join("refs/main")).unwrap(),
+            COMMIT
+        );
+        assert!(repo_dir.join("blobs").join(sha256_hex(&content)).is_file());
+
+        // The dropped first response was resumed, not restarted
+        let file_requests: Vec<String> = log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|h| h.contains("/resolve/"))
+            .cloned()
+            .collect();
+        assert_eq!(file_requests.len(), 2);
+        assert!(!file_requests[0].contains("range:"));
+        assert!(file_requests[1].contains(&format!("range: bytes={}-", content.len() / 2)));
+
+        // Cached: no further file requests
+        client.download(REPO, &info, FILE).await.unwrap();
+        assert_eq!(
+            log.lock()
+                .unwrap()
+                .iter()
+                .filter(|h| h.contains("/resolve/"))
+                .count(),
+            2
+        );
+
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_checksum_mismatch() {
+        let content = b"not the weights you were looking for".to_vec();
+        let (base, _) = hub_stand_in(content, "00".repeat(32), 0).await;
+        let cache = temp_cache("mismatch");
+
+        let client = HubClient::with_endpoint(&cache, &base, None).unwrap();
+        let info = client.repo_info(REPO).await.unwrap();
+        let err = client.download(REPO, &info, FILE).await.unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+
+        // Nothing half-verified is left behind to be picked up later
+        let blobs = cache.join("models--org--model-GGUF/blobs");
+        assert_eq!(std::fs::read_dir(&blobs).unwrap().count(), 0);
+        assert!(!cache.join("models--org--model-GGUF/snapshots").exists());
+
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remaining_bytes_accounts_for_partial_and_cached() {
+        let content = vec![7u8; 1000];
+        let sha = sha256_hex(&content);
+        let (base, _) = hub_stand_in(content.clone(), sha.clone(), 0).await;
+        let cache = temp_cache("remaining");
+
+        let client = HubClient::with_endpoint(&cache, &base, None).unwrap();
+        let info = client.repo_info(REPO).await.unwrap();
+        assert_eq!(info.file(FILE).unwrap().size(), Some(1000));
+        assert_eq!(client.remaining_bytes(REPO, &info, FILE), 1000);
+
+        let blobs = cache.join("models--org--model-GGUF/blobs");
+        std::fs::create_dir_all(&blobs).unwrap();
+        std::fs::write(blobs.join(format!("{}.incomplete", sha)), &content[..400]).unwrap();
+        assert_eq!(client.remaining_bytes(REPO, &info, FILE), 600);
+
+        client.download(REPO, &info, FILE).await.unwrap();
+        assert_eq!(client.remaining_bytes(REPO, &info, FILE), 0);
+
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn test_check_space() {
+        let dir = std::env::temp_dir();
+        assert!(check_space(&dir, 0).is_ok());
+        let err = check_space(&dir, u64::MAX).unwrap_err();
+        assert_eq!(err.needed, u64::MAX);
+        assert!(err.to_string().starts_with("Not enough disk space in"));
+    }
+
+    #[tokio::test]
+    async fn test_repo_info_missing_repo() {
+        let (base, _) = hub_stand_in(Vec::new(), String::new(), 0).await;
+        let client = HubClient::with_endpoint(&temp_cache("missing"), &base, None).unwrap();
+        assert!(client.repo_info("nobody/nothing-GGUF").await.is_err());
+    }
+}
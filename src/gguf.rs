@@ -0,0 +1,472 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Little-endian "GGUF".
+const GGUF_MAGIC: u32 = 0x4655_4747;
+
+/// Refuse to allocate strings bigger than this; chat templates are a few KB,
+/// so anything near this size means we're reading garbage.
+const MAX_STRING_LEN: u64 = 16 * 1024 * 1024;
+
+/// Same for array lengths; the biggest real arrays are vocabularies and
+/// merge lists of a few hundred thousand entries.
+const MAX_ARRAY_LEN: u64 = 16 * 1024 * 1024;
+
+// GGUF metadata value types (see ggml/docs/gguf.md).
+const TYPE_UINT8: u32 = 0;
+const TYPE_INT8: u32 = 1;
+const TYPE_UINT16: u32 = 2;
+const TYPE_INT16: u32 = 3;
+const TYPE_UINT32: u32 = 4;
+const TYPE_INT32: u32 = 5;
+const TYPE_FLOAT32: u32 = 6;
+const TYPE_BOOL: u32 = 7;
+const TYPE_STRING: u32 = 8;
+const TYPE_ARRAY: u32 = 9;
+const TYPE_UINT64: u32 = 10;
+const TYPE_INT64: u32 = 11;
+const TYPE_FLOAT64: u32 = 12;
+
+/// The subset of GGUF header metadata LocalCode uses for sizing decisions.
+///
+/// Architecture-specific keys (`llama.context_length`, `qwen2.block_count`, ...)
+/// are resolved against `general.architecture`, so callers never deal with the
+/// prefix.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GgufMetadata {
+    pub architecture: Option<String>,
+    pub name: Option<String>,
+    pub context_length: Option<u32>,
+    pub block_count: Option<u32>,
+    pub head_count: Option<u32>,
+    pub head_count_kv: Option<u32>,
+    pub embedding_length: Option<u32>,
+    /// Per-head key/value widths. Only present when they differ from
+    /// `embedding_length / head_count` (e.g. Gemma, DeepSeek MLA).
+    pub key_length: Option<u32>,
+    pub value_length: Option<u32>,
+    /// Total number of weights, summed over every tensor in the file.
+    pub parameter_count: u64,
+    /// llama.cpp `LLAMA_FTYPE_*` value describing the dominant quantization.
+    pub file_type: Option<u32>,
+    /// llama.cpp `LLAMA_POOLING_TYPE_*` of an embedding model.
+    pub pooling_type: Option<u32>,
+    pub chat_template: Option<String>,
+}
+
+impl GgufMetadata {
+    /// Parameter count in billions, if the file declared any tensors.
+    pub fn params_b(&self) -> Option<f64> {
+        (self.parameter_count > 0).then(|| self.parameter_count as f64 / 1e9)
+    }
+}
+
+/// llama.cpp `LLAMA_FTYPE_*` values and the quant labels used in file names.
+const FILE_TYPES: &[(u32, &str)] = &[
+    (0, "F32"),
+    (1, "F16"),
+    (2, "Q4_0"),
+    (3, "Q4_1"),
+    (7, "Q8_0"),
+    (8, "Q5_0"),
+    (9, "Q5_1"),
+    (10, "Q2_K"),
+    (11, "Q3_K_S"),
+    (12, "Q3_K_M"),
+    (13, "Q3_K_L"),
+    (14, "Q4_K_S"),
+    (15, "Q4_K_M"),
+    (16, "Q5_K_S"),
+    (17, "Q5_K_M"),
+    (18, "Q6_K"),
+    (19, "IQ2_XXS"),
+    (20, "IQ2_XS"),
+    (23, "IQ3_XXS"),
+    (24, "IQ1_S"),
+    (25, "IQ4_NL"),
+    (26, "IQ3_S"),
+    (27, "IQ3_M"),
+    (28, "IQ2_S"),
+    (29, "IQ2_M"),
+    (30, "IQ4_XS"),
+    (31, "IQ1_M"),
+    (32, "BF16"),
+];
+
+/// `LLAMA_FTYPE_*` value for a quant label (case-insensitive).
+pub fn file_type_for(quant: &str) -> Option<u32> {
+    FILE_TYPES
+        .iter()
+        .find(|(_, name)| name.eq_ignore_ascii_case(quant))
+        .map(|(ftype, _)| *ftype)
+}
+
+/// Quant label for an `LLAMA_FTYPE_*` value, e.g. 15 → `Q4_K_M`.
+pub fn file_type_name(ftype: u32) -> Option<&'static str> {
+    FILE_TYPES
+        .iter()
+        .find(|(t, _)| *t == ftype)
+        .map(|(_, name)| *name)
+}
+
+/// Read the metadata header of a GGUF file. Only the header and tensor index
+/// are read; tensor data is never touched, so this is cheap even for 40 GB files.
+pub fn read_metadata(path: &Path) -> Result<GgufMetadata> {
+    let file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    parse(&mut BufReader::new(file))
+        .with_context(|| format!("Invalid GGUF file {}", path.display()))
+}
+
+pub fn parse<R: Read>(r: &mut R) -> Result<GgufMetadata> {
+    if read_u32(r)? != GGUF_MAGIC {
+        anyhow::bail!("not a GGUF file (bad magic)");
+    }
+    let version = read_u32(r)?;
+    if version < 2 {
+        anyhow::bail!("unsupported GGUF version {}", version);
+    }
+    let tensor_count = read_u64(r)?;
+    let kv_count = read_u64(r)?;
+
+    let mut strings: HashMap<String, String> = HashMap::new();
+    let mut numbers: HashMap<String, u64> = HashMap::new();
+
+    for _ in 0..kv_count {
+        let key = read_string(r)?;
+        let value_type = read_u32(r)?;
+        match value_type {
+            TYPE_STRING => {
+                let value = read_string(r)?;
+                strings.insert(key, value);
+            }
+            TYPE_ARRAY => {
+                // Per-layer arrays (e.g. head_count_kv on hybrid models) are
+                // collapsed to their largest entry; everything else is skipped.
+                if let Some(max) = read_array_max(r)? {
+                    numbers.insert(key, max);
+                }
+            }
+            t => {
+                if let Some(v) = read_scalar(r, t)? {
+                    numbers.insert(key, v);
+                }
+            }
+        }
+    }
+
+    let mut parameter_count: u64 = 0;
+    for _ in 0..tensor_count {
+        skip_string(r)?;
+        let n_dims = read_u32(r)?;
+        let mut elements: u64 = 1;
+        for _ in 0..n_dims {
+            elements = elements.saturating_mul(read_u64(r)?);
+        }
+        let _ggml_type = read_u32(r)?;
+        let _offset = read_u64(r)?;
+        parameter_count = parameter_count.saturating_add(elements);
+    }
+
+    let architecture = strings.get("general.architecture").cloned();
+    let arch_u32 = |suffix: &str| -> Option<u32> {
+        let arch = architecture.as_deref()?;
+        numbers
+            .get(&format!("{}.{}", arch, suffix))
+            .and_then(|v| u32::try_from(*v).ok())
+    };
+
+    Ok(GgufMetadata {
+        context_length: arch_u32("context_length"),
+        block_count: arch_u32("block_count"),
+        head_count: arch_u32("attention.head_count"),
+        head_count_kv: arch_u32("attention.head_count_kv"),
+        embedding_length: arch_u32("embedding_length"),
+        key_length: arch_u32("attention.key_length"),
+        value_length: arch_u32("attention.value_length"),
+        pooling_type: arch_u32("pooling_type"),
+        name: strings.get("general.name").cloned(),
+        chat_template: strings.remove("tokenizer.chat_template"),
+        file_type: numbers
+            .get("general.file_type")
+            .and_then(|v| u32::try_from(*v).ok()),
+        parameter_count,
+        architecture,
+    })
+}
+
+fn read_u32<R: Read>(r: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string<R: Read>(r: &mut R) -> Result<String> {
+    let len = read_u64(r)?;
+    if len > MAX_STRING_LEN {
+        anyhow::bail!("string of {} bytes exceeds sanity limit", len);
+    }
+    let mut buf = vec![0u8; len as usize];
+    r.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn skip_string<R: Read>(r: &mut R) -> Result<()> {
+    let len = read_u64(r)?;
+    if len > MAX_STRING_LEN {
+        anyhow::bail!("string of {} bytes exceeds sanity limit", len);
+    }
+    skip(r, len)
+}
+
+/// Read and discard `len` bytes. Skips are mostly a few bytes (one vocabulary
+/// entry), which a `BufReader` serves from its buffer; seeking instead would
+/// throw the buffer away and cost a syscall per entry.
+fn skip<R: Read>(r: &mut R, len: u64) -> Result<()> {
+    let skipped = std::io::copy(&mut r.take(len), &mut std::io::sink())?;
+    if skipped < len {
+        anyhow::bail!("unexpected end of file");
+    }
+    Ok(())
+}
+
+fn scalar_size(value_type: u32) -> Option<u64> {
+    match value_type {
+        TYPE_UINT8 | TYPE_INT8 | TYPE_BOOL => Some(1),
+        TYPE_UINT16 | TYPE_INT16 => Some(2),
+        TYPE_UINT32 | TYPE_INT32 | TYPE_FLOAT32 => Some(4),
+        TYPE_UINT64 | TYPE_INT64 | TYPE_FLOAT64 => Some(8),
+        _ => None,
+    }
+}
+
+/// Read a scalar value. Integers are returned as `u64` (negative values are
+/// dropped); floats are consumed but not returned since no key we need is a float.
+fn read_scalar<R: Read>(r: &mut R, value_type: u32) -> Result<Option<u64>> {
+    let size = scalar_size(value_type)
+        .with_context(|| format!("unknown GGUF value type {}", value_type))?;
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf[..size as usize])?;
+    Ok(match value_type {
+        TYPE_UINT8 | TYPE_BOOL => Some(buf[0] as u64),
+        TYPE_INT8 => u64::try_from(buf[0] as i8).ok(),
+        TYPE_UINT16 => Some(u16::from_le_bytes([buf[0], buf[1]]) as u64),
+        TYPE_INT16 => u64::try_from(i16::from_le_bytes([buf[0], buf[1]])).ok(),
+        TYPE_UINT32 => Some(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as u64),
+        TYPE_INT32 => u64::try_from(i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])).ok(),
+        TYPE_UINT64 => Some(u64::from_le_bytes(buf)),
+        TYPE_INT64 => u64::try_from(i64::from_le_bytes(buf)).ok(),
+        _ => None,
+    })
+}
+
+/// Consume an array value, returning the maximum element for integer arrays.
+fn read_array_max<R: Read>(r: &mut R) -> Result<Option<u64>> {
+    let item_type = read_u32(r)?;
+    let len = read_u64(r)?;
+    if len > MAX_ARRAY_LEN {
+        anyhow::bail!("array of {} elements exceeds sanity limit", len);
+    }
+    match item_type {
+        TYPE_STRING => {
+            // Vocabularies are arrays of 100k+ strings; skip them entry by entry.
+            for _ in 0..len {
+                skip_string(r)?;
+            }
+            Ok(None)
+        }
+        TYPE_ARRAY => anyhow::bail!("nested GGUF arrays are not supported"),
+        TYPE_FLOAT32 | TYPE_FLOAT64 => {
+            let size = scalar_size(item_type).unwrap_or(4);
+            skip(r, size.checked_mul(len).context("GGUF array too large")?)?;
+            Ok(None)
+        }
+        t => {
+            let size = scalar_size(t).with_context(|| format!("unknown GGUF value type {}", t))?;
+            // Per-layer arrays are small; large integer arrays (token types)
+            // are skipped wholesale.
+            if len > 4096 {
+                skip(r, size.checked_mul(len).context("GGUF array too large")?)?;
+                return Ok(None);
+            }
+            let mut max = None;
+            for _ in 0..len {
+                if let Some(v) = read_scalar(r, t)? {
+                    max = Some(max.map_or(v, |m: u64| m.max(v)));
+                }
+            }
+            Ok(max)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Minimal GGUF writer for building test fixtures in memory.
+    pub(crate) struct GgufBuilder {
+        kvs: Vec<u8>,
+        kv_count: u64,
+        tensors: Vec<u8>,
+        tensor_count: u64,
+    }
+
+    impl GgufBuilder {
+        pub(crate) fn new() -> Self {
+            Self {
+                kvs: Vec::new(),
+                kv_count: 0,
+                tensors: Vec::new(),
+                tensor_count: 0,
+            }
+        }
+
+        fn push_str(buf: &mut Vec<u8>, s: &str) {
+            buf.extend((s.len() as u64).to_le_bytes());
+            buf.extend(s.as_bytes());
+        }
+
+        pub(crate) fn string(mut self, key: &str, value: &str) -> Self {
+            Self::push_str(&mut self.kvs, key);
+            self.kvs.extend(TYPE_STRING.to_le_bytes());
+            Self::push_str(&mut self.kvs, value);
+            self.kv_count += 1;
+            self
+        }
+
+        pub(crate) fn u32(mut self, key: &str, value: u32) -> Self {
+            Self::push_str(&mut self.kvs, key);
+            self.kvs.extend(TYPE_UINT32.to_le_bytes());
+            self.kvs.extend(value.to_le_bytes());
+            self.kv_count += 1;
+            self
+        }
+
+        pub(crate) fn string_array(mut self, key: &str, values: &[&str]) -> Self {
+            Self::push_str(&mut self.kvs, key);
+            self.kvs.extend(TYPE_ARRAY.to_le_bytes());
+            self.kvs.extend(TYPE_STRING.to_le_bytes());
+            self.kvs.extend((values.len() as u64).to_le_bytes());
+            for v in values {
+                Self::push_str(&mut self.kvs, v);
+            }
+            self.kv_count += 1;
+            self
+        }
+
+        pub(crate) fn i32_array(mut self, key: &str, values: &[i32]) -> Self {
+            Self::push_str(&mut self.kvs, key);
+            self.kvs.extend(TYPE_ARRAY.to_le_bytes());
+            self.kvs.extend(TYPE_INT32.to_le_bytes());
+            self.kvs.extend((values.len() as u64).to_le_bytes());
+            for v in values {
+                self.kvs.extend(v.to_le_bytes());
+            }
+            self.kv_count += 1;
+            self
+        }
+
+        pub(crate) fn tensor(mut self, name: &str, dims: &[u64]) -> Self {
+            Self::push_str(&mut self.tensors, name);
+            self.tensors.extend((dims.len() as u32).to_le_bytes());
+            for d in dims {
+                self.tensors.extend(d.to_le_bytes());
+            }
+            self.tensors.extend(0u32.to_le_bytes()); // GGML_TYPE_F32
+            self.tensors.extend(0u64.to_le_bytes());
+            self.tensor_count += 1;
+            self
+        }
+
+        pub(crate) fn build(self) -> Vec<u8> {
+            let mut out = Vec::new();
+            out.extend(GGUF_MAGIC.to_le_bytes());
+            out.extend(3u32.to_le_bytes());
+            out.extend(self.tensor_count.to_le_bytes());
+            out.extend(self.kv_count.to_le_bytes());
+            out.extend(self.kvs);
+            out.extend(self.tensors);
+            out
+        }
+    }
+
+    #[test]
+    fn test_parse_gguf_metadata() {
+        let bytes = GgufBuilder::new()
+            .string("general.architecture", "qwen2")
+            .string("general.name", "Qwen2.5 Coder 7B Instruct")
+            .u32("general.file_type", 15)
+            .u32("qwen2.context_length", 32768)
+            .u32("qwen2.block_count", 28)
+            .u32("qwen2.attention.head_count", 28)
+            .u32("qwen2.attention.head_count_kv", 4)
+            .u32("qwen2.embedding_length", 3584)
+            .u32("qwen2.pooling_type", 3)
+            .string_array("tokenizer.ggml.tokens", &["<s>", "</s>", "hello"])
+            .i32_array("tokenizer.ggml.token_type", &[3, 3, 1])
+            .string("tokenizer.chat_template", "{{ messages }}")
+            .tensor("token_embd.weight", &[3584, 152064])
+            .tensor("blk.0.attn_q.weight", &[3584, 3584])
+            .build();
+
+        let meta = parse(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(meta.architecture.as_deref(), Some("qwen2"));
+        assert_eq!(meta.name.as_deref(), Some("Qwen2.5 Coder 7B Instruct"));
+        assert_eq!(meta.context_length, Some(32768));
+        assert_eq!(meta.block_count, Some(28));
+        assert_eq!(meta.head_count, Some(28));
+        assert_eq!(meta.head_count_kv, Some(4));
+        assert_eq!(meta.embedding_length, Some(3584));
+        assert_eq!(meta.file_type, Some(15));
+        assert_eq!(meta.pooling_type, Some(3));
+        assert_eq!(meta.chat_template.as_deref(), Some("{{ messages }}"));
+        assert_eq!(meta.parameter_count, 3584 * 152064 + 3584 * 3584);
+    }
+
+    #[test]
+    fn test_parse_gguf_per_layer_arrays() {
+        // Hybrid models store head_count_kv per layer; take the widest layer.
+        let bytes = GgufBuilder::new()
+            .string("general.architecture", "jamba")
+            .i32_array("jamba.attention.head_count_kv", &[0, 8, 0, 8])
+            .build();
+        let meta = parse(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(meta.head_count_kv, Some(8));
+        assert_eq!(meta.params_b(), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_non_gguf() {
+        let err = parse(&mut Cursor::new(b"not a gguf file at all".to_vec())).unwrap_err();
+        assert!(err.to_string().contains("bad magic"));
+
+        // Cut off in the middle of a skipped vocabulary
+        let mut bytes = GgufBuilder::new()
+            .string("general.architecture", "qwen2")
+            .string_array("tokenizer.ggml.tokens", &["<s>", "</s>", "hello"])
+            .build();
+        bytes.truncate(bytes.len() - 2);
+        let err = parse(&mut Cursor::new(bytes)).unwrap_err();
+        assert!(err.to_string().contains("unexpected end of file"));
+
+        // An array length that would overflow the byte count
+        let mut builder = GgufBuilder::new().string("general.architecture", "qwen2");
+        GgufBuilder::push_str(&mut builder.kvs, "qwen2.attention.head_count_kv");
+        builder.kvs.extend(TYPE_ARRAY.to_le_bytes());
+        builder.kvs.extend(TYPE_INT64.to_le_bytes());
+        builder.kvs.extend((u64::MAX / 4).to_le_bytes());
+        builder.kv_count += 1;
+        let err = parse(&mut Cursor::new(builder.build())).unwrap_err();
+        assert!(format!("{:#}", err).contains("exceeds sanity limit"));
+    }
+}
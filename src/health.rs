@@ -0,0 +1,505 @@
+use crate::ui::InitConfig;
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long to wait on any single endpoint. The proxy is local, so anything
+/// slower than this is as good as down.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Snapshot of the server as reported by `localcode status`.
+#[derive(Serialize, Debug)]
+pub struct ServerStatus {
+    /// `docker`, `podman`, `nerdctl` or `native`.
+    pub mode: String,
+    /// Container/process state: `running`, `exited`, `not found`, ...
+    pub state: String,
+    pub uptime_secs: Option<u64>,
+    pub port: u16,
+    /// Whether llama-swap answered on `port`.
+    pub proxy_reachable: bool,
+    pub models: Vec<ModelStatus>,
+    pub healthy: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ModelStatus {
+    pub name: String,
+    /// llama-swap process state (`ready`, `starting`, ...) or `not loaded`.
+    pub state: String,
+    /// llama-server answered both `/health` and `/props`.
+    pub healthy: bool,
+    pub ctx_size: Option<u64>,
+    pub slots_total: Option<u64>,
+    pub slots_busy: Option<u64>,
+}
+
+/// `GET /running` on llama-swap.
+#[derive(Deserialize, Debug)]
+struct RunningResponse {
+    running: Vec<RunningModel>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RunningModel {
+    model: String,
+    state: String,
+}
+
+/// The parts of llama-server's `GET /props` we report.
+#[derive(Deserialize, Debug, Default)]
+struct Props {
+    #[serde(default)]
+    default_generation_settings: Option<GenerationSettings>,
+    #[serde(default)]
+    total_slots: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GenerationSettings {
+    n_ctx: Option<u64>,
+}
+
+/// Gather container/process state and per-model health for the configured server.
+pub async fn collect_status(config: &InitConfig) -> ServerStatus {
+    let (mode, state, uptime_secs) = if config.run_in_docker {
+        let runtime = config.container_runtime;
+        match crate::runner::inspect_container(runtime).await {
+            Some(c) => {
+                let uptime = (c.status == "running")
+                    .then(|| parse_timestamp(&c.started_at))
+                    .flatten()
+                    .and_then(|t| SystemTime::now().duration_since(t).ok())
+                    .map(|d| d.as_secs());
+                (runtime.binary().to_string(), c.status, uptime)
+            }
+            None => (runtime.binary().to_string(), "not found".to_string(), None),
+        }
+    } else {
+        match crate::native::running_pid().await {
+            Some(_) => {
+                let uptime = crate::native::started_at()
+                    .and_then(|t| SystemTime::now().duration_since(t).ok())
+                    .map(|d| d.as_secs());
+                ("native".to_string(), "running".to_string(), uptime)
+            }
+            _ => ("native".to_string(), "not running".to_string(), None),
+        }
+    };
+
+    let configured: Vec<String> = config.models.iter().map(|m| m.name.clone()).collect();
+    let base_url = format!("http://127.0.0.1:{}", config.port);
+    let probe = if state == "running" {
+        probe_models(&base_url, &configured).await
+    } else {
+        None
+    };
+    let proxy_reachable = probe.is_some();
+    let models = probe.unwrap_or_else(|| {
+        configured
+            .iter()
+            .map(|name| ModelStatus {
+                name: name.clone(),
+                state: "unknown".to_string(),
+                healthy: false,
+                ctx_size: None,
+                slots_total: None,
+                slots_busy: None,
+            })
+            .collect()
+    });
+    let healthy = server_healthy(proxy_reachable, &models);
+
+    ServerStatus {
+        mode,
+        state,
+        uptime_secs,
+        port: config.port,
+        proxy_reachable,
+        models,
+        healthy,
+    }
+}
+
+/// Whether the server works: the proxy answers and every model llama-swap
+/// has loaded is ready and answering. A model that isn't loaded (e.g. swapped
+/// out for a LoRA entry) is a normal state and doesn't count.
+fn server_healthy(proxy_reachable: bool, models: &[ModelStatus]) -> bool {
+    proxy_reachable
+        && models
+            .iter()
+            .all(|m| m.state == NOT_LOADED || (m.state == "ready" && m.healthy))
+}
+
+/// `ModelStatus::state` of a model llama-swap isn't running.
+const NOT_LOADED: &str = "not loaded";
+
+/// Query llama-swap for every configured model. `None` if the proxy itself
+/// doesn't answer.
+///
+/// Upstream endpoints are only hit for models llama-swap reports as running:
+/// requesting `/upstream/<model>/...` for an unloaded model would load it.
+pub async fn probe_models(base_url: &str, configured: &[String]) -> Option<Vec<ModelStatus>> {
+    let client = reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .build()
+        .ok()?;
+
+    let running: RunningResponse = client
+        .get(format!("{}/running", base_url))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+
+    let mut models = Vec::new();
+    for name in configured {
+        let state = running
+            .running
+            .iter()
+            .find(|r| &r.model == name)
+            .map(|r| r.state.clone());
+
+        let mut status = ModelStatus {
+            name: name.clone(),
+            state: state.clone().unwrap_or_else(|| NOT_LOADED.to_string()),
+            healthy: false,
+            ctx_size: None,
+            slots_total: None,
+            slots_busy: None,
+        };
+
+        if state.as_deref() == Some("ready") {
+            let upstream = format!("{}/upstream/{}", base_url, name);
+            let health_ok = client
+                .get(format!("{}/health", upstream))
+                .send()
+                .await
+                .is_ok_and(|r| r.status().is_success());
+
+            let props = get_json::<Props>(&client, &format!("{}/props", upstream)).await;
+            status.healthy = health_ok && props.is_some();
+            if let Some(props) = props {
+                status.ctx_size = props.default_generation_settings.and_then(|g| g.n_ctx);
+                status.slots_total = props.total_slots;
+            }
+            // `/slots` is disabled with --no-slots; busy count is best-effort.
+            if let Some(slots) =
+                get_json::<Vec<serde_json::Value>>(&client, &format!("{}/slots", upstream)).await
+            {
+                status.slots_busy = Some(
+                    slots
+                        .iter()
+                        .filter(|s| s["is_processing"].as_bool().unwrap_or(false))
+                        .count() as u64,
+                );
+                status.slots_total.get_or_insert(slots.len() as u64);
+            }
+        }
+
+        models.push(status);
+    }
+    Some(models)
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    url: &str,
+) -> Option<T> {
+    client
+        .get(url)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()
+}
+
+pub fn print_status(status: &ServerStatus) {
+    let uptime = status
+        .uptime_secs
+        .map(|s| format!(", up {}", format_duration(s)))
+        .unwrap_or_default();
+    let summary = format!(
+        "llama-swap ({}) is {}{} on port {}",
+        status.mode, status.state, uptime, status.port
+    );
+
+    if status.healthy {
+        println!("{} {}", style("✓").green().bold(), style(summary).green());
+    } else if status.state == "running" {
+        println!(
+            "{} {}",
+            style("⚠️").yellow().bold(),
+            style(summary).yellow()
+        );
+        if !status.proxy_reachable {
+            println!(
+                "  {}",
+                style("llama-swap is not answering yet. Run `localcode logs` to see why.").dim()
+            );
+        }
+    } else {
+        println!("{} {}", style("✗").red().bold(), style(summary).red());
+        println!("  {}", style("Run `localcode start` to launch it.").dim());
+        return;
+    }
+
+    println!();
+    println!(
+        "{:<50} | {:<12} | {:<8} | {:<8} | {:<10}",
+        style("Model").bold().cyan(),
+        style("State").bold().cyan(),
+        style("Health").bold().cyan(),
+        style("Context").bold().cyan(),
+        style("Slots busy").bold().cyan()
+    );
+    println!(
+        "{:-<50}-|-{:-<12}-|-{:-<8}-|-{:-<8}-|-{:-<10}",
+        "", "", "", "", ""
+    );
+    for m in &status.models {
+        let health = if m.healthy {
+            style("ok").green()
+        } else if m.state == "ready" {
+            style("failed").red()
+        } else {
+            style("-").dim()
+        };
+        let ctx = m.ctx_size.map(|c| c.to_string()).unwrap_or_default();
+        let slots = match (m.slots_busy, m.slots_total) {
+            (Some(busy), Some(total)) => format!("{}/{}", busy, total),
+            (None, Some(total)) => format!("?/{}", total),
+            _ => String::new(),
+        };
+        println!(
+            "{:<50} | {:<12} | {:<8} | {:<8} | {:<10}",
+            m.name, m.state, health, ctx, slots
+        );
+    }
+}
+
+/// Compact human duration: `45s`, `12m 5s`, `3h 20m`, `2d 4h`.
+fn format_duration(secs: u64) -> String {
+    let (d, h, m, s) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if d > 0 {
+        format!("{}d {}h", d, h)
+    } else if h > 0 {
+        format!("{}h {}m", h, m)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+/// Parse a container `StartedAt` timestamp.
+///
+/// Docker and nerdctl print RFC 3339 (`2024-05-01T10:00:00.123456789Z`);
+/// Podman prints Go's default layout (`2024-05-01 10:00:00.123 +0200 CEST`).
+/// Fractional seconds are ignored.
+pub(crate) fn parse_timestamp(s: &str) -> Option<SystemTime> {
+    let s = s.trim();
+    let date = s.get(0..10)?;
+    let time = s.get(11..19)?;
+    if !matches!(s.as_bytes().get(10), Some(b'T' | b' ')) {
+        return None;
+    }
+
+    let mut ymd = date.split('-').map(|p| p.parse::<i64>().ok());
+    let (y, mo, d) = (ymd.next()??, ymd.next()??, ymd.next()??);
+    let mut hms = time.split(':').map(|p| p.parse::<i64>().ok());
+    let (h, mi, sec) = (hms.next()??, hms.next()??, hms.next()??);
+
+    // Skip fractional seconds, then read the zone: `Z`, `+hh:mm` or ` +hhmm`.
+    let rest = s[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let rest = rest.trim_start();
+    let offset_secs = if rest.is_empty() || rest.starts_with('Z') {
+        0
+    } else {
+        let sign = match rest.as_bytes()[0] {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return None,
+        };
+        let digits: String = rest[1..]
+            .chars()
+            .filter(|c| c.is_ascii_digit())
+            .take(4)
+            .collect();
+        if digits.len() != 4 {
+            return None;
+        }
+        let oh: i64 = digits[0..2].parse().ok()?;
+        let om: i64 = digits[2..4].parse().ok()?;
+        sign * (oh * 3600 + om * 60)
+    };
+
+    let epoch = days_from_civil(y, mo, d) * 86400 + h * 3600 + mi * 60 + sec - offset_secs;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(epoch).ok()?))
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Minimal HTTP server for exercising clients against canned responses.
+    /// `routes` maps a request path to `(status, body)`; anything else is a 404.
+    /// Returns the base URL.
+    pub(crate) async fn serve(routes: Vec<(&'static str, u16, String)>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    break;
+                };
+                let routes = routes.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let (status, body) = routes
+                        .iter()
+                        .find(|(p, _, _)| *p == path)
+                        .map(|(_, s, b)| (*s, b.clone()))
+                        .unwrap_or((404, String::new()));
+                    let response = format!(
+                        "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_probe_models() {
+        let base = serve(vec![
+            (
+                "/running",
+                200,
+                r#"{"running":[{"model":"Qwen/Qwen2.5-Coder-7B-Instruct","state":"ready"},{"model":"Qwen/Qwen2.5-Coder-1.5B-Instruct","state":"starting"}]}"#.to_string(),
+            ),
+            ("/upstream/Qwen/Qwen2.5-Coder-7B-Instruct/health", 200, r#"{"status":"ok"}"#.to_string()),
+            (
+                "/upstream/Qwen/Qwen2.5-Coder-7B-Instruct/props",
+                200,
+                r#"{"default_generation_settings":{"n_ctx":32768},"total_slots":2}"#.to_string(),
+            ),
+            (
+                "/upstream/Qwen/Qwen2.5-Coder-7B-Instruct/slots",
+                200,
+                r#"[{"id":0,"is_processing":true},{"id":1,"is_processing":false}]"#.to_string(),
+            ),
+        ])
+        .await;
+
+        let configured = vec![
+            "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            "Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string(),
+            "unused-model".to_string(),
+        ];
+        let mut models = probe_models(&base, &configured).await.unwrap();
+
+        assert_eq!(
+            models[0],
+            ModelStatus {
+                name: configured[0].clone(),
+                state: "ready".to_string(),
+                healthy: true,
+                ctx_size: Some(32768),
+                slots_total: Some(2),
+                slots_busy: Some(1),
+            }
+        );
+        // Loading models aren't probed upstream (and so aren't healthy yet)
+        assert_eq!(models[1].state, "starting");
+        assert!(!models[1].healthy);
+        assert_eq!(models[1].ctx_size, None);
+        assert_eq!(models[2].state, "not loaded");
+        // Still loading: not healthy yet
+        assert!(!server_healthy(true, &models));
+        // A model llama-swap hasn't loaded is fine; the rest must answer
+        models.retain(|m| m.state != "starting");
+        assert!(server_healthy(true, &models));
+        assert!(!server_healthy(false, &[]));
+    }
+
+    #[tokio::test]
+    async fn test_probe_models_ready_but_failing() {
+        // Loaded, answers /health, but /props fails
+        let base = serve(vec![
+            (
+                "/running",
+                200,
+                r#"{"running":[{"model":"m","state":"ready"}]}"#.to_string(),
+            ),
+            ("/upstream/m/health", 200, r#"{"status":"ok"}"#.to_string()),
+            ("/upstream/m/props", 500, String::new()),
+        ])
+        .await;
+        let models = probe_models(&base, &["m".to_string()]).await.unwrap();
+        assert!(!models[0].healthy);
+        assert!(!server_healthy(true, &models));
+    }
+
+    #[tokio::test]
+    async fn test_probe_models_proxy_down() {
+        // Bind then drop to get a port nothing listens on
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let models = probe_models(&format!("http://127.0.0.1:{}", port), &["m".to_string()]).await;
+        assert!(models.is_none());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = UNIX_EPOCH + Duration::from_secs(1_714_557_600); // 2024-05-01T10:00:00Z
+        assert_eq!(
+            parse_timestamp("2024-05-01T10:00:00.123456789Z"),
+            Some(expected)
+        );
+        assert_eq!(parse_timestamp("2024-05-01T12:00:00+02:00"), Some(expected));
+        assert_eq!(
+            parse_timestamp("2024-05-01 12:00:00.5 +0200 CEST"),
+            Some(expected)
+        );
+        assert_eq!(parse_timestamp("0001-01-01T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("garbage"), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(725), "12m 5s");
+        assert_eq!(format_duration(3 * 3600 + 20 * 60), "3h 20m");
+        assert_eq!(format_duration(2 * 86400 + 4 * 3600 + 59), "2d 4h");
+    }
+}
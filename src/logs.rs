@@ -0,0 +1,384 @@
+use crate::runner::ContainerRuntime;
+use anyhow::{Context, Result};
+use console::style;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+/// Rotated logs kept next to the live one (`llama-swap.log.1` ... `.5`).
+const KEEP_ROTATED: usize = 5;
+
+/// Persisted server log, used when `persist_logs` is enabled.
+pub fn persisted_log_path(models_dir: &Path) -> PathBuf {
+    models_dir.join("logs").join("llama-swap.log")
+}
+
+/// Shift `log` → `log.1` → `log.2` ..., dropping the oldest. Called on every
+/// `start`, so each file holds exactly one server run.
+pub fn rotate(path: &Path) -> std::io::Result<()> {
+    let numbered = |i: usize| PathBuf::from(format!("{}.{}", path.display(), i));
+    let _ = std::fs::remove_file(numbered(KEEP_ROTATED));
+    for i in (1..KEEP_ROTATED).rev() {
+        if numbered(i).exists() {
+            std::fs::rename(numbered(i), numbered(i + 1))?;
+        }
+    }
+    if path.exists() {
+        std::fs::rename(path, numbered(1))?;
+    }
+    Ok(())
+}
+
+/// Severity of a log line. Ordered, so `--level warn` keeps warn and error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// Best-effort severity of a line. llama-swap tags its own lines
+    /// (`[INFO]`, `[WARN]`), llama-server writes `error:` / `warning:` or
+    /// `"level":"ERR"`; the first recognised word wins. Untagged lines are
+    /// treated as info.
+    fn of_line(line: &str) -> Level {
+        line.split(|c: char| !c.is_ascii_alphanumeric())
+            .find_map(|word| match word.to_ascii_lowercase().as_str() {
+                "error" | "err" | "fatal" | "panic" => Some(Level::Error),
+                "warn" | "warning" | "wrn" => Some(Level::Warn),
+                "info" | "inf" => Some(Level::Info),
+                "debug" | "dbg" | "trace" => Some(Level::Debug),
+                _ => None,
+            })
+            .unwrap_or(Level::Info)
+    }
+}
+
+/// Options for `localcode logs`.
+pub struct LogOptions {
+    pub follow: bool,
+    /// Lines of history to show before following; `0` means all of it.
+    pub tail: usize,
+    /// Only show lines newer than this (`10m`, `2h`, or an RFC 3339 timestamp).
+    pub since: Option<String>,
+    /// Only show lines mentioning this model. llama-swap prefixes upstream
+    /// output with the model ID.
+    pub model: Option<String>,
+    pub level: Option<Level>,
+}
+
+impl LogOptions {
+    fn matches(&self, line: &str) -> bool {
+        if let Some(model) = &self.model
+            && !line.contains(model.as_str())
+        {
+            return false;
+        }
+        match self.level {
+            Some(min) => Level::of_line(line) >= min,
+            None => true,
+        }
+    }
+}
+
+/// Parse `--since`: a relative duration (`90s`, `10m`, `2h`, `1d`) or an
+/// absolute timestamp.
+fn parse_since(since: &str) -> Option<SystemTime> {
+    let since = since.trim();
+    let unit = since.chars().last()?;
+    let secs_per_unit = match unit {
+        's' => Some(1),
+        'm' => Some(60),
+        'h' => Some(3600),
+        'd' => Some(86400),
+        _ => None,
+    };
+    if let Some(per) = secs_per_unit
+        && let Ok(n) = since[..since.len() - 1].parse::<u64>()
+    {
+        return SystemTime::now().checked_sub(Duration::from_secs(n.checked_mul(per)?));
+    }
+    crate::health::parse_timestamp(since)
+}
+
+/// Stream logs of the localcode container through the runtime's `logs` command.
+pub async fn stream_container(runtime: ContainerRuntime, opts: &LogOptions) -> Result<()> {
+    let mut args = vec!["logs".to_string()];
+    if opts.follow {
+        args.push("--follow".to_string());
+    }
+    args.push("--tail".to_string());
+    args.push(if opts.tail == 0 {
+        "all".to_string()
+    } else {
+        opts.tail.to_string()
+    });
+    if let Some(since) = &opts.since {
+        args.push("--since".to_string());
+        args.push(since.clone());
+    }
+    args.push(crate::runner::CONTAINER_NAME.to_string());
+
+    if opts.follow {
+        println!(
+            "{}",
+            style("Streaming live logs from localcode-llm container... (Press Ctrl+C to stop)")
+                .cyan()
+        );
+    }
+
+    let mut child = Command::new(runtime.binary())
+        .args(&args)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run `{} logs`", runtime.binary()))?;
+
+    // The container's stdout and stderr arrive on separate pipes; merge them
+    // line by line so filtering sees both.
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    for reader in [
+        Box::new(stdout) as Box<dyn tokio::io::AsyncRead + Unpin + Send>,
+        Box::new(stderr),
+    ] {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    loop {
+        tokio::select! {
+            line = rx.recv() => match line {
+                Some(line) => {
+                    if opts.matches(&line) {
+                        println!("{}", line);
+                    }
+                }
+                None => break,
+            },
+            _ = tokio::signal::ctrl_c() => {
+                let _ = child.kill().await;
+                break;
+            }
+        }
+    }
+    let _ = child.wait().await;
+    Ok(())
+}
+
+/// Stream a log file: the last `tail` lines, then (with `follow`) anything
+/// appended until Ctrl+C.
+///
+/// `timestamped` files (persisted container logs, written with
+/// `logs --timestamps`) support `--since`; plain llama-swap output does not.
+pub async fn stream_file(path: &Path, timestamped: bool, opts: &LogOptions) -> Result<()> {
+    let since = match &opts.since {
+        Some(_) if !timestamped => anyhow::bail!(
+            "--since needs timestamped logs; native llama-swap output has no timestamps."
+        ),
+        Some(s) => Some(parse_since(s).with_context(|| format!("Invalid --since value '{}'", s))?),
+        None => None,
+    };
+    let keep = |line: &str| {
+        opts.matches(line)
+            && since.is_none_or(|since| {
+                // Container timestamps look like `2024-05-01T10:00:00.123456789Z message`
+                line.split_whitespace()
+                    .next()
+                    .and_then(crate::health::parse_timestamp)
+                    .is_none_or(|t| t >= since)
+            })
+    };
+
+    if opts.follow {
+        println!(
+            "{}",
+            style(format!(
+                "Streaming live logs from {}... (Press Ctrl+C to stop)",
+                path.display()
+            ))
+            .cyan()
+        );
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let matching: Vec<&str> = content.lines().filter(|l| keep(l)).collect();
+    let start = if opts.tail == 0 {
+        0
+    } else {
+        matching.len().saturating_sub(opts.tail)
+    };
+    for line in &matching[start..] {
+        println!("{}", line);
+    }
+    if !opts.follow {
+        return Ok(());
+    }
+
+    let mut offset = content.len() as u64;
+    let mut partial = String::new();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+        let len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if len < offset {
+            // Log was truncated or rotated by a restart.
+            offset = 0;
+            partial.clear();
+        }
+        if len > offset {
+            use std::io::{Read, Seek, SeekFrom};
+            let mut f = std::fs::File::open(path)?;
+            f.seek(SeekFrom::Start(offset))?;
+            let mut buf = Vec::new();
+            f.read_to_end(&mut buf)?;
+            offset += buf.len() as u64;
+            partial.push_str(&String::from_utf8_lossy(&buf));
+
+            // Only complete lines can be filtered; keep the tail for next round.
+            while let Some(pos) = partial.find('\n') {
+                let line: String = partial.drain(..=pos).collect();
+                let line = line.trim_end_matches(['\n', '\r']);
+                if keep(line) {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep a copy of the container's output under the models dir. A detached
+/// `<runtime> logs --follow --timestamps` process appends to the file until
+/// the container goes away, so the log survives crashes and `rm`.
+pub fn persist_container_logs(runtime: ContainerRuntime, log_path: &Path) -> Result<()> {
+    if let Some(dir) = log_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    rotate(log_path)?;
+    let log = std::fs::File::create(log_path)
+        .with_context(|| format!("Failed to create log file {}", log_path.display()))?;
+
+    let mut cmd = std::process::Command::new(runtime.binary());
+    cmd.args([
+        "logs",
+        "--follow",
+        "--timestamps",
+        crate::runner::CONTAINER_NAME,
+    ])
+    .stdin(std::process::Stdio::null())
+    .stdout(log.try_clone()?)
+    .stderr(log);
+    crate::native::detach(&mut cmd);
+    cmd.spawn()
+        .with_context(|| format!("Failed to run `{} logs`", runtime.binary()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(model: Option<&str>, level: Option<Level>) -> LogOptions {
+        LogOptions {
+            follow: false,
+            tail: 0,
+            since: None,
+            model: model.map(str::to_string),
+            level,
+        }
+    }
+
+    #[test]
+    fn test_level_of_line() {
+        assert_eq!(
+            Level::of_line("[INFO] Request 127.0.0.1 \"POST /v1/chat/completions\""),
+            Level::Info
+        );
+        assert_eq!(
+            Level::of_line("[WARN] upstream took 30s to become ready"),
+            Level::Warn
+        );
+        assert_eq!(
+            Level::of_line("llama_model_load: error: failed to load model"),
+            Level::Error
+        );
+        assert_eq!(Level::of_line("[DEBUG] swapping model"), Level::Debug);
+        // The tag wins over words later in the message; untagged lines are info
+        assert_eq!(Level::of_line("[INFO] retrying after error"), Level::Info);
+        assert_eq!(
+            Level::of_line("srv  update_slots: all slots are idle"),
+            Level::Info
+        );
+    }
+
+    #[test]
+    fn test_log_filters() {
+        let line = "[Qwen/Qwen2.5-Coder-7B-Instruct] srv  load_model: error: out of memory";
+        assert!(opts(Some("Qwen/Qwen2.5-Coder-7B-Instruct"), None).matches(line));
+        assert!(!opts(Some("Qwen/Qwen2.5-Coder-1.5B-Instruct"), None).matches(line));
+        assert!(opts(None, Some(Level::Warn)).matches(line));
+        assert!(!opts(None, Some(Level::Warn)).matches("[INFO] health check passed"));
+        assert!(opts(None, None).matches("anything"));
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = SystemTime::now();
+        let ten_min = parse_since("10m").unwrap();
+        let elapsed = now.duration_since(ten_min).unwrap().as_secs();
+        assert!((599..=601).contains(&elapsed));
+        assert!(parse_since("2024-05-01T10:00:00Z").is_some());
+        assert!(parse_since("yesterday").is_none());
+        // Overflowing offsets are rejected, not a panic
+        assert!(parse_since("99999999999999999d").is_none());
+    }
+
+    #[test]
+    fn test_rotate_keeps_bounded_history() {
+        let dir = std::env::temp_dir().join(format!("localcode-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("llama-swap.log");
+
+        for run in 0..(KEEP_ROTATED + 2) {
+            rotate(&log).unwrap();
+            std::fs::write(&log, format!("run {}", run)).unwrap();
+        }
+
+        let last = KEEP_ROTATED + 1;
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            format!("run {}", last)
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("llama-swap.log.1")).unwrap(),
+            format!("run {}", last - 1)
+        );
+        assert!(
+            dir.join(format!("llama-swap.log.{}", KEEP_ROTATED))
+                .exists()
+        );
+        assert!(
+            !dir.join(format!("llama-swap.log.{}", KEEP_ROTATED + 1))
+                .exists()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
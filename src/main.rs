@@ -1,13 +1,23 @@
 mod config;
+mod disk;
+mod doctor;
+mod download;
+mod gguf;
+mod health;
+mod logs;
+mod manage;
 mod models;
+mod native;
 mod profiling;
 mod runner;
+mod swap_config;
 mod ui;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Args as ClapArgs, Parser, Subcommand};
 use console::style;
 use self_update::cargo_crate_version;
+use std::io::IsTerminal;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "LocalCode - OpenCode Local LLM Setup", long_about = None)]
@@ -23,15 +33,140 @@ pub enum Commands {
     /// Starts the localcode update engine to fetch the newest github release
     Upgrade,
     /// List all natively available downloaded models across standard caches
-    Ls,
+    Ls(LsArgs),
+    /// Download a model into the models directory without starting the server
+    Pull(PullArgs),
+    /// Delete a downloaded model (GGUF plus its Hugging Face cache entries) from the models directory
+    Rm(RmArgs),
+    /// Reclaim space from weights no known localcode.json uses and from stale partial downloads
+    Gc(GcArgs),
     /// Start the background LLM server using saved configuration
-    Start,
-    /// Show the real-time loading status of the background model
-    Status,
+    Start(StartArgs),
+    /// Show server state and per-model health (exits non-zero when unhealthy)
+    Status(StatusArgs),
+    /// Show the background LLM server logs
+    Logs(LogsArgs),
     /// Stop the background LLM server
     Stop,
     /// Show configuration instructions for OpenCode and Claude Code
     Info,
+    /// Show the detected hardware profile and the models ranked for it
+    Profile(ProfileArgs),
+    /// Diagnose the environment: container runtime, GPU, port, disk, tokens and client configs
+    Doctor,
+    /// Inspect the generated llama-swap configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the llama-swap YAML that `start` would use, without starting anything
+    Render,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct LsArgs {
+    /// Order rows by this column (sizes, parameters and context largest first)
+    #[arg(long, value_enum, default_value_t)]
+    pub sort: models::SortKey,
+
+    /// Only show models from caches whose label contains this (e.g. ollama, "lm studio")
+    #[arg(long)]
+    pub source: Option<String>,
+
+    /// Only show models whose name matches this regular expression (case-insensitive)
+    #[arg(long)]
+    pub filter: Option<String>,
+
+    /// Print machine-readable JSON instead of a table
+    #[arg(long, default_value_t = false, conflicts_with = "dedupe")]
+    pub json: bool,
+
+    /// Replace duplicate copies of the same weights with hard links to one file
+    #[arg(long)]
+    pub dedupe: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct PullArgs {
+    /// Model name (e.g. Qwen/Qwen2.5-Coder-7B-Instruct) or GGUF repo (e.g. bartowski/Qwen2.5-Coder-7B-Instruct-GGUF)
+    pub model: String,
+
+    /// Quantization to download
+    #[arg(short, long, default_value = "Q4_K_M")]
+    pub quant: String,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct RmArgs {
+    /// Configured model name, GGUF file name, Hugging Face repo (org/name) or path
+    pub model: String,
+
+    /// Remove even if the active configuration uses it
+    #[arg(short, long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct GcArgs {
+    /// Only report what would be deleted
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Delete without asking for confirmation
+    #[arg(short, long, default_value_t = false)]
+    pub yes: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct StartArgs {
+    /// Use only local GGUFs and never contact Hugging Face (also `offline` in localcode.json)
+    #[arg(long, default_value_t = false)]
+    pub offline: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct StatusArgs {
+    /// Print machine-readable JSON instead of a table
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ProfileArgs {
+    /// Number of recommended models to list (0 for all)
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub top: usize,
+
+    /// Print the full profile and every recommendation as JSON
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct LogsArgs {
+    /// Keep streaming new output until Ctrl+C
+    #[arg(short, long, default_value_t = false)]
+    pub follow: bool,
+
+    /// Number of lines of history to show (0 for all)
+    #[arg(short = 'n', long, default_value_t = 50)]
+    pub tail: usize,
+
+    /// Only show logs newer than a duration (10m, 2h, 1d) or RFC 3339 timestamp
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only show lines for this model
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    /// Minimum severity to show
+    #[arg(long, value_enum)]
+    pub level: Option<logs::Level>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -48,10 +183,42 @@ pub struct InitArgs {
     #[arg(short, long)]
     pub models: Option<Vec<String>>,
 
-    /// Do not use Docker to run llama.cpp (assumes native installation)
+    /// Do not use Docker; run llama-swap and llama-server binaries from PATH instead
     #[arg(long, default_value_t = false)]
     pub no_docker: bool,
 
+    /// Container runtime to use (auto-detected when omitted)
+    #[arg(long, value_enum)]
+    pub runtime: Option<runner::ContainerRuntime>,
+
+    /// Keep server logs in <models_dir>/logs, rotated on every start
+    #[arg(long, default_value_t = false)]
+    pub persist_logs: bool,
+
+    /// Never contact Hugging Face; choose only from GGUFs already on disk
+    #[arg(long, default_value_t = false)]
+    pub offline: bool,
+
+    /// Attach a small same-family draft model to the primary for speculative decoding
+    #[arg(long, default_value_t = false)]
+    pub draft: bool,
+
+    /// GPUs to use, by index (e.g. 0,1). Docker gets only these; all GPUs when omitted
+    #[arg(long, value_delimiter = ',')]
+    pub gpus: Vec<u32>,
+
+    /// Use this much VRAM (GB) instead of the detected amount; 0 means no GPU
+    #[arg(long)]
+    pub vram_gb: Option<f32>,
+
+    /// Use this much RAM (GB) instead of the detected amount
+    #[arg(long)]
+    pub ram_gb: Option<f32>,
+
+    /// Use this acceleration backend instead of the detected one
+    #[arg(long, value_enum)]
+    pub backend: Option<profiling::Backend>,
+
     /// Specify the port for the LLM API to bind to
     #[arg(short, long, default_value_t = 8080)]
     pub port: u16,
@@ -61,25 +228,96 @@ pub struct InitArgs {
     pub models_dir: Option<std::path::PathBuf>,
 }
 
+impl InitArgs {
+    /// The hardware overrides given on the command line.
+    fn hardware_override(&self) -> profiling::HardwareOverride {
+        profiling::HardwareOverride {
+            vram_gb: self.vram_gb,
+            ram_gb: self.ram_gb,
+            backend: self.backend,
+            ..Default::default()
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
     match args.command {
-        Commands::Ls => {
+        Commands::Ls(ls_args) => {
             let config = config::load_localcode_config().await.unwrap_or_default();
-
-            println!(
-                "{}",
-                style("🔍 Scanning local system for cached weights...").dim()
-            );
+            let filter = ls_args
+                .filter
+                .as_deref()
+                .map(|pattern| {
+                    regex::RegexBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .with_context(|| format!("Invalid --filter pattern '{}'", pattern))
+                })
+                .transpose()?;
+
+            if !ls_args.json {
+                println!(
+                    "{}",
+                    style("🔍 Scanning local system for cached weights...").dim()
+                );
+            }
 
             let models_dir_expanded = shellexpand::tilde(&config.models_dir).to_string();
             let models_dir = std::path::PathBuf::from(models_dir_expanded);
 
-            let all_models = models::find_all_local_models(&models_dir);
+            // --source also skips scanning caches that can't match
+            let mut scanners = models::default_scanners(&models_dir, &config.extra_model_dirs);
+            if let Some(source) = ls_args.source.as_deref().map(str::to_lowercase) {
+                scanners.retain(|s| s.source().to_lowercase().contains(&source));
+            }
+            let mut all_models = models::scan_all(&scanners);
+            for m in &mut all_models {
+                m.load_metadata();
+            }
+
+            // Files the active configuration would serve
+            let in_config: std::collections::HashSet<_> = config
+                .models
+                .iter()
+                .filter_map(|m| runner::find_existing_copy(m, &models_dir, &all_models))
+                .filter_map(|(path, _)| path.canonicalize().ok())
+                .collect();
+            let is_active = |m: &models::DiscoveredModel| {
+                m.path.canonicalize().is_ok_and(|p| in_config.contains(&p))
+            };
+
+            let found_any = !all_models.is_empty();
+            let all_models = models::filter_and_sort(
+                all_models,
+                ls_args.source.as_deref(),
+                filter.as_ref(),
+                ls_args.sort,
+            );
+
+            if ls_args.json {
+                let entries = all_models
+                    .iter()
+                    .map(|m| {
+                        let mut entry = serde_json::to_value(m)?;
+                        entry["in_config"] = serde_json::Value::Bool(is_active(m));
+                        Ok(entry)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
 
             if all_models.is_empty() {
+                if found_any {
+                    println!(
+                        "\n{} No models match the given filters.",
+                        style("ℹ").yellow()
+                    );
+                    return Ok(());
+                }
                 println!(
                     "\n{} No cached models found on the system.",
                     style("ℹ").yellow()
@@ -93,62 +331,329 @@ async fn main() -> Result<()> {
                 style("✓").green().bold(),
                 all_models.len()
             );
+            // Wide enough for the longest name, so nothing is cut off
+            let name_width = all_models
+                .iter()
+                .map(|m| m.display_name().chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(4);
             println!(
-                "{:<60} | {:<12} | {:<20}",
+                "{:<name_width$} | {:<10} | {:<8} | {:<8} | {:<8} | {:<10} | {:<6} | {}",
                 style("Name").bold().cyan(),
+                style("Arch").bold().cyan(),
+                style("Params").bold().cyan(),
+                style("Quant").bold().cyan(),
+                style("Context").bold().cyan(),
                 style("Size").bold().cyan(),
+                style("Active").bold().cyan(),
                 style("Cache Source").bold().cyan()
             );
-            println!("{:-<60}-|-{:-<12}-|-{:-<20}", "", "", "");
-
-            for m in all_models {
-                let size_str = human_bytes::human_bytes(m.size_bytes as f64);
-                // Truncate names that are too long
-                let mut print_name = m.name.clone();
-                if print_name.len() > 58 {
-                    print_name.truncate(55);
-                    print_name.push_str("...");
-                }
+            println!(
+                "{:-<name_width$}-|-{:-<10}-|-{:-<8}-|-{:-<8}-|-{:-<8}-|-{:-<10}-|-{:-<6}-|-{:-<20}",
+                "", "", "", "", "", "", "", ""
+            );
+
+            for m in &all_models {
+                // Header values when the file parses as GGUF, blank otherwise
+                let params_str = m
+                    .params_b()
+                    .map(|p| format!("{:.1}B", p))
+                    .unwrap_or_default();
+                let ctx_str = m.context_length.map(|c| c.to_string()).unwrap_or_default();
                 println!(
-                    "{:<60} | {:<12} | {:<20}",
-                    print_name,
-                    size_str,
-                    style(m.source).dim()
+                    "{:<name_width$} | {:<10} | {:<8} | {:<8} | {:<8} | {:<10} | {:<6} | {}",
+                    m.display_name(),
+                    m.architecture.as_deref().unwrap_or_default(),
+                    params_str,
+                    m.quant.as_deref().unwrap_or_default(),
+                    ctx_str,
+                    human_bytes::human_bytes(m.size_bytes as f64),
+                    if is_active(m) { "✓" } else { "" },
+                    style(&m.source).dim()
                 );
             }
             println!();
+
+            let duplicates = models::find_duplicates(&all_models);
+            let wasted: u64 = duplicates.iter().map(|g| g.wasted_bytes()).sum();
+            if wasted == 0 {
+                return Ok(());
+            }
+            println!(
+                "{} Duplicate weights: {} could be reclaimed",
+                style("⚠").yellow(),
+                style(human_bytes::human_bytes(wasted as f64)).bold()
+            );
+            for group in duplicates.iter().filter(|g| g.wasted_bytes() > 0) {
+                println!(
+                    "  {} × {} ({} wasted)",
+                    group.distinct_files,
+                    human_bytes::human_bytes(group.size_bytes as f64),
+                    human_bytes::human_bytes(group.wasted_bytes() as f64)
+                );
+                for copy in &group.copies {
+                    println!(
+                        "    {} {}",
+                        style(format!("[{}]", copy.source)).dim(),
+                        copy.path.display()
+                    );
+                }
+            }
+
+            if !ls_args.dedupe {
+                println!(
+                    "\n  Run `localcode ls --dedupe` to replace the copies with hard links.\n"
+                );
+                return Ok(());
+            }
+
+            let mut freed = 0;
+            for group in duplicates.iter().filter(|g| g.wasted_bytes() > 0) {
+                let (group_freed, skipped) = models::dedupe(group);
+                freed += group_freed;
+                for reason in skipped {
+                    println!("  {} Skipped {}", style("ℹ").yellow(), reason);
+                }
+            }
+            println!(
+                "\n{} Freed {} by hard-linking duplicate weights.\n",
+                style("✓").green().bold(),
+                human_bytes::human_bytes(freed as f64)
+            );
+        }
+        Commands::Config {
+            command: ConfigCommand::Render,
+        } => {
+            let config = config::load_localcode_config().await?;
+            let models_dir =
+                std::path::PathBuf::from(shellexpand::tilde(&config.models_dir).to_string());
+
+            // Nothing is downloaded: models resolve to files already on disk,
+            // otherwise to the --hf-repo fallback llama-server would use.
+            // Offline configs must resolve fully, exactly as `start` would.
+            let resolved = if config.offline {
+                runner::resolve_local_models(&config.models, &models_dir, &config.extra_model_dirs)?
+            } else {
+                std::collections::HashMap::new()
+            };
+            let hw_profile = profiling::profile_hardware(&config.hardware)
+                .await
+                .ok()
+                .map(|mut p| {
+                    p.select_gpus(&config.gpu_devices());
+                    p
+                });
+            // Sized exactly as `start` would, but nothing is saved
+            let llama_server_args =
+                runner::resized_server_args(&config, &models_dir, &resolved, hw_profile.as_ref())
+                    .or_else(|| config.llama_server_args.clone());
+            let target = if config.run_in_docker {
+                runner::ServerTarget::Docker
+            } else {
+                let llama_server =
+                    native::find_binary("llama-server", config.llama_server_path.as_deref())
+                        .unwrap_or_else(|_| std::path::PathBuf::from("llama-server"));
+                runner::ServerTarget::Native {
+                    llama_server,
+                    gpus: config.gpu_devices(),
+                }
+            };
+
+            let yaml = runner::generate_llama_swap_yaml(
+                &config.models,
+                &models_dir,
+                llama_server_args.as_ref(),
+                &resolved,
+                hw_profile.as_ref(),
+                &target,
+            )
+            .await?;
+            print!("{}", yaml);
+        }
+        Commands::Pull(pull_args) => {
+            let config = config::load_localcode_config().await.unwrap_or_default();
+            let models_dir =
+                std::path::PathBuf::from(shellexpand::tilde(&config.models_dir).to_string());
+            let path =
+                manage::pull(&config, &models_dir, &pull_args.model, &pull_args.quant).await?;
+            println!(
+                "{} {} {}",
+                style("✓").green().bold(),
+                style(&pull_args.model).magenta(),
+                style(format!("is at {}", path.display())).dim()
+            );
+        }
+        Commands::Rm(rm_args) => {
+            let config = config::load_localcode_config().await.unwrap_or_default();
+            let models_dir =
+                std::path::PathBuf::from(shellexpand::tilde(&config.models_dir).to_string());
+            manage::rm(&config, &models_dir, &rm_args.model, rm_args.force)?;
+        }
+        Commands::Gc(gc_args) => {
+            let config = config::load_localcode_config().await.unwrap_or_default();
+            let models_dir =
+                std::path::PathBuf::from(shellexpand::tilde(&config.models_dir).to_string());
+            manage::gc(&models_dir, gc_args.dry_run, gc_args.yes)?;
+        }
+        Commands::Doctor => {
+            if !doctor::run_doctor().await {
+                std::process::exit(1);
+            }
         }
-        Commands::Status => {
-            runner::show_status().await?;
+        Commands::Status(status_args) => {
+            let config = config::load_localcode_config().await.unwrap_or_default();
+            let status = health::collect_status(&config).await;
+            if status_args.json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                health::print_status(&status);
+            }
+            if !status.healthy {
+                std::process::exit(1);
+            }
+        }
+        Commands::Profile(profile_args) => {
+            // Same view of the hardware as `start`: overrides and GPU selection apply
+            let config = config::load_localcode_config().await.unwrap_or_default();
+            let mut profile = profiling::profile_hardware(&config.hardware).await?;
+            profile.select_gpus(&config.gpu_devices());
+            if profile_args.json {
+                let mut entry = serde_json::to_value(&profile)?;
+                if !config.hardware.is_empty() {
+                    entry["hardware_overrides"] = serde_json::to_value(&config.hardware)?;
+                }
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                profiling::print_profile(&profile, profile_args.top);
+                if !config.hardware.is_empty() {
+                    println!(
+                        "\n  {}",
+                        style("(with hardware overrides from localcode.json)").dim()
+                    );
+                }
+            }
+        }
+        Commands::Logs(logs_args) => {
+            let config = config::load_localcode_config().await.unwrap_or_default();
+            let models_dir =
+                std::path::PathBuf::from(shellexpand::tilde(&config.models_dir).to_string());
+            let opts = logs::LogOptions {
+                follow: logs_args.follow,
+                tail: logs_args.tail,
+                since: logs_args.since,
+                model: logs_args.model,
+                level: logs_args.level,
+            };
+
+            if !config.run_in_docker {
+                let log_path = native::log_file(&config, &models_dir);
+                if !log_path.exists() {
+                    println!(
+                        "{} {}",
+                        style("ℹ").yellow(),
+                        style("No native llama-swap log yet. Run `localcode start` first.")
+                            .yellow()
+                    );
+                    return Ok(());
+                }
+                logs::stream_file(&log_path, false, &opts).await?;
+            } else if runner::inspect_container(config.container_runtime)
+                .await
+                .is_some()
+            {
+                logs::stream_container(config.container_runtime, &opts).await?;
+            } else {
+                // The container is gone (`stop`), but a persisted copy may remain.
+                let log_path = logs::persisted_log_path(&models_dir);
+                if !config.persist_logs || !log_path.exists() {
+                    println!(
+                        "{} {}",
+                        style("ℹ").yellow(),
+                        style("The localcode-llm container does not exist. Run `localcode start` first.")
+                            .yellow()
+                    );
+                    return Ok(());
+                }
+                println!(
+                    "{}",
+                    style(format!(
+                        "Container not found; showing {}",
+                        log_path.display()
+                    ))
+                    .dim()
+                );
+                logs::stream_file(&log_path, true, &opts).await?;
+            }
         }
         Commands::Stop => {
-            runner::stop_server().await?;
+            let config = config::load_localcode_config().await.unwrap_or_default();
+            if config.run_in_docker {
+                runner::stop_server(config.container_runtime).await?;
+            } else {
+                native::stop_server_native().await?;
+            }
         }
-        Commands::Start => {
-            let config = config::load_localcode_config().await?;
+        Commands::Start(start_args) => {
+            let mut config = config::load_localcode_config().await?;
+            let offline = start_args.offline || config.offline;
+            let model_names = config
+                .models
+                .iter()
+                .map(|m| m.name.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
             if config.run_in_docker {
-                let model_names = config
-                    .models
-                    .iter()
-                    .map(|m| m.name.clone())
-                    .collect::<Vec<_>>()
-                    .join(", ");
                 println!(
-                    "{} {} with llama-swap in Docker on port {}...",
+                    "{} {} with llama-swap in {} on port {}...",
                     style("🐳 Starting").blue(),
                     style(&model_names).magenta().bold(),
+                    config.container_runtime.label(),
+                    style(config.port).yellow()
+                );
+            } else {
+                println!(
+                    "{} {} with native llama-swap on port {}...",
+                    style("🚀 Starting").blue(),
+                    style(&model_names).magenta().bold(),
                     style(config.port).yellow()
                 );
+            }
 
-                let models_dir_expanded = shellexpand::tilde(&config.models_dir).to_string();
-                let models_dir = std::path::PathBuf::from(models_dir_expanded);
+            let models_dir_expanded = shellexpand::tilde(&config.models_dir).to_string();
+            let models_dir = std::path::PathBuf::from(models_dir_expanded);
 
-                if !models_dir.exists() {
-                    tokio::fs::create_dir_all(&models_dir).await.unwrap_or(());
-                }
+            if !models_dir.exists() {
+                tokio::fs::create_dir_all(&models_dir).await.unwrap_or(());
+            }
 
-                let downloaded_files = match runner::download_models(&config.models, &models_dir).await {
+            let downloaded_files = if offline {
+                match runner::resolve_local_models(
+                    &config.models,
+                    &models_dir,
+                    &config.extra_model_dirs,
+                ) {
+                    Ok(files) => files,
+                    Err(e) => {
+                        eprintln!("\n{} {}", style("❌").red().bold(), e);
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                let interactive = std::io::stdin().is_terminal();
+                match runner::download_models(
+                    &config.models,
+                    &models_dir,
+                    &config.extra_model_dirs,
+                    interactive,
+                )
+                .await
+                {
                     Ok(files) => files,
+                    Err(e) if e.is::<download::InsufficientSpace>() => {
+                        eprintln!("\n{} {}", style("❌").red().bold(), e);
+                        std::process::exit(1);
+                    }
                     Err(e) => {
                         println!(
                             "\n{} {}",
@@ -163,18 +668,50 @@ async fn main() -> Result<()> {
                         );
                         std::collections::HashMap::new()
                     }
-                };
+                }
+            };
 
-                // Quick hardware profile for per-model arg generation.
-                // This is lightweight (no model DB scan) and only used to
-                // determine VRAM / backend so secondary models get correct settings.
-                let hw_profile = profiling::profile_hardware().await.ok();
+            // Quick hardware profile for per-model arg generation.
+            // This is lightweight (no model DB scan) and only used to
+            // determine VRAM / backend so secondary models get correct settings.
+            let hw_profile = profiling::profile_hardware(&config.hardware)
+                .await
+                .ok()
+                .map(|mut p| {
+                    p.select_gpus(&config.gpu_devices());
+                    p
+                });
+
+            // Size the primary from the headers of the files just resolved.
+            // Only this run uses the result; localcode.json stays as written.
+            if let Some(args) = runner::resized_server_args(
+                &config,
+                &models_dir,
+                &downloaded_files,
+                hw_profile.as_ref(),
+            ) {
+                let ctx = |a: Option<&ui::LlamaServerArgs>| {
+                    a.and_then(|a| a.ctx_size)
+                        .map(|c| c.to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                };
+                println!(
+                    "{} {}",
+                    style("📐 Sized llama_server_args from the GGUF headers for this run:").cyan(),
+                    style(format!(
+                        "ctx_size {} → {}",
+                        ctx(config.llama_server_args.as_ref()),
+                        ctx(Some(&args))
+                    ))
+                    .dim()
+                );
+                config.llama_server_args = Some(args);
+            }
 
+            if config.run_in_docker {
                 if let Err(e) = runner::start_llama_swap_docker(
-                    &config.models,
+                    &config,
                     &models_dir,
-                    config.port,
-                    config.llama_server_args.as_ref(),
                     &downloaded_files,
                     hw_profile.as_ref(),
                 )
@@ -182,30 +719,48 @@ async fn main() -> Result<()> {
                 {
                     println!(
                         "\n{} {}",
-                        style("❌ Failed to start Docker container:").red().bold(),
+                        style(format!(
+                            "❌ Failed to start {} container:",
+                            config.container_runtime.label()
+                        ))
+                        .red()
+                        .bold(),
                         e
                     );
                     std::process::exit(1);
                 }
-                println!("{} {}", style("➜").cyan(), style("The model server is starting in the background. \n  Run `localcode status` to view its loading progress!").white().bold());
-                println!(
-                    "  {}",
-                    style("Run `localcode stop` later when you want to shut down the server.")
-                        .dim()
-                );
-            } else {
-                let model_names = config
-                    .models
-                    .iter()
-                    .map(|m| m.name.clone())
-                    .collect::<Vec<_>>()
-                    .join(", ");
+                if config.persist_logs {
+                    let log_path = logs::persisted_log_path(&models_dir);
+                    if let Err(e) =
+                        logs::persist_container_logs(config.container_runtime, &log_path)
+                    {
+                        println!(
+                            "{} {}",
+                            style("⚠️  Could not persist container logs:").yellow(),
+                            e
+                        );
+                    }
+                }
+            } else if let Err(e) = native::start_llama_swap_native(
+                &config,
+                &models_dir,
+                &downloaded_files,
+                hw_profile.as_ref(),
+            )
+            .await
+            {
                 println!(
-                    "{} {} natively... (Not implemented in zero-config)",
-                    style("🚀 Starting").blue(),
-                    style(&model_names).magenta().bold()
+                    "\n{} {}",
+                    style("❌ Failed to start native llama-swap:").red().bold(),
+                    e
                 );
+                std::process::exit(1);
             }
+            println!("{} {}", style("➜").cyan(), style("The model server is starting in the background. \n  Run `localcode logs -f` to view its loading progress, or `localcode status` to check readiness!").white().bold());
+            println!(
+                "  {}",
+                style("Run `localcode stop` later when you want to shut down the server.").dim()
+            );
         }
         Commands::Upgrade => {
             println!("{}", style("Checking for updates...").dim());
@@ -239,13 +794,29 @@ async fn main() -> Result<()> {
                 "{}",
                 style("🔍 Profiling hardware capabilities via llmfit...").dim()
             );
-            let profile = profiling::profile_hardware().await?;
+            // Overrides from the existing config carry over; flags win
+            let hardware = config::load_localcode_config()
+                .await
+                .map(|c| c.hardware)
+                .unwrap_or_default()
+                .merged_with(&init_args.hardware_override());
+            let mut profile = profiling::profile_hardware(&hardware).await?;
+            profile.select_gpus(&init_args.gpus);
+            let gpus = if profile.gpu_count > 1 {
+                format!(" across {} GPUs", profile.gpu_count)
+            } else {
+                String::new()
+            };
             println!(
-                "{} {}GB VRAM, {}GB RAM",
+                "{} {}GB VRAM{}, {}GB RAM",
                 style("✓ Hardware Profile Detected:").green().bold(),
                 style(profile.vram_gb).yellow(),
+                gpus,
                 style(profile.ram_gb).yellow()
             );
+            if !hardware.is_empty() {
+                println!("  {}", style("(with hardware overrides)").dim());
+            }
 
             // 2. Determine Optimal Model
             let recommended_model = profile
@@ -256,14 +827,27 @@ async fn main() -> Result<()> {
 
             // 3. User Interaction
             println!();
-            let (user_config, is_project_scoped) =
+            let (mut user_config, is_project_scoped) =
                 ui::prompt_user(&init_args, &profile, recommended_model)?;
+            user_config.hardware = hardware;
             println!();
 
             // 4. Configure OpenCode
             let provider_url = format!("http://localhost:{}/v1", user_config.port);
-            config::configure_opencode(&user_config.models, &provider_url, is_project_scoped)
-                .await?;
+            let models_dir =
+                std::path::PathBuf::from(shellexpand::tilde(&user_config.models_dir).to_string());
+            let metadata = runner::local_model_metadata(
+                &user_config.models,
+                &models_dir,
+                &std::collections::HashMap::new(),
+            );
+            config::configure_opencode(
+                &user_config.models,
+                &metadata,
+                &provider_url,
+                is_project_scoped,
+            )
+            .await?;
 
             // 5. Save configuration to disk
             config::save_localcode_config(&user_config, is_project_scoped).await?;
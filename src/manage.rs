@@ -0,0 +1,728 @@
+use crate::runner;
+use crate::ui::{InitConfig, ModelSelection};
+use anyhow::{Context, Result};
+use console::style;
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Download one model into `models_dir` without starting a server.
+///
+/// `model` is a model name (`Qwen/Qwen2.5-Coder-7B-Instruct`) resolved through
+/// the usual GGUF publishers, or a GGUF repo (`bartowski/...-GGUF`) tried first.
+pub async fn pull(
+    config: &InitConfig,
+    models_dir: &Path,
+    model: &str,
+    quant: &str,
+) -> Result<PathBuf> {
+    if config.offline {
+        anyhow::bail!("Offline mode is enabled in localcode.json; nothing can be downloaded.");
+    }
+    std::fs::create_dir_all(models_dir)
+        .with_context(|| format!("Failed to create {}", models_dir.display()))?;
+
+    let selection = ModelSelection {
+        name: model.to_string(),
+        quant: Some(quant.to_string()),
+        ..Default::default()
+    };
+    let interactive = std::io::stdin().is_terminal();
+    let files = runner::download_models(
+        std::slice::from_ref(&selection),
+        models_dir,
+        &config.extra_model_dirs,
+        interactive,
+    )
+    .await?;
+    files
+        .get(model)
+        .cloned()
+        .with_context(|| format!("No {} GGUF found for {} on Hugging Face", quant, model))
+}
+
+/// What `localcode rm` deletes.
+#[derive(Debug, PartialEq)]
+enum RemoveTarget {
+    /// A single GGUF, possibly a snapshot entry in the Hugging Face cache layout.
+    File(PathBuf),
+    /// A whole `models--org--repo` cache directory.
+    Repo(PathBuf),
+}
+
+impl RemoveTarget {
+    fn path(&self) -> &Path {
+        match self {
+            RemoveTarget::File(p) | RemoveTarget::Repo(p) => p,
+        }
+    }
+}
+
+/// Delete a model from `models_dir`: a configured model name, a GGUF file name
+/// (with or without `.gguf`), a Hugging Face repo (`org/name`) or a path.
+/// Models used by the active config are only removed with `force`.
+pub fn rm(config: &InitConfig, models_dir: &Path, model: &str, force: bool) -> Result<()> {
+    let target = resolve_target(config, models_dir, model)?;
+
+    let inside = match (target.path().canonicalize(), models_dir.canonicalize()) {
+        (Ok(t), Ok(d)) => t.starts_with(&d) || target.path().starts_with(models_dir),
+        _ => false,
+    };
+    if !inside {
+        anyhow::bail!(
+            "{} is outside {}; LocalCode only deletes models it manages. Remove it with the app that owns it (Ollama, LM Studio, ...).",
+            target.path().display(),
+            models_dir.display()
+        );
+    }
+
+    if !force && let Some(name) = referenced_by(config, models_dir, &target) {
+        anyhow::bail!(
+            "{} is used by model `{}` in the active localcode.json. Pass --force to remove it anyway.",
+            target.path().display(),
+            name
+        );
+    }
+
+    let freed = remove(&target, models_dir)?;
+    println!(
+        "{} Removed {} ({} freed)",
+        style("🗑️ ").red(),
+        style(target.path().display()).bold(),
+        human_bytes::human_bytes(freed as f64)
+    );
+    Ok(())
+}
+
+fn resolve_target(config: &InitConfig, models_dir: &Path, model: &str) -> Result<RemoveTarget> {
+    let as_path = PathBuf::from(shellexpand::tilde(model).to_string());
+    if as_path.is_file() {
+        return Ok(RemoveTarget::File(as_path));
+    }
+
+    if let Some(m) = config
+        .models
+        .iter()
+        .find(|m| m.name.eq_ignore_ascii_case(model))
+    {
+        return runner::find_model_file(m, models_dir, &HashMap::new())
+            .filter(|p| p.is_file())
+            .map(RemoveTarget::File)
+            .with_context(|| format!("{} is configured but not downloaded", m.name));
+    }
+
+    if model.contains('/') {
+        let repo_dir = models_dir.join(format!("models--{}", model.replace('/', "--")));
+        if repo_dir.is_dir() {
+            return Ok(RemoveTarget::Repo(repo_dir));
+        }
+    }
+
+    let matches: Vec<PathBuf> =
+        crate::models::scan_directory_for_gguf(models_dir, "LocalCode Config")
+            .into_iter()
+            .filter(|m| {
+                m.name.eq_ignore_ascii_case(model)
+                    || m.name
+                        .strip_suffix(".gguf")
+                        .is_some_and(|stem| stem.eq_ignore_ascii_case(model))
+            })
+            .map(|m| m.path)
+            .collect();
+    match matches.as_slice() {
+        [] => anyhow::bail!(
+            "No model named {} in {} (see `localcode ls`)",
+            model,
+            models_dir.display()
+        ),
+        [path] => Ok(RemoveTarget::File(path.clone())),
+        many => anyhow::bail!(
+            "{} matches several files; pass one of these paths instead:\n{}",
+            model,
+            many.iter()
+                .map(|p| format!("  - {}", p.display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    }
+}
+
+/// Name of the configured model whose weights (or projector or adapter)
+/// `target` would delete.
+fn referenced_by(config: &InitConfig, models_dir: &Path, target: &RemoveTarget) -> Option<String> {
+    let target_path = target.path().canonicalize().ok()?;
+    runner::with_drafts(&config.models).iter().find_map(|m| {
+        let hit = runner::model_files(m, models_dir, &HashMap::new())
+            .into_iter()
+            .filter_map(|used| used.canonicalize().ok())
+            .any(|used| match target {
+                RemoveTarget::File(_) => used == target_path,
+                RemoveTarget::Repo(_) => used.starts_with(&target_path),
+            });
+        hit.then(|| m.name.clone())
+    })
+}
+
+/// `models--org--repo` directory when `path` is a snapshot entry in the
+/// Hugging Face cache layout under `models_dir`.
+fn hf_repo_dir(path: &Path, models_dir: &Path) -> Option<PathBuf> {
+    let rel = path.strip_prefix(models_dir).ok()?;
+    let mut parts = rel.components();
+    let repo = parts.next()?.as_os_str().to_str()?;
+    let snapshots = parts.next()?.as_os_str();
+    (repo.starts_with("models--") && snapshots == "snapshots").then(|| models_dir.join(repo))
+}
+
+/// Bytes held by regular files under `dir` (symlinks not followed).
+fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Delete `target`, returning the bytes freed. A snapshot entry takes its
+/// blob with it unless another snapshot still points there, and a repo whose
+/// last snapshot file is gone is removed entirely (blobs, refs, partials).
+fn remove(target: &RemoveTarget, models_dir: &Path) -> Result<u64> {
+    let path = match target {
+        RemoveTarget::Repo(dir) => {
+            let freed = dir_size(dir);
+            std::fs::remove_dir_all(dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+            return Ok(freed);
+        }
+        RemoveTarget::File(path) => path,
+    };
+
+    let Some(repo_dir) = hf_repo_dir(path, models_dir) else {
+        let freed = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+        return Ok(freed);
+    };
+
+    let blob = path.canonicalize().ok();
+    let is_symlink = std::fs::symlink_metadata(path)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false);
+    let mut freed = if is_symlink {
+        0
+    } else {
+        std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    };
+    std::fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+
+    let snapshots = repo_dir.join("snapshots");
+    let remaining: Vec<PathBuf> = walkdir::WalkDir::new(&snapshots)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_type().is_dir())
+        .map(|e| e.into_path())
+        .collect();
+
+    if remaining.is_empty() {
+        freed += dir_size(&repo_dir);
+        std::fs::remove_dir_all(&repo_dir)
+            .with_context(|| format!("Failed to remove {}", repo_dir.display()))?;
+        return Ok(freed);
+    }
+
+    if let Some(blob) =
+        blob.filter(|b| b.starts_with(repo_dir.join("blobs").canonicalize().unwrap_or_default()))
+        && !remaining
+            .iter()
+            .any(|p| p.canonicalize().ok().as_ref() == Some(&blob))
+    {
+        freed += std::fs::metadata(&blob).map(|m| m.len()).unwrap_or(0);
+        std::fs::remove_file(&blob)?;
+    }
+
+    // Drop now-empty snapshot directories (nested file names, the commit dir)
+    let mut dir = path.parent();
+    while let Some(d) = dir {
+        if d == snapshots || std::fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+    Ok(freed)
+}
+
+/// Partial downloads touched more recently than this may still be in progress.
+const PARTIAL_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Something `localcode gc` can delete.
+#[derive(Debug)]
+struct Garbage {
+    path: PathBuf,
+    bytes: u64,
+    reason: &'static str,
+}
+
+/// Prune the Hugging Face cache layout under `models_dir`: snapshot entries
+/// no known `localcode.json` uses, blobs nothing keeps, and stale partial
+/// downloads. Plain GGUF files placed by hand are never touched. Deleting
+/// asks first unless `yes`.
+pub fn gc(models_dir: &Path, dry_run: bool, yes: bool) -> Result<()> {
+    let configs = crate::config::known_config_paths();
+    if configs.is_empty() && !dry_run {
+        anyhow::bail!(
+            "No localcode.json found, so every model would count as unused. Run `localcode init` first, or use --dry-run to inspect."
+        );
+    }
+    let referenced = referenced_files(&configs)?;
+    let garbage = find_garbage(models_dir, &referenced, SystemTime::now());
+
+    println!(
+        "{} Checked {} against {} config(s):",
+        style("🔍").dim(),
+        models_dir.display(),
+        configs.len()
+    );
+    for path in &configs {
+        println!("  {}", style(path.display()).dim());
+    }
+    println!(
+        "{}",
+        style(
+            "Projects not opened with localcode since gc was added are not listed, and their models are not protected."
+        )
+        .dim()
+    );
+    if garbage.is_empty() {
+        println!("{} Nothing to clean up.", style("✓").green().bold());
+        return Ok(());
+    }
+
+    let total: u64 = garbage.iter().map(|g| g.bytes).sum();
+    for g in &garbage {
+        println!(
+            "  {:>10}  {:<22} {}",
+            human_bytes::human_bytes(g.bytes as f64),
+            style(g.reason).yellow(),
+            g.path.strip_prefix(models_dir).unwrap_or(&g.path).display()
+        );
+    }
+
+    if dry_run {
+        println!(
+            "\n{} {} reclaimable. Run without --dry-run to delete.",
+            style("ℹ").cyan(),
+            style(human_bytes::human_bytes(total as f64)).bold()
+        );
+        return Ok(());
+    }
+
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!("Not deleting without confirmation. Re-run with --yes, or --dry-run.");
+        }
+        let proceed = inquire::Confirm::new(&format!(
+            "Delete these files ({})?",
+            human_bytes::human_bytes(total as f64)
+        ))
+        .with_default(false)
+        .prompt()?;
+        if !proceed {
+            println!("{} Nothing deleted.", style("ℹ").cyan());
+            return Ok(());
+        }
+    }
+
+    for g in &garbage {
+        std::fs::remove_file(&g.path)
+            .with_context(|| format!("Failed to remove {}", g.path.display()))?;
+    }
+    remove_empty_repos(models_dir);
+    println!(
+        "\n{} Freed {}",
+        style("✓").green().bold(),
+        style(human_bytes::human_bytes(total as f64)).bold()
+    );
+    Ok(())
+}
+
+/// Canonical paths of every model file (with projectors and adapters) the given
+/// configs resolve to. Going through `canonicalize` turns snapshot symlinks
+/// into the blobs they keep. Fails on a config that can't be read, since its
+/// models would otherwise look unused.
+fn referenced_files(configs: &[PathBuf]) -> Result<HashSet<PathBuf>> {
+    let mut referenced = HashSet::new();
+    for path in configs {
+        let config = crate::config::load_config_file(path).with_context(|| {
+            format!(
+                "Failed to load {}; fix or remove it before running gc",
+                path.display()
+            )
+        })?;
+        let models_dir = PathBuf::from(shellexpand::tilde(&config.models_dir).to_string());
+        referenced.extend(
+            runner::with_drafts(&config.models)
+                .iter()
+                .flat_map(|m| runner::model_files(m, &models_dir, &HashMap::new()))
+                .filter_map(|p| p.canonicalize().ok()),
+        );
+    }
+    Ok(referenced)
+}
+
+/// `models--*` repo directories under `models_dir` (and `hub/`, where
+/// llama-server puts `--hf-repo` downloads with `HF_HOME=/models`).
+fn hf_repo_dirs(models_dir: &Path) -> Vec<PathBuf> {
+    [models_dir.to_path_buf(), models_dir.join("hub")]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_dir()
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("models--"))
+        })
+        .collect()
+}
+
+fn find_garbage(models_dir: &Path, referenced: &HashSet<PathBuf>, now: SystemTime) -> Vec<Garbage> {
+    let mut garbage = Vec::new();
+    for repo in hf_repo_dirs(models_dir) {
+        // Snapshot entries and the file each one resolves to
+        let entries: Vec<(PathBuf, Option<PathBuf>)> =
+            walkdir::WalkDir::new(repo.join("snapshots"))
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| !e.file_type().is_dir())
+                .map(|e| {
+                    let target = e.path().canonicalize().ok();
+                    (e.into_path(), target)
+                })
+                .collect();
+
+        let mut kept = HashSet::new();
+        for (entry, target) in &entries {
+            match target {
+                Some(t) if referenced.contains(t) => {
+                    kept.insert(t.clone());
+                }
+                _ => garbage.push(Garbage {
+                    bytes: std::fs::symlink_metadata(entry)
+                        .ok()
+                        .filter(|m| m.is_file())
+                        .map(|m| m.len())
+                        .unwrap_or(0),
+                    path: entry.clone(),
+                    reason: "unreferenced snapshot",
+                }),
+            }
+        }
+
+        let Ok(blobs) = std::fs::read_dir(repo.join("blobs")) else {
+            continue;
+        };
+        for blob in blobs.filter_map(|e| e.ok()) {
+            let path = blob.path();
+            let Ok(meta) = blob.metadata() else {
+                continue;
+            };
+            if path.extension().is_some_and(|e| e == "incomplete") {
+                let stale = meta
+                    .modified()
+                    .ok()
+                    .and_then(|m| now.duration_since(m).ok())
+                    .is_some_and(|age| age > PARTIAL_GRACE);
+                if stale {
+                    garbage.push(Garbage {
+                        path,
+                        bytes: meta.len(),
+                        reason: "partial download",
+                    });
+                }
+                continue;
+            }
+            let real = path.canonicalize().unwrap_or_else(|_| path.clone());
+            if kept.contains(&real) {
+                continue;
+            }
+            let linked = entries.iter().any(|(_, t)| t.as_ref() == Some(&real));
+            garbage.push(Garbage {
+                path,
+                bytes: meta.len(),
+                reason: if linked {
+                    "unreferenced blob"
+                } else {
+                    "orphaned blob"
+                },
+            });
+        }
+    }
+    garbage
+}
+
+/// Drop repo directories left without any snapshot file, and empty snapshot dirs.
+fn remove_empty_repos(models_dir: &Path) {
+    for repo in hf_repo_dirs(models_dir) {
+        let has_files = walkdir::WalkDir::new(repo.join("snapshots"))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .any(|e| !e.file_type().is_dir());
+        let has_partials = std::fs::read_dir(repo.join("blobs"))
+            .map(|mut d| d.next().is_some())
+            .unwrap_or(false);
+        if !has_files && !has_partials {
+            let _ = std::fs::remove_dir_all(&repo);
+            continue;
+        }
+        // Commit directories emptied by the cleanup
+        if let Ok(commits) = std::fs::read_dir(repo.join("snapshots")) {
+            for commit in commits.filter_map(|e| e.ok()) {
+                let _ = std::fs::remove_dir(commit.path());
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// `models--org--repo` with one blob per file name, linked from snapshot `c1`.
+    fn hf_repo(models_dir: &Path, files: &[(&str, &[u8])]) -> PathBuf {
+        let repo = models_dir.join("models--org--repo-GGUF");
+        std::fs::create_dir_all(repo.join("blobs")).unwrap();
+        std::fs::create_dir_all(repo.join("snapshots/c1")).unwrap();
+        std::fs::create_dir_all(repo.join("refs")).unwrap();
+        std::fs::write(repo.join("refs/main"), "c1").unwrap();
+        for (i, (name, data)) in files.iter().enumerate() {
+            let blob = format!("blob{}", i);
+            std::fs::write(repo.join("blobs").join(&blob), data).unwrap();
+            std::os::unix::fs::symlink(
+                format!("../../blobs/{}", blob),
+                repo.join("snapshots/c1").join(name),
+            )
+            .unwrap();
+        }
+        repo
+    }
+
+    fn temp_models_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("localcode-rm-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_rm_hf_cache_entry() {
+        let models_dir = temp_models_dir("hf");
+        let repo = hf_repo(
+            &models_dir,
+            &[("a-Q4_K_M.gguf", b"aaaa"), ("a-Q8_0.gguf", b"bbbbbbbb")],
+        );
+        let config = InitConfig::default();
+
+        // One of two quants: its blob goes, the repo stays
+        rm(&config, &models_dir, "a-Q8_0", false).unwrap();
+        assert!(!repo.join("snapshots/c1/a-Q8_0.gguf").exists());
+        assert!(!repo.join("blobs/blob1").exists());
+        assert!(repo.join("blobs/blob0").exists());
+
+        // Last file: the whole repo directory goes, refs included
+        let target = resolve_target(&config, &models_dir, "a-Q4_K_M.gguf").unwrap();
+        assert_eq!(remove(&target, &models_dir).unwrap(), 4 + 2); // blob + refs/main
+        assert!(!repo.exists());
+
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[test]
+    fn test_rm_refuses_configured_model() {
+        let models_dir = temp_models_dir("configured");
+        let repo = hf_repo(&models_dir, &[("a-Q4_K_M.gguf", b"aaaa")]);
+        let config = InitConfig {
+            models: vec![ModelSelection {
+                name: "in-use".to_string(),
+                path: Some(
+                    repo.join("snapshots/c1/a-Q4_K_M.gguf")
+                        .to_string_lossy()
+                        .to_string(),
+                ),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        // By repo, file name or config name: all refused without --force
+        for name in ["org/repo-GGUF", "a-Q4_K_M", "in-use"] {
+            let err = rm(&config, &models_dir, name, false)
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains("used by model `in-use`"), "{}", err);
+        }
+        assert!(repo.exists());
+
+        rm(&config, &models_dir, "org/repo-GGUF", true).unwrap();
+        assert!(!repo.exists());
+
+        assert!(rm(&config, &models_dir, "nothing-here", false).is_err());
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[test]
+    fn test_rm_refuses_outside_models_dir() {
+        let models_dir = temp_models_dir("outside");
+        let elsewhere = temp_models_dir("outside-other").join("x.gguf");
+        std::fs::write(&elsewhere, b"x").unwrap();
+
+        let err = rm(
+            &InitConfig::default(),
+            &models_dir,
+            &elsewhere.to_string_lossy(),
+            true,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("only deletes models it manages"));
+        assert!(elsewhere.exists());
+
+        std::fs::remove_dir_all(elsewhere.parent().unwrap()).unwrap();
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[test]
+    fn test_gc_finds_unreferenced_orphaned_and_partial() {
+        let models_dir = temp_models_dir("gc");
+        let repo = hf_repo(
+            &models_dir,
+            &[("a-Q4_K_M.gguf", b"aaaa"), ("a-Q8_0.gguf", b"bbbbbbbb")],
+        );
+        std::fs::write(repo.join("blobs/orphan"), b"ooo").unwrap();
+        std::fs::write(repo.join("blobs/fresh.incomplete"), b"pp").unwrap();
+        let unused = hf_repo_dir_named(&models_dir, "models--old--unused-GGUF");
+
+        let referenced: HashSet<PathBuf> = [repo
+            .join("snapshots/c1/a-Q4_K_M.gguf")
+            .canonicalize()
+            .unwrap()]
+        .into();
+
+        // A fresh partial may still be downloading; in two hours it's stale
+        let now = SystemTime::now();
+        let mut found = find_garbage(&models_dir, &referenced, now);
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        let summary: Vec<(String, u64, &str)> = found
+            .iter()
+            .map(|g| {
+                (
+                    g.path
+                        .strip_prefix(&models_dir)
+                        .unwrap()
+                        .display()
+                        .to_string(),
+                    g.bytes,
+                    g.reason,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "models--old--unused-GGUF/blobs/blob0".to_string(),
+                    1,
+                    "unreferenced blob"
+                ),
+                (
+                    "models--old--unused-GGUF/snapshots/c1/x.gguf".to_string(),
+                    0,
+                    "unreferenced snapshot"
+                ),
+                (
+                    "models--org--repo-GGUF/blobs/blob1".to_string(),
+                    8,
+                    "unreferenced blob"
+                ),
+                (
+                    "models--org--repo-GGUF/blobs/orphan".to_string(),
+                    3,
+                    "orphaned blob"
+                ),
+                (
+                    "models--org--repo-GGUF/snapshots/c1/a-Q8_0.gguf".to_string(),
+                    0,
+                    "unreferenced snapshot"
+                ),
+            ]
+        );
+        let later = now + Duration::from_secs(2 * 60 * 60);
+        assert!(
+            find_garbage(&models_dir, &referenced, later)
+                .iter()
+                .any(|g| g.reason == "partial download")
+        );
+
+        // Deleting leaves the referenced quant and drops the emptied repo
+        for g in &found {
+            std::fs::remove_file(&g.path).unwrap();
+        }
+        remove_empty_repos(&models_dir);
+        assert!(!unused.exists());
+        assert!(repo.join("snapshots/c1/a-Q4_K_M.gguf").is_file());
+        assert!(find_garbage(&models_dir, &referenced, now).is_empty());
+
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[test]
+    fn test_gc_keeps_hf_repo_fallback_in_hub() {
+        let models_dir = temp_models_dir("gc-hub");
+        // What llama-server's --hf-repo left behind, named unlike our own downloads
+        let repo = models_dir.join("hub/models--bartowski--phi-3-mini-4k-instruct-GGUF");
+        std::fs::create_dir_all(repo.join("blobs")).unwrap();
+        std::fs::create_dir_all(repo.join("snapshots/c1")).unwrap();
+        std::fs::write(repo.join("blobs/blob0"), b"wwww").unwrap();
+        std::os::unix::fs::symlink(
+            "../../blobs/blob0",
+            repo.join("snapshots/c1/phi-3-mini-4k-instruct-q4.gguf"),
+        )
+        .unwrap();
+
+        let config = crate::ui::InitConfig {
+            models: vec![crate::ui::ModelSelection {
+                name: "microsoft/phi-3-mini-4k-instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            }],
+            models_dir: models_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let config_path = models_dir.join("localcode.json");
+        std::fs::write(&config_path, serde_json::to_string(&config).unwrap()).unwrap();
+
+        let referenced = referenced_files(std::slice::from_ref(&config_path)).unwrap();
+        assert!(referenced.contains(&repo.join("blobs/blob0").canonicalize().unwrap()));
+        assert!(find_garbage(&models_dir, &referenced, SystemTime::now()).is_empty());
+
+        // A config that doesn't load stops gc instead of unprotecting its models
+        let broken = models_dir.join("broken.json");
+        std::fs::write(&broken, "{ not json").unwrap();
+        let err = referenced_files(&[config_path, broken.clone()]).unwrap_err();
+        assert!(err.to_string().contains(&broken.display().to_string()));
+
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    fn hf_repo_dir_named(models_dir: &Path, name: &str) -> PathBuf {
+        let repo = models_dir.join(name);
+        std::fs::create_dir_all(repo.join("blobs")).unwrap();
+        std::fs::create_dir_all(repo.join("snapshots/c1")).unwrap();
+        std::fs::write(repo.join("blobs/blob0"), b"x").unwrap();
+        std::os::unix::fs::symlink("../../blobs/blob0", repo.join("snapshots/c1/x.gguf")).unwrap();
+        repo
+    }
+}
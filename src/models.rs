@@ -1,15 +1,116 @@
+use serde::Serialize;
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct DiscoveredModel {
     pub name: String,
-    #[allow(dead_code)]
     pub path: PathBuf,
     pub size_bytes: u64,
     pub source: String, // e.g. "LM Studio", "Ollama", "LocalCode"
+    pub kind: FileKind,
+    // GGUF header fields, filled in by `load_metadata`
+    pub architecture: Option<String>,
+    pub parameter_count: Option<u64>,
+    pub quant: Option<String>,
+    pub context_length: Option<u32>,
+}
+
+/// What a discovered file is. Only `Model` files can be served on their own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    #[default]
+    Model,
+    /// Vision projector (`mmproj-*.gguf` or an Ollama projector layer).
+    Projector,
+    /// LoRA adapter from an Ollama manifest.
+    Adapter,
+    /// Self-contained `.llamafile`; llama-server can't load it.
+    Llamafile,
+}
+
+impl DiscoveredModel {
+    /// Name as `ls` shows it: auxiliary layers are tagged, since they share
+    /// their model's name.
+    pub fn display_name(&self) -> String {
+        match self.kind {
+            // Loose mmproj files already say so in their name
+            FileKind::Projector if self.source != "Ollama" => self.name.clone(),
+            FileKind::Projector => format!("{} (projector)", self.name),
+            FileKind::Adapter => format!("{} (adapter)", self.name),
+            FileKind::Model | FileKind::Llamafile => self.name.clone(),
+        }
+    }
+
+    /// Fill in the header fields from the file's GGUF metadata. Files that
+    /// don't parse keep them empty.
+    pub fn load_metadata(&mut self) {
+        let Ok(meta) = crate::gguf::read_metadata(&self.path) else {
+            return;
+        };
+        self.parameter_count = (meta.parameter_count > 0).then_some(meta.parameter_count);
+        self.quant = meta.file_type.map(|t| {
+            crate::gguf::file_type_name(t).map_or_else(|| format!("type {}", t), String::from)
+        });
+        self.context_length = meta.context_length;
+        self.architecture = meta.architecture;
+    }
+
+    /// Parameter count in billions, if known.
+    pub fn params_b(&self) -> Option<f64> {
+        self.parameter_count.map(|p| p as f64 / 1e9)
+    }
+}
+
+/// Column `localcode ls --sort` orders by. Numeric columns sort largest first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Params,
+    Context,
+    Arch,
+    Quant,
+    Source,
+}
+
+/// Keep models whose source label contains `source` and whose name matches
+/// `filter` (both case-insensitive), ordered by `sort`.
+pub fn filter_and_sort(
+    models: Vec<DiscoveredModel>,
+    source: Option<&str>,
+    filter: Option<&regex::Regex>,
+    sort: SortKey,
+) -> Vec<DiscoveredModel> {
+    let source = source.map(str::to_lowercase);
+    let mut kept: Vec<_> = models
+        .into_iter()
+        .filter(|m| {
+            source
+                .as_ref()
+                .is_none_or(|s| m.source.to_lowercase().contains(s))
+        })
+        .filter(|m| filter.is_none_or(|re| re.is_match(&m.name)))
+        .collect();
+    let lower = |s: &Option<String>| s.as_deref().unwrap_or_default().to_lowercase();
+    kept.sort_by(|a, b| {
+        let by_key = match sort {
+            SortKey::Name => std::cmp::Ordering::Equal,
+            SortKey::Size => b.size_bytes.cmp(&a.size_bytes),
+            SortKey::Params => b.parameter_count.cmp(&a.parameter_count),
+            SortKey::Context => b.context_length.cmp(&a.context_length),
+            SortKey::Arch => lower(&a.architecture).cmp(&lower(&b.architecture)),
+            SortKey::Quant => lower(&a.quant).cmp(&lower(&b.quant)),
+            SortKey::Source => a.source.cmp(&b.source),
+        };
+        by_key.then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    kept
 }
 
 /// Recursively scans a given directory for `.gguf` files.
@@ -27,43 +128,72 @@ pub fn scan_directory_for_gguf(dir: &Path, source_name: &str) -> Vec<DiscoveredM
             && let Ok(metadata) = fs::metadata(path)
             && let Some(file_name) = path.file_name().and_then(|n| n.to_str())
         {
+            let kind = if crate::runner::is_mmproj(file_name) {
+                FileKind::Projector
+            } else {
+                FileKind::Model
+            };
             found.push(DiscoveredModel {
                 name: file_name.to_string(),
                 path: path.to_path_buf(),
                 size_bytes: metadata.len(),
                 source: source_name.to_string(),
+                kind,
+                ..Default::default()
             });
         }
     }
     found
 }
 
-/// Scans standard Ollama manifest structures to locate valid GGUF blobs.
-pub fn scan_ollama_cache() -> Vec<DiscoveredModel> {
-    let mut found = Vec::new();
-
-    // Resolve ~/.ollama
-    let ollama_dir = match shellexpand::tilde("~/.ollama") {
-        std::borrow::Cow::Borrowed(s) => PathBuf::from(s),
-        std::borrow::Cow::Owned(s) => PathBuf::from(s),
-    };
+/// Ollama `models` directories (each holding `manifests/` and `blobs/`):
+/// `$OLLAMA_MODELS` when set, the per-user default, and the locations the
+/// Linux system service uses.
+pub fn ollama_models_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("OLLAMA_MODELS")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    dirs.push(expand("~/.ollama/models"));
+    if cfg!(target_os = "linux") {
+        dirs.push(PathBuf::from("/usr/share/ollama/.ollama/models"));
+        dirs.push(PathBuf::from("/var/lib/ollama/.ollama/models"));
+    }
+    dirs
+}
 
-    if !ollama_dir.exists() {
-        return found;
+/// Display name for a manifest at `<host>/<namespace>/<model>/<tag>` under
+/// `manifests/`, the way `ollama list` prints it: `llama3:8b` for the default
+/// library, `user/model:tag` for other namespaces on ollama.com and
+/// `hf.co/org/repo:tag` for any other registry.
+fn ollama_model_name(rel: &Path) -> String {
+    let parts: Vec<_> = rel.iter().map(|p| p.to_string_lossy()).collect();
+    match parts.as_slice() {
+        [host, namespace, model, tag] => match (host.as_ref(), namespace.as_ref()) {
+            ("registry.ollama.ai", "library") => format!("{}:{}", model, tag),
+            ("registry.ollama.ai", _) => format!("{}/{}:{}", namespace, model, tag),
+            _ => format!("{}/{}/{}:{}", host, namespace, model, tag),
+        },
+        _ => parts.join("/"),
     }
+}
+
+/// Scans Ollama's manifests (every registry host) to locate GGUF blobs: the
+/// model weights plus any vision projector and LoRA adapter layers.
+/// `models_dir` holds `manifests/` and `blobs/` (normally `~/.ollama/models`).
+pub fn scan_ollama_cache(models_dir: &Path) -> Vec<DiscoveredModel> {
+    let mut found = Vec::new();
 
-    let manifests_dir = ollama_dir
-        .join("models")
-        .join("manifests")
-        .join("registry.ollama.ai");
-    let blobs_dir = ollama_dir.join("models").join("blobs");
+    let manifests_dir = models_dir.join("manifests");
+    let blobs_dir = models_dir.join("blobs");
 
     if !manifests_dir.exists() || !blobs_dir.exists() {
         return found;
     }
 
     // Traverse all JSON manifests recursively
-    for entry in WalkDir::new(manifests_dir)
+    for entry in WalkDir::new(&manifests_dir)
         .into_iter()
         .filter_map(|e| e.ok())
     {
@@ -72,46 +202,29 @@ pub fn scan_ollama_cache() -> Vec<DiscoveredModel> {
             && let Ok(content) = fs::read_to_string(path)
             && let Ok(json) = serde_json::from_str::<Value>(&content)
             && let Some(layers) = json.get("layers").and_then(|l| l.as_array())
+            && let Ok(rel) = path.strip_prefix(&manifests_dir)
         {
-            // Look for layers
+            let model_name = ollama_model_name(rel);
             for layer in layers {
-                if let Some(media_type) = layer.get("mediaType").and_then(|m| m.as_str())
-                    && media_type == "application/vnd.ollama.image.model"
-                    && let Some(digest) = layer.get("digest").and_then(|d| d.as_str())
+                let kind = match layer.get("mediaType").and_then(|m| m.as_str()) {
+                    Some("application/vnd.ollama.image.model") => FileKind::Model,
+                    Some("application/vnd.ollama.image.projector") => FileKind::Projector,
+                    Some("application/vnd.ollama.image.adapter") => FileKind::Adapter,
+                    _ => continue,
+                };
+                // The blob is named exactly after the digest but with sha256- prefix
+                // Some systems use `:` separator, Ollama locally uses `-`
+                if let Some(digest) = layer.get("digest").and_then(|d| d.as_str())
+                    && let Ok(metadata) = fs::metadata(blobs_dir.join(digest.replace(":", "-")))
                 {
-                    // The blob is named exactly after the digest but with sha256- prefix
-                    // Some systems use `:` separator, Ollama locally uses `-`
-                    let blob_name = digest.replace(":", "-");
-                    let blob_path = blobs_dir.join(&blob_name);
-
-                    if blob_path.exists()
-                        && let Ok(metadata) = fs::metadata(&blob_path)
-                    {
-                        // Extract model name from the directory structure if possible
-                        // e.g. ~/.ollama/models/manifests/registry.ollama.ai/library/llama3/8b/latest
-                        let mut model_name = "Ollama-Model".to_string();
-                        if let Some(parent) = path.parent()
-                            && let Some(repo_name) = parent
-                                .parent()
-                                .and_then(|p| p.file_name())
-                                .and_then(|f| f.to_str())
-                            && let Some(tag_name) = parent.file_name().and_then(|f| f.to_str())
-                        {
-                            let final_tag = path
-                                .file_name()
-                                .unwrap_or_default()
-                                .to_str()
-                                .unwrap_or("latest");
-                            model_name = format!("{}:{}-{}", repo_name, tag_name, final_tag);
-                        }
-
-                        found.push(DiscoveredModel {
-                            name: model_name,
-                            path: blob_path,
-                            size_bytes: metadata.len(),
-                            source: "Ollama".to_string(),
-                        });
-                    }
+                    found.push(DiscoveredModel {
+                        name: model_name.clone(),
+                        path: blobs_dir.join(digest.replace(":", "-")),
+                        size_bytes: metadata.len(),
+                        source: "Ollama".to_string(),
+                        kind,
+                        ..Default::default()
+                    });
                 }
             }
         }
@@ -120,28 +233,562 @@ pub fn scan_ollama_cache() -> Vec<DiscoveredModel> {
     found
 }
 
+/// Scans one directory for `.llamafile` archives. These bundle llama.cpp with
+/// the weights, so they're listed but never served directly.
+pub fn scan_directory_for_llamafiles(dir: &Path, max_depth: usize) -> Vec<DiscoveredModel> {
+    WalkDir::new(dir)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "llamafile"))
+        .filter_map(|e| {
+            let metadata = fs::metadata(e.path()).ok()?;
+            metadata.is_file().then(|| DiscoveredModel {
+                name: e.file_name().to_string_lossy().to_string(),
+                path: e.path().to_path_buf(),
+                size_bytes: metadata.len(),
+                source: "llamafile".to_string(),
+                kind: FileKind::Llamafile,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// A place on disk where some application keeps model weights.
+pub trait ModelScanner {
+    /// Label reported as `DiscoveredModel::source`.
+    fn source(&self) -> &str;
+    fn scan(&self) -> Vec<DiscoveredModel>;
+}
+
+/// Any directory of loose `.gguf` files, searched recursively. Covers LM
+/// Studio, GPT4All, Jan, the Hugging Face hub cache (whose snapshot entries
+/// keep their file names) and user-configured directories.
+pub struct GgufDirScanner {
+    pub dir: PathBuf,
+    pub source: String,
+}
+
+impl ModelScanner for GgufDirScanner {
+    fn source(&self) -> &str {
+        &self.source
+    }
+
+    fn scan(&self) -> Vec<DiscoveredModel> {
+        scan_directory_for_gguf(&self.dir, &self.source)
+    }
+}
+
+/// Ollama's manifest/blob store; `dir` is the one holding `manifests/`.
+pub struct OllamaScanner {
+    pub dir: PathBuf,
+}
+
+impl ModelScanner for OllamaScanner {
+    fn source(&self) -> &str {
+        "Ollama"
+    }
+
+    fn scan(&self) -> Vec<DiscoveredModel> {
+        scan_ollama_cache(&self.dir)
+    }
+}
+
+/// `.llamafile` archives in the given directories.
+pub struct LlamafileScanner {
+    pub dirs: Vec<(PathBuf, usize)>,
+}
+
+impl ModelScanner for LlamafileScanner {
+    fn source(&self) -> &str {
+        "llamafile"
+    }
+
+    fn scan(&self) -> Vec<DiscoveredModel> {
+        self.dirs
+            .iter()
+            .flat_map(|(dir, depth)| scan_directory_for_llamafiles(dir, *depth))
+            .collect()
+    }
+}
+
+fn expand(path: &str) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(path).to_string())
+}
+
+/// Hugging Face hub cache, honouring `HF_HUB_CACHE` and `HF_HOME` like the
+/// `huggingface_hub` library does.
+fn hf_hub_cache_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("HF_HUB_CACHE") {
+        return PathBuf::from(dir);
+    }
+    std::env::var_os("HF_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| expand("~/.cache/huggingface"))
+        .join("hub")
+}
+
+/// Every cache location LocalCode knows about, in the order results are
+/// listed: the configured directory and `extra_dirs` first, then other apps.
+pub fn default_scanners(models_dir: &Path, extra_dirs: &[String]) -> Vec<Box<dyn ModelScanner>> {
+    let dir = |dir: PathBuf, source: &str| -> Box<dyn ModelScanner> {
+        Box::new(GgufDirScanner {
+            dir,
+            source: source.to_string(),
+        })
+    };
+
+    let mut scanners = vec![dir(models_dir.to_path_buf(), "LocalCode Config")];
+    scanners.extend(extra_dirs.iter().map(|d| dir(expand(d), "Custom")));
+    for dir in ollama_models_dirs() {
+        scanners.push(Box::new(OllamaScanner { dir }));
+    }
+    scanners.push(dir(expand("~/.lmstudio/models"), "LM Studio"));
+    scanners.push(dir(expand("~/.cache/lm-studio/models"), "LM Studio"));
+    scanners.push(dir(hf_hub_cache_dir(), "Hugging Face"));
+    if let Some(data) = dirs::data_local_dir() {
+        scanners.push(dir(data.join("nomic.ai").join("GPT4All"), "GPT4All"));
+    }
+    scanners.push(dir(expand("~/jan/models"), "Jan"));
+    if let Some(data) = dirs::data_dir() {
+        scanners.push(dir(data.join("Jan").join("data"), "Jan"));
+    }
+
+    // llamafiles usually sit wherever they were downloaded
+    let mut llamafile_dirs = vec![(models_dir.to_path_buf(), usize::MAX)];
+    llamafile_dirs.extend(extra_dirs.iter().map(|d| (expand(d), usize::MAX)));
+    if let Some(downloads) = dirs::download_dir() {
+        llamafile_dirs.push((downloads, 2));
+    }
+    scanners.push(Box::new(LlamafileScanner {
+        dirs: llamafile_dirs,
+    }));
+    scanners
+}
+
+/// Run `scanners` in order. A file reachable from several of them (e.g. the
+/// configured directory is also LM Studio's, or a snapshot symlink and its
+/// blob) is reported once, by the first scanner that found it.
+pub fn scan_all(scanners: &[Box<dyn ModelScanner>]) -> Vec<DiscoveredModel> {
+    let mut seen = HashSet::new();
+    scanners
+        .iter()
+        .flat_map(|s| s.scan())
+        .filter(|m| seen.insert(m.path.canonicalize().unwrap_or_else(|_| m.path.clone())))
+        .collect()
+}
+
 /// Helper function to perform a unified search across all known and configured paths
-pub fn find_all_local_models(configured_models_dir: &Path) -> Vec<DiscoveredModel> {
-    let mut all_models = Vec::new();
+pub fn find_all_local_models(
+    configured_models_dir: &Path,
+    extra_dirs: &[String],
+) -> Vec<DiscoveredModel> {
+    scan_all(&default_scanners(configured_models_dir, extra_dirs))
+}
+
+/// The same weights found in more than one place.
+#[derive(Debug)]
+pub struct DuplicateGroup {
+    pub size_bytes: u64,
+    pub copies: Vec<DiscoveredModel>,
+    /// Physically distinct files among `copies`; hard links and symlinks to
+    /// the same file count once.
+    pub distinct_files: usize,
+}
+
+impl DuplicateGroup {
+    /// Disk space that replacing the extra copies with hard links would free.
+    pub fn wasted_bytes(&self) -> u64 {
+        self.size_bytes * self.distinct_files.saturating_sub(1) as u64
+    }
+}
+
+/// Bytes hashed at each end of a file for its fingerprint. The start covers
+/// the GGUF header and tensor index, which already differ between
+/// quantizations and fine-tunes; the end catches truncated copies.
+const FINGERPRINT_SPAN: u64 = 1 << 20;
+
+/// Cheap content fingerprint: SHA-256 over the first and last
+/// [`FINGERPRINT_SPAN`] bytes plus the size. Good enough to group candidates;
+/// [`dedupe`] still compares full contents before linking anything.
+fn fingerprint(path: &Path) -> Option<String> {
+    use sha2::{Digest, Sha256};
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = fs::File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+
+    let mut buf = vec![0u8; FINGERPRINT_SPAN.min(size) as usize];
+    file.read_exact(&mut buf).ok()?;
+    hasher.update(&buf);
+    if size > FINGERPRINT_SPAN {
+        let tail = FINGERPRINT_SPAN.min(size - FINGERPRINT_SPAN);
+        file.seek(SeekFrom::End(-(tail as i64))).ok()?;
+        buf.resize(tail as usize, 0);
+        file.read_exact(&mut buf).ok()?;
+        hasher.update(&buf);
+    }
+    Some(
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    )
+}
+
+/// Identity of the file on disk, so hard links and symlinks to the same data
+/// aren't reported as waste.
+fn file_identity(path: &Path) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let meta = fs::metadata(path).ok()?;
+        Some((meta.dev(), meta.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        path.canonicalize().ok()?.hash(&mut hasher);
+        Some((0, hasher.finish()))
+    }
+}
 
-    // 1. Scan user's explicitly configured directory
-    let mut primary_models = scan_directory_for_gguf(configured_models_dir, "LocalCode Config");
-    all_models.append(&mut primary_models);
+/// Group models whose files have identical contents. Only same-size files are
+/// fingerprinted, so this stays fast on large caches.
+pub fn find_duplicates(models: &[DiscoveredModel]) -> Vec<DuplicateGroup> {
+    let mut by_size: HashMap<u64, Vec<&DiscoveredModel>> = HashMap::new();
+    for m in models.iter().filter(|m| m.size_bytes > 0) {
+        by_size.entry(m.size_bytes).or_default().push(m);
+    }
 
-    // 2. Scan Ollama
-    let mut ollama_models = scan_ollama_cache();
-    all_models.append(&mut ollama_models);
+    let mut groups = Vec::new();
+    for (size, same_size) in by_size.into_iter().filter(|(_, v)| v.len() > 1) {
+        let mut by_print: HashMap<String, Vec<DiscoveredModel>> = HashMap::new();
+        for m in same_size {
+            if let Some(print) = fingerprint(&m.path) {
+                by_print.entry(print).or_default().push(m.clone());
+            }
+        }
+        for copies in by_print.into_values().filter(|c| c.len() > 1) {
+            let distinct_files = copies
+                .iter()
+                .filter_map(|c| file_identity(&c.path))
+                .collect::<HashSet<_>>()
+                .len();
+            groups.push(DuplicateGroup {
+                size_bytes: size,
+                copies,
+                distinct_files,
+            });
+        }
+    }
+    groups.sort_by_key(|g| std::cmp::Reverse(g.wasted_bytes()));
+    groups
+}
+
+/// Byte-for-byte comparison, the final check before a copy is replaced.
+fn same_contents(a: &Path, b: &Path) -> std::io::Result<bool> {
+    use std::io::Read;
+    let (mut fa, mut fb) = (fs::File::open(a)?, fs::File::open(b)?);
+    if fa.metadata()?.len() != fb.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut ba, mut bb) = (vec![0u8; 1 << 20], vec![0u8; 1 << 20]);
+    loop {
+        let n = fa.read(&mut ba)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        fb.read_exact(&mut bb[..n])?;
+        if ba[..n] != bb[..n] {
+            return Ok(false);
+        }
+    }
+}
 
-    // 3. Scan LM Studio default cache if it's not the primary dir already
-    let lm_studio_dir = match shellexpand::tilde("~/.cache/lm-studio/models") {
-        std::borrow::Cow::Borrowed(s) => PathBuf::from(s),
-        std::borrow::Cow::Owned(s) => PathBuf::from(s),
+/// Replace every extra copy in `group` with a hard link to the first one,
+/// returning the bytes freed. Copies on another filesystem, or that can't be
+/// replaced (e.g. owned by another user), are skipped and reported in the
+/// returned messages.
+pub fn dedupe(group: &DuplicateGroup) -> (u64, Vec<String>) {
+    let mut freed = 0;
+    let mut skipped = Vec::new();
+    let Some((keeper, rest)) = group.copies.split_first() else {
+        return (0, skipped);
+    };
+    let keeper_path = keeper
+        .path
+        .canonicalize()
+        .unwrap_or_else(|_| keeper.path.clone());
+    let Some(keeper_id) = file_identity(&keeper_path) else {
+        return (0, skipped);
     };
+    let mut linked = HashSet::from([keeper_id]);
 
-    if lm_studio_dir != configured_models_dir {
-        let mut lm_models = scan_directory_for_gguf(&lm_studio_dir, "LM Studio");
-        all_models.append(&mut lm_models);
+    for copy in rest {
+        // Replace the real file, not a symlink pointing at it
+        let target = copy
+            .path
+            .canonicalize()
+            .unwrap_or_else(|_| copy.path.clone());
+        let Some(id) = file_identity(&target) else {
+            continue;
+        };
+        if linked.contains(&id) {
+            continue;
+        }
+        if id.0 != keeper_id.0 {
+            skipped.push(format!(
+                "{}: on a different filesystem",
+                copy.path.display()
+            ));
+            continue;
+        }
+        let result = same_contents(&keeper_path, &target).and_then(|same| {
+            if !same {
+                return Err(std::io::Error::other("contents differ"));
+            }
+            // Link next to the copy, then atomically swap it in
+            let tmp = target.with_extension("localcode-dedupe");
+            let _ = fs::remove_file(&tmp);
+            fs::hard_link(&keeper_path, &tmp)?;
+            fs::rename(&tmp, &target).inspect_err(|_| {
+                let _ = fs::remove_file(&tmp);
+            })
+        });
+        match result {
+            Ok(()) => {
+                linked.insert(id);
+                freed += group.size_bytes;
+            }
+            Err(e) => skipped.push(format!("{}: {}", copy.path.display(), e)),
+        }
+    }
+    (freed, skipped)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn model(path: &Path, source: &str) -> DiscoveredModel {
+        DiscoveredModel {
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            path: path.to_path_buf(),
+            size_bytes: fs::metadata(path).unwrap().len(),
+            source: source.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter_and_sort() {
+        let entry = |name: &str, source: &str, params: u64| DiscoveredModel {
+            name: name.to_string(),
+            source: source.to_string(),
+            parameter_count: Some(params),
+            ..Default::default()
+        };
+        let models = vec![
+            entry("qwen2.5-coder-7b-Q4_K_M.gguf", "LocalCode Config", 7),
+            entry("qwen2.5-coder:1.5b-latest", "Ollama", 2),
+            entry("Llama-3-8B-Q8_0.gguf", "LM Studio", 8),
+        ];
+        let names = |v: Vec<DiscoveredModel>| v.into_iter().map(|m| m.name).collect::<Vec<_>>();
+
+        let sorted = filter_and_sort(models.clone(), None, None, SortKey::Params);
+        assert_eq!(sorted[0].name, "Llama-3-8B-Q8_0.gguf");
+        assert_eq!(sorted[2].name, "qwen2.5-coder:1.5b-latest");
+
+        let sorted = filter_and_sort(models.clone(), None, None, SortKey::Name);
+        assert_eq!(sorted[0].name, "Llama-3-8B-Q8_0.gguf");
+
+        let re = regex::RegexBuilder::new("^QWEN")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let kept = filter_and_sort(models.clone(), Some("ollama"), Some(&re), SortKey::Name);
+        assert_eq!(names(kept), vec!["qwen2.5-coder:1.5b-latest"]);
+
+        let kept = filter_and_sort(models, Some("lm studio"), Some(&re), SortKey::Name);
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn test_scan_all() {
+        let dir = std::env::temp_dir().join(format!("localcode-scan-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        // Hugging Face layout: two snapshots pointing at one blob
+        let repo = dir.join("hub").join("models--org--Tiny-GGUF");
+        fs::create_dir_all(repo.join("blobs")).unwrap();
+        fs::write(repo.join("blobs").join("abc"), b"GGUF weights").unwrap();
+        for commit in ["c1", "c2"] {
+            let snapshot = repo.join("snapshots").join(commit);
+            fs::create_dir_all(&snapshot).unwrap();
+            std::os::unix::fs::symlink("../../blobs/abc", snapshot.join("tiny-Q4_K_M.gguf"))
+                .unwrap();
+        }
+        fs::create_dir_all(dir.join("extra")).unwrap();
+        fs::write(dir.join("extra").join("other.gguf"), b"GGUF other").unwrap();
+        fs::write(dir.join("extra").join("mistral.llamafile"), b"MZ").unwrap();
+
+        let scanners: Vec<Box<dyn ModelScanner>> = vec![
+            Box::new(GgufDirScanner {
+                dir: dir.join("extra"),
+                source: "Custom".to_string(),
+            }),
+            Box::new(GgufDirScanner {
+                dir: dir.join("hub"),
+                source: "Hugging Face".to_string(),
+            }),
+            // Overlaps the first scanner; its files are already reported
+            Box::new(GgufDirScanner {
+                dir: dir.clone(),
+                source: "LM Studio".to_string(),
+            }),
+            Box::new(LlamafileScanner {
+                dirs: vec![(dir.join("extra"), 1)],
+            }),
+        ];
+        let mut found: Vec<_> = scan_all(&scanners)
+            .into_iter()
+            .map(|m| (m.name, m.source, m.size_bytes))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("mistral.llamafile".to_string(), "llamafile".to_string(), 2),
+                ("other.gguf".to_string(), "Custom".to_string(), 10),
+                (
+                    "tiny-Q4_K_M.gguf".to_string(),
+                    "Hugging Face".to_string(),
+                    12
+                ),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
-    all_models
+    #[test]
+    fn test_scan_ollama_cache() {
+        let dir = std::env::temp_dir().join(format!("localcode-ollama-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("blobs")).unwrap();
+        for (digest, bytes) in [("aa", 10), ("bb", 3), ("cc", 7)] {
+            fs::write(
+                dir.join("blobs").join(format!("sha256-{}", digest)),
+                vec![0u8; bytes],
+            )
+            .unwrap();
+        }
+        let manifest = |rel: &str, layers: &[(&str, &str)]| {
+            let path = dir.join("manifests").join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let layers: Vec<_> = layers
+                .iter()
+                .map(|(kind, digest)| {
+                    serde_json::json!({
+                        "mediaType": format!("application/vnd.ollama.image.{}", kind),
+                        "digest": format!("sha256:{}", digest),
+                    })
+                })
+                .collect();
+            fs::write(path, serde_json::json!({ "layers": layers }).to_string()).unwrap();
+        };
+        manifest(
+            "registry.ollama.ai/library/llava/7b",
+            &[("model", "aa"), ("projector", "bb"), ("template", "dd")],
+        );
+        manifest(
+            "registry.ollama.ai/someone/coder/latest",
+            &[("model", "cc")],
+        );
+        manifest(
+            "hf.co/bartowski/Qwen2.5-Coder-7B-Instruct-GGUF/Q4_K_M",
+            &[("model", "cc"), ("adapter", "ee")],
+        );
+
+        let mut found: Vec<_> = scan_ollama_cache(&dir)
+            .into_iter()
+            .map(|m| (m.display_name(), m.kind, m.size_bytes))
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "hf.co/bartowski/Qwen2.5-Coder-7B-Instruct-GGUF:Q4_K_M".to_string(),
+                    FileKind::Model,
+                    7
+                ),
+                ("llava:7b".to_string(), FileKind::Model, 10),
+                ("llava:7b (projector)".to_string(), FileKind::Projector, 3),
+                ("someone/coder:latest".to_string(), FileKind::Model, 7),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_duplicates_and_dedupe() {
+        let dir = std::env::temp_dir().join(format!("localcode-dedupe-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // 3 MB so the fingerprint covers head and tail separately
+        let weights: Vec<u8> = (0..3_000_000u32).map(|i| (i % 253) as u8).collect();
+        let mut other = weights.clone();
+        other[1_500_000] ^= 0xff; // same size, same ends, different middle
+        fs::write(dir.join("a.gguf"), &weights).unwrap();
+        fs::write(dir.join("sha256-b"), &weights).unwrap();
+        fs::write(dir.join("c.gguf"), &weights).unwrap();
+        fs::write(dir.join("d.gguf"), &other).unwrap();
+        fs::write(dir.join("small.gguf"), b"GGUF").unwrap();
+
+        let models = vec![
+            model(&dir.join("a.gguf"), "LocalCode Config"),
+            model(&dir.join("sha256-b"), "Ollama"),
+            model(&dir.join("c.gguf"), "LM Studio"),
+            model(&dir.join("small.gguf"), "LM Studio"),
+        ];
+        let groups = find_duplicates(&models);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].copies.len(), 3);
+        assert_eq!(groups[0].wasted_bytes(), 2 * 3_000_000);
+
+        let (freed, skipped) = dedupe(&groups[0]);
+        assert_eq!(freed, 2 * 3_000_000);
+        assert!(skipped.is_empty());
+        assert_eq!(fs::read(dir.join("sha256-b")).unwrap(), weights);
+
+        // Now all one file: still grouped, but nothing wasted
+        let groups = find_duplicates(&models);
+        assert_eq!(groups[0].distinct_files, 1);
+        assert_eq!(groups[0].wasted_bytes(), 0);
+
+        // A fingerprint collision is caught by the full comparison
+        let collision = DuplicateGroup {
+            size_bytes: 3_000_000,
+            copies: vec![
+                model(&dir.join("a.gguf"), "x"),
+                model(&dir.join("d.gguf"), "y"),
+            ],
+            distinct_files: 2,
+        };
+        let (freed, skipped) = dedupe(&collision);
+        assert_eq!(freed, 0);
+        assert!(skipped[0].contains("contents differ"));
+        assert_eq!(fs::read(dir.join("d.gguf")).unwrap(), other);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,314 @@
+use crate::ui::InitConfig;
+use anyhow::{Context, Result};
+use console::style;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Native (non-Docker) mode runs `llama-swap` straight on the host. It is
+/// spawned detached, its output goes to a log file and its PID is recorded so
+/// `status` / `stop` can find it again from a different invocation.
+const PID_FILE: &str = "llama-swap.pid";
+const LOG_FILE: &str = "llama-swap.log";
+
+pub fn pid_file() -> PathBuf {
+    crate::config::global_config_dir().join(PID_FILE)
+}
+
+/// Where native llama-swap output goes: the rotating log under the models dir
+/// when `persist_logs` is set, otherwise a single file that each start overwrites.
+pub fn log_file(config: &InitConfig, models_dir: &Path) -> PathBuf {
+    if config.persist_logs {
+        crate::logs::persisted_log_path(models_dir)
+    } else {
+        crate::config::global_config_dir().join(LOG_FILE)
+    }
+}
+
+/// Detach a child from our process group so Ctrl+C in this terminal doesn't
+/// take it down with us.
+pub fn detach(cmd: &mut std::process::Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+/// Resolve a binary from an explicitly configured path, or search `PATH`.
+pub fn find_binary(name: &str, configured: Option<&str>) -> Result<PathBuf> {
+    if let Some(p) = configured {
+        let path = PathBuf::from(shellexpand::tilde(p).to_string());
+        if path.is_file() {
+            return Ok(path);
+        }
+        anyhow::bail!("Configured {} binary not found at {}", name, path.display());
+    }
+
+    let exe_name = if cfg!(target_os = "windows") {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    };
+    std::env::var_os("PATH")
+        .and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(&exe_name))
+                .find(|candidate| candidate.is_file())
+        })
+        .with_context(|| {
+            format!(
+                "`{}` was not found on PATH. Install it or set `{}_path` in localcode.json.",
+                name,
+                name.replace('-', "_")
+            )
+        })
+}
+
+/// Read the PID of a previously started llama-swap, if its PID file exists.
+pub fn read_pid() -> Option<u32> {
+    std::fs::read_to_string(pid_file())
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+}
+
+pub async fn is_process_alive(pid: u32) -> bool {
+    let output = if cfg!(target_os = "windows") {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .await
+    } else {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .output()
+            .await
+    };
+    match output {
+        Ok(o) if cfg!(target_os = "windows") => {
+            String::from_utf8_lossy(&o.stdout).contains(&pid.to_string())
+        }
+        Ok(o) => o.status.success(),
+        Err(_) => false,
+    }
+}
+
+/// Whether `pid` is a llama-swap process. PIDs get reused, so a PID file left
+/// behind by a crash may point at something unrelated by now.
+async fn is_llama_swap(pid: u32) -> bool {
+    let output = if cfg!(target_os = "windows") {
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+            .output()
+            .await
+    } else {
+        Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "comm="])
+            .output()
+            .await
+    };
+    let Ok(output) = output else {
+        return false;
+    };
+    // tasklist prints `"llama-swap.exe","1234",...`; ps prints the command
+    // name, which is the full path on macOS.
+    let stdout = String::from_utf8_lossy(&output.stdout).to_lowercase();
+    let image = stdout
+        .split(',')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches('"');
+    output.status.success()
+        && Path::new(image)
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with("llama-swap"))
+}
+
+/// PID of the llama-swap we started, if it is still running.
+pub async fn running_pid() -> Option<u32> {
+    let pid = read_pid()?;
+    (is_process_alive(pid).await && is_llama_swap(pid).await).then_some(pid)
+}
+
+async fn kill_process(pid: u32) -> Result<()> {
+    // llama-swap shuts down its llama-server children on SIGTERM.
+    let output = if cfg!(target_os = "windows") {
+        Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .output()
+            .await?
+    } else {
+        Command::new("kill")
+            .args(["-TERM", &pid.to_string()])
+            .output()
+            .await?
+    };
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to stop process {}: {}",
+            pid,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Last `n` lines of the native log file, for error reports.
+fn tail_lines(path: &Path, n: usize) -> String {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let lines: Vec<&str> = content.lines().collect();
+    lines[lines.len().saturating_sub(n)..].join("\n")
+}
+
+pub async fn start_llama_swap_native(
+    config: &InitConfig,
+    models_dir: &Path,
+    downloaded_files: &std::collections::HashMap<String, PathBuf>,
+    profile: Option<&crate::profiling::HardwareProfile>,
+) -> Result<()> {
+    println!("📦 Launching native llama-swap...");
+
+    let llama_swap = find_binary("llama-swap", config.llama_swap_path.as_deref())?;
+    let llama_server = find_binary("llama-server", config.llama_server_path.as_deref())?;
+
+    // Mirror the Docker flow, which always replaces the previous container.
+    if let Some(pid) = running_pid().await {
+        kill_process(pid).await?;
+    } else {
+        let _ = tokio::fs::remove_file(pid_file()).await;
+    }
+
+    let yaml_content = crate::runner::generate_llama_swap_yaml(
+        &config.models,
+        models_dir,
+        config.llama_server_args.as_ref(),
+        downloaded_files,
+        profile,
+        &crate::runner::ServerTarget::Native {
+            llama_server,
+            gpus: config.gpu_devices(),
+        },
+    )
+    .await?;
+
+    let config_path = models_dir.join("llama-swap.yaml");
+    tokio::fs::write(&config_path, yaml_content).await?;
+
+    let state_dir = crate::config::global_config_dir();
+    tokio::fs::create_dir_all(&state_dir).await?;
+    let log_path = log_file(config, models_dir);
+    if config.persist_logs {
+        tokio::fs::create_dir_all(log_path.parent().unwrap_or(models_dir)).await?;
+        crate::logs::rotate(&log_path)?;
+    }
+    let log = std::fs::File::create(&log_path)
+        .with_context(|| format!("Failed to create log file {}", log_path.display()))?;
+
+    let mut cmd = std::process::Command::new(&llama_swap);
+    cmd.arg("--config")
+        .arg(&config_path)
+        .arg("--listen")
+        .arg(format!("127.0.0.1:{}", config.port))
+        // Same cache location the container uses for --hf-repo fallbacks.
+        .env("HF_HOME", models_dir)
+        .stdin(std::process::Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // GPU indices everywhere in localcode are nvidia-smi's
+    cmd.env("CUDA_DEVICE_ORDER", "PCI_BUS_ID");
+    // The native counterpart of the container's --gpus "device=..."
+    if !config.gpus.is_empty() {
+        let devices: Vec<String> = config.gpu_devices().iter().map(u32::to_string).collect();
+        cmd.env("CUDA_VISIBLE_DEVICES", devices.join(","));
+    }
+
+    detach(&mut cmd);
+
+    let child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to launch {}", llama_swap.display()))?;
+    let pid = child.id();
+    tokio::fs::write(pid_file(), pid.to_string()).await?;
+
+    // llama-swap fails fast on a bad config or a busy port; catch that here
+    // instead of leaving a stale PID file behind.
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    if !is_process_alive(pid).await {
+        let _ = tokio::fs::remove_file(pid_file()).await;
+        anyhow::bail!(
+            "llama-swap exited immediately. Last log lines:\n{}",
+            tail_lines(&log_path, 20)
+        );
+    }
+
+    Ok(())
+}
+
+/// When the running llama-swap was started, taken from its PID file.
+pub fn started_at() -> Option<std::time::SystemTime> {
+    std::fs::metadata(pid_file())
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+pub async fn stop_server_native() -> Result<()> {
+    println!(
+        "{}",
+        style("🛑 Stopping native llama-swap (models are preserved on disk)...").yellow()
+    );
+
+    match running_pid().await {
+        Some(pid) => {
+            kill_process(pid).await?;
+            println!(
+                "{} {}",
+                style("✓").green().bold(),
+                style("Server stopped.").green()
+            );
+        }
+        _ => println!(
+            "{} {}",
+            style("ℹ").yellow(),
+            style("Native llama-swap was not running.").dim()
+        ),
+    }
+
+    let _ = tokio::fs::remove_file(pid_file()).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_binary_configured_path() {
+        let exe = std::env::current_exe().unwrap();
+        let found = find_binary("llama-swap", Some(exe.to_str().unwrap())).unwrap();
+        assert_eq!(found, exe);
+
+        let missing = find_binary("llama-swap", Some("/definitely/not/here/llama-swap"));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_find_binary_missing_on_path() {
+        let err = find_binary("localcode-no-such-binary", None).unwrap_err();
+        assert!(err.to_string().contains("localcode_no_such_binary_path"));
+    }
+
+    #[tokio::test]
+    async fn test_is_llama_swap_rejects_other_processes() {
+        // A live PID that belongs to something else, as after PID reuse
+        let pid = std::process::id();
+        assert!(is_process_alive(pid).await);
+        assert!(!is_llama_swap(pid).await);
+    }
+}
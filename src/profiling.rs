@@ -1,5 +1,7 @@
 use anyhow::Result;
-use llmfit_core::hardware::GpuBackend;
+use console::style;
+use llmfit_core::hardware::{GpuBackend, GpuInfo, SystemSpecs};
+use serde::{Deserialize, Serialize};
 
 /// Upgrade a standard llmfit quant to the corresponding Unsloth Dynamic (UD-*_XL)
 /// variant. UD quants use per-layer mixed precision: important attention layers get
@@ -9,32 +11,34 @@ use llmfit_core::hardware::GpuBackend;
 /// Unsloth publishes UD-GGUF files on HuggingFace under `unsloth/{model}-GGUF`.
 fn upgrade_to_ud(quant: &str) -> String {
     match quant {
-        "Q2_K"   => "UD-Q2_K_XL".to_string(),
+        "Q2_K" => "UD-Q2_K_XL".to_string(),
         "Q3_K_M" => "UD-Q3_K_XL".to_string(),
         "Q4_K_M" => "UD-Q4_K_XL".to_string(),
         "Q5_K_M" => "UD-Q5_K_XL".to_string(),
-        "Q6_K"   => "UD-Q6_K_XL".to_string(),
-        "Q8_0"   => "UD-Q8_0".to_string(),
-        other    => other.to_string(), // F16, mlx-*, etc. — unchanged
+        "Q6_K" => "UD-Q6_K_XL".to_string(),
+        "Q8_0" => "UD-Q8_0".to_string(),
+        other => other.to_string(), // F16, mlx-*, etc. — unchanged
     }
 }
 
+#[derive(Serialize)]
 pub struct HardwareProfile {
     pub vram_gb: f32,
     pub ram_gb: f32,
     pub cpu_cores: usize,
-    #[allow(dead_code)]
     pub gpu_name: Option<String>,
     pub gpu_backend: GpuBackend,
-    #[allow(dead_code)]
     pub gpu_count: u32,
+    /// VRAM of each GPU llama.cpp can use, in device order. Empty when
+    /// nothing was detected per device.
+    pub gpu_vram_gb: Vec<f32>,
     pub unified_memory: bool,
     pub recommended_models: Vec<RecommendedModel>,
     /// Total available memory for model loading (VRAM if GPU, RAM if CPU-only)
     pub available_memory_gb: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RecommendedModel {
     pub name: String,
     pub category: String,
@@ -42,17 +46,185 @@ pub struct RecommendedModel {
     pub best_quant: String,
     /// Estimated memory footprint in GB (weights + KV overhead)
     pub memory_gb: f32,
-    /// Parameter count in billions (for VRAM budget calculations)
-    #[allow(dead_code)]
+    /// Parameter count in billions (for VRAM budget and draft model choice)
     pub params_b: f32,
     /// Whether this is an autocomplete-sized model (≤3B)
     pub is_autocomplete: bool,
 }
 
-pub async fn profile_hardware() -> Result<HardwareProfile> {
-    println!("🔍 Profiling hardware capabilities via llmfit...");
+/// Acceleration backend named in the `hardware` section or `init --backend`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Cuda,
+    Metal,
+    Rocm,
+    Vulkan,
+    Sycl,
+    /// No GPU at all
+    Cpu,
+}
+
+impl Backend {
+    fn gpu_backend(self) -> GpuBackend {
+        match self {
+            Backend::Cuda => GpuBackend::Cuda,
+            Backend::Metal => GpuBackend::Metal,
+            Backend::Rocm => GpuBackend::Rocm,
+            Backend::Vulkan => GpuBackend::Vulkan,
+            Backend::Sycl => GpuBackend::Sycl,
+            Backend::Cpu if cfg!(target_arch = "aarch64") => GpuBackend::CpuArm,
+            Backend::Cpu => GpuBackend::CpuX86,
+        }
+    }
+}
+
+/// The `hardware` section of `localcode.json`. Every field that is set
+/// replaces what detection reports, which is wrong inside WSL and VMs and
+/// empty in containers.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct HardwareOverride {
+    /// Total VRAM in GB; 0 means no GPU.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub vram_gb: Option<f32>,
+    /// VRAM of each GPU in device order. Sets the GPU count, and `vram_gb`
+    /// to their sum.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub gpu_vram_gb: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ram_gb: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cpu_cores: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub gpu_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub backend: Option<Backend>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub unified_memory: Option<bool>,
+}
+
+impl HardwareOverride {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `other`'s fields where set, ours elsewhere.
+    pub fn merged_with(&self, other: &HardwareOverride) -> HardwareOverride {
+        HardwareOverride {
+            vram_gb: other.vram_gb.or(self.vram_gb),
+            gpu_vram_gb: other
+                .gpu_vram_gb
+                .clone()
+                .or_else(|| self.gpu_vram_gb.clone()),
+            ram_gb: other.ram_gb.or(self.ram_gb),
+            cpu_cores: other.cpu_cores.or(self.cpu_cores),
+            gpu_name: other.gpu_name.clone().or_else(|| self.gpu_name.clone()),
+            backend: other.backend.or(self.backend),
+            unified_memory: other.unified_memory.or(self.unified_memory),
+        }
+    }
+
+    /// Whether the GPUs' memory is given, so it shouldn't be probed. A
+    /// backend alone still leaves the cards to `nvidia-smi`.
+    fn sets_gpus(&self) -> bool {
+        self.vram_gb.is_some() || self.gpu_vram_gb.is_some()
+    }
+
+    /// Rewrite detected `specs` with every field that is set, keeping them
+    /// consistent (a GPU list, VRAM totals and `has_gpu` agree).
+    pub fn apply(&self, mut specs: SystemSpecs) -> SystemSpecs {
+        if let Some(ram) = self.ram_gb {
+            specs.total_ram_gb = ram as f64;
+            specs.available_ram_gb = ram as f64;
+        }
+        if let Some(cores) = self.cpu_cores {
+            specs.total_cpu_cores = cores;
+        }
+        let backend = self
+            .backend
+            .map(Backend::gpu_backend)
+            .unwrap_or(specs.backend);
+        let no_gpu = self.backend == Some(Backend::Cpu) || self.vram_gb == Some(0.0);
+        let cards: Option<Vec<f64>> = if no_gpu {
+            Some(Vec::new())
+        } else if let Some(list) = &self.gpu_vram_gb {
+            Some(list.iter().map(|&v| v as f64).collect())
+        } else if let Some(total) = self.vram_gb {
+            // Keep the detected card count, sharing the total between them
+            let count = specs.gpus.first().map(|g| g.count.max(1)).unwrap_or(1);
+            Some(vec![total as f64 / count as f64; count as usize])
+        } else {
+            None
+        };
+        if let Some(cards) = cards {
+            let name = self
+                .gpu_name
+                .clone()
+                .or_else(|| specs.gpu_name.clone())
+                .unwrap_or_else(|| "User-specified GPU".to_string());
+            let unified = self.unified_memory.unwrap_or(specs.unified_memory);
+            specs.gpus = cards
+                .iter()
+                .map(|&vram| GpuInfo {
+                    name: name.clone(),
+                    vram_gb: Some(vram),
+                    backend,
+                    count: 1,
+                    unified_memory: unified,
+                })
+                .collect();
+            specs.has_gpu = !cards.is_empty();
+            specs.gpu_vram_gb = cards.first().copied();
+            specs.total_gpu_vram_gb = specs.has_gpu.then(|| cards.iter().sum());
+            specs.gpu_count = cards.len() as u32;
+            specs.gpu_name = specs.has_gpu.then_some(name);
+        } else if self.backend.is_some() {
+            // Same cards, but counted under the overridden backend
+            specs.gpus.iter_mut().for_each(|g| g.backend = backend);
+        }
+        specs.backend = match self.backend {
+            Some(b) => b.gpu_backend(),
+            None if no_gpu => Backend::Cpu.gpu_backend(),
+            None => specs.backend,
+        };
+        if let Some(name) = &self.gpu_name
+            && specs.has_gpu
+        {
+            specs.gpu_name = Some(name.clone());
+            specs.gpus.iter_mut().for_each(|g| g.name = name.clone());
+        }
+        if let Some(unified) = self.unified_memory {
+            specs.unified_memory = unified;
+            specs
+                .gpus
+                .iter_mut()
+                .for_each(|g| g.unified_memory = unified);
+        }
+        specs
+    }
+}
+
+pub async fn profile_hardware(overrides: &HardwareOverride) -> Result<HardwareProfile> {
+    // stderr, so `localcode config render` output stays pipeable.
+    eprintln!("🔍 Profiling hardware capabilities via llmfit...");
+    Ok(build_profile(SystemSpecs::detect(), overrides))
+}
 
-    let specs = llmfit_core::hardware::SystemSpecs::detect();
+/// The profile and recommendations for `specs` as amended by `overrides`.
+/// Only `nvidia-smi` is consulted beyond `specs`, and not when the overrides
+/// describe the GPUs, so a fully overridden profile is the same everywhere.
+fn build_profile(specs: SystemSpecs, overrides: &HardwareOverride) -> HardwareProfile {
+    let mut specs = overrides.apply(specs);
+    let gpu_vram_gb = if overrides.sets_gpus() {
+        llmfit_gpu_vram(&specs)
+    } else {
+        detect_gpu_vram(&specs)
+    };
+    // nvidia-smi sees every card; llmfit only counts those like the first one
+    if specs.backend == GpuBackend::Cuda && gpu_vram_gb.len() > 1 {
+        specs.total_gpu_vram_gb = Some(gpu_vram_gb.iter().map(|&v| v as f64).sum());
+        specs.gpu_count = gpu_vram_gb.len() as u32;
+    }
     let ram_gb = specs.total_ram_gb as f32;
     let vram_gb = specs.total_gpu_vram_gb.or(specs.gpu_vram_gb).unwrap_or(0.0) as f32;
     let cpu_cores = specs.total_cpu_cores;
@@ -86,7 +258,7 @@ pub async fn profile_hardware() -> Result<HardwareProfile> {
         let is_auto = crate::runner::is_autocomplete_model(&fit.model.name);
         recommended_models.push(RecommendedModel {
             name: fit.model.name.clone(),
-            category: format!("{:?}", fit.model.use_case),
+            category: fit.model.use_case.clone(),
             score: fit.score as f32,
             best_quant: upgrade_to_ud(&fit.best_quant),
             memory_gb: fit.memory_required_gb as f32,
@@ -95,36 +267,382 @@ pub async fn profile_hardware() -> Result<HardwareProfile> {
         });
     }
 
-    let available_memory = if specs.has_gpu {
-        specs
-            .total_gpu_vram_gb
-            .or(specs.gpu_vram_gb)
-            .unwrap_or(specs.available_ram_gb)
+    let available_memory = if specs.has_gpu && vram_gb > 0.0 {
+        vram_gb as f64
     } else {
         specs.available_ram_gb
     };
 
-    Ok(HardwareProfile {
+    HardwareProfile {
         vram_gb,
         ram_gb,
         cpu_cores,
         gpu_name,
         gpu_backend,
         gpu_count,
+        gpu_vram_gb,
         unified_memory,
         recommended_models,
         available_memory_gb: available_memory as f32,
-    })
+    }
+}
+
+impl HardwareProfile {
+    /// Keep only the GPUs in `devices` (indices into `gpu_vram_gb`), in host
+    /// order, as a container started with `--gpus "device=..."` sees them.
+    pub fn select_gpus(&mut self, devices: &[u32]) {
+        if devices.is_empty() || self.gpu_vram_gb.is_empty() {
+            return;
+        }
+        self.gpu_vram_gb = (0..self.gpu_vram_gb.len())
+            .filter(|&i| devices.contains(&(i as u32)))
+            .map(|i| self.gpu_vram_gb[i])
+            .collect();
+        self.gpu_count = self.gpu_vram_gb.len() as u32;
+        self.vram_gb = self.gpu_vram_gb.iter().sum();
+        self.available_memory_gb = self.vram_gb;
+    }
+}
+
+#[cfg(test)]
+impl HardwareProfile {
+    /// One CUDA GPU with `vram_gb`, 32 GB of RAM and 16 cores. Tests override
+    /// only the fields they are about.
+    pub fn test_gpu(vram_gb: f32) -> Self {
+        HardwareProfile {
+            vram_gb,
+            ram_gb: 32.0,
+            cpu_cores: 16,
+            gpu_name: None,
+            gpu_backend: GpuBackend::Cuda,
+            gpu_count: 1,
+            gpu_vram_gb: vec![],
+            unified_memory: false,
+            recommended_models: vec![],
+            available_memory_gb: vram_gb,
+        }
+    }
+}
+
+/// `localcode profile`: the hardware summary, then the `top` best-ranked
+/// models (all of them when `top` is 0).
+pub fn print_profile(profile: &HardwareProfile, top: usize) {
+    let gpu = match &profile.gpu_name {
+        Some(name) if profile.gpu_count > 1 => format!("{} × {}", profile.gpu_count, name),
+        Some(name) => name.clone(),
+        None if profile.gpu_count > 0 => format!("{} GPU(s)", profile.gpu_count),
+        None => "none".to_string(),
+    };
+    let per_gpu = if profile.gpu_vram_gb.len() > 1 {
+        let sizes: Vec<String> = profile
+            .gpu_vram_gb
+            .iter()
+            .map(|v| format!("{:.1}", v))
+            .collect();
+        format!(" ({} GB)", sizes.join(" + "))
+    } else {
+        String::new()
+    };
+    println!("{}", style("Hardware Profile").bold());
+    println!("  {:<16} {}", style("GPU").cyan(), gpu);
+    println!(
+        "  {:<16} {}",
+        style("Backend").cyan(),
+        profile.gpu_backend.label()
+    );
+    println!(
+        "  {:<16} {:.1} GB{}",
+        style("VRAM").cyan(),
+        profile.vram_gb,
+        per_gpu
+    );
+    println!("  {:<16} {:.1} GB", style("RAM").cyan(), profile.ram_gb);
+    println!(
+        "  {:<16} {}",
+        style("Unified memory").cyan(),
+        if profile.unified_memory { "yes" } else { "no" }
+    );
+    println!("  {:<16} {}", style("CPU cores").cyan(), profile.cpu_cores);
+    println!(
+        "  {:<16} {:.1} GB",
+        style("Model budget").cyan(),
+        profile.available_memory_gb
+    );
+
+    let models = &profile.recommended_models;
+    if models.is_empty() {
+        println!("\n{} No models fit this hardware.", style("ℹ").yellow());
+        return;
+    }
+    let shown = if top == 0 {
+        models.len()
+    } else {
+        top.min(models.len())
+    };
+    println!(
+        "\n{} {} Recommended Models",
+        style("✓").green().bold(),
+        models.len()
+    );
+    let name_width = models[..shown]
+        .iter()
+        .map(|m| m.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    println!(
+        "{:>3} | {:<name_width$} | {:>5} | {:<12} | {:>8} | {:>7} | {:<4} | {}",
+        style("#").bold().cyan(),
+        style("Model").bold().cyan(),
+        style("Score").bold().cyan(),
+        style("Quant").bold().cyan(),
+        style("Memory").bold().cyan(),
+        style("Params").bold().cyan(),
+        style("Auto").bold().cyan(),
+        style("Category").bold().cyan()
+    );
+    println!(
+        "{:-<3}-|-{:-<name_width$}-|-{:-<5}-|-{:-<12}-|-{:-<8}-|-{:-<7}-|-{:-<4}-|-{:-<12}",
+        "", "", "", "", "", "", "", ""
+    );
+    for (i, m) in models[..shown].iter().enumerate() {
+        println!(
+            "{:>3} | {:<name_width$} | {:>5.1} | {:<12} | {:>8} | {:>7} | {:<4} | {}",
+            i + 1,
+            m.name,
+            m.score,
+            m.best_quant,
+            format!("{:.1} GB", m.memory_gb),
+            format!("{:.1}B", m.params_b),
+            if m.is_autocomplete { "✓" } else { "" },
+            style(&m.category).dim()
+        );
+    }
+    if shown < models.len() {
+        println!(
+            "  {}",
+            style(format!(
+                "… {} more. Use --top 0 or --json to list all.",
+                models.len() - shown
+            ))
+            .dim()
+        );
+    }
+}
+
+/// Per-device VRAM. NVIDIA cards come from `nvidia-smi` in PCI bus order, which
+/// is also how CUDA numbers them once `CUDA_DEVICE_ORDER=PCI_BUS_ID` is set
+/// (the default puts the fastest card first); otherwise see [`llmfit_gpu_vram`].
+fn detect_gpu_vram(specs: &SystemSpecs) -> Vec<f32> {
+    if specs.backend == GpuBackend::Cuda
+        && let Some(vram) = nvidia_smi_vram()
+    {
+        return vram;
+    }
+    llmfit_gpu_vram(specs)
+}
+
+/// Per-device VRAM from llmfit's GPU groups for the primary backend,
+/// expanded one entry per card.
+fn llmfit_gpu_vram(specs: &SystemSpecs) -> Vec<f32> {
+    specs
+        .gpus
+        .iter()
+        .filter(|g| g.backend == specs.backend)
+        .flat_map(|g| {
+            let vram = g.vram_gb.unwrap_or(0.0) as f32;
+            std::iter::repeat_n(vram, g.count as usize)
+        })
+        .collect()
+}
+
+/// `memory.total` of every NVIDIA GPU, in GB, in PCI bus order.
+fn nvidia_smi_vram() -> Option<Vec<f32>> {
+    let output = std::process::Command::new("nvidia-smi")
+        .args(["--query-gpu=memory.total", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let vram: Vec<f32> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| l.trim().parse::<f32>().ok())
+        .map(|mib| mib / 1024.0)
+        .collect();
+    (!vram.is_empty()).then_some(vram)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_hardware_override() {
+        // What a container sees: no GPU and a sliver of RAM
+        let container = SystemSpecs {
+            total_ram_gb: 2.0,
+            available_ram_gb: 1.0,
+            total_cpu_cores: 2,
+            cpu_name: "Test CPU".to_string(),
+            has_gpu: false,
+            gpu_vram_gb: None,
+            total_gpu_vram_gb: None,
+            gpu_name: None,
+            gpu_count: 0,
+            unified_memory: false,
+            backend: GpuBackend::CpuX86,
+            gpus: vec![],
+        };
+        // What a VM might misreport
+        let vm = SystemSpecs {
+            has_gpu: true,
+            gpu_vram_gb: Some(4.0),
+            total_gpu_vram_gb: Some(4.0),
+            gpu_name: Some("Virtual GPU".to_string()),
+            gpu_count: 1,
+            backend: GpuBackend::Vulkan,
+            gpus: vec![GpuInfo {
+                name: "Virtual GPU".to_string(),
+                vram_gb: Some(4.0),
+                backend: GpuBackend::Vulkan,
+                count: 1,
+                unified_memory: false,
+            }],
+            ..container.clone()
+        };
+        let overrides: HardwareOverride = serde_json::from_str(
+            r#"{ "vram_gb": 24, "ram_gb": 64, "cpu_cores": 16, "backend": "cuda", "gpu_name": "RTX 4090" }"#,
+        )
+        .unwrap();
+
+        let a = build_profile(container.clone(), &overrides);
+        assert_eq!(a.vram_gb, 24.0);
+        assert_eq!(a.ram_gb, 64.0);
+        assert_eq!(a.cpu_cores, 16);
+        assert_eq!(a.gpu_backend, GpuBackend::Cuda);
+        assert_eq!(a.gpu_name.as_deref(), Some("RTX 4090"));
+        assert_eq!(a.gpu_vram_gb, [24.0]);
+        assert_eq!(a.available_memory_gb, 24.0);
+        assert!(!a.recommended_models.is_empty());
+
+        // Whatever was detected, the same overrides give the same sizing
+        let b = build_profile(vm.clone(), &overrides);
+        let names = |p: &HardwareProfile| {
+            p.recommended_models
+                .iter()
+                .map(|m| m.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&a), names(&b));
+        let models = vec![crate::ui::ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-14B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        }];
+        let args = |p: &HardwareProfile| {
+            crate::ui::LlamaServerArgs::from_hardware(p, &models, &Default::default()).to_cli_args()
+        };
+        assert_eq!(args(&a), args(&b));
+
+        let two_cards = HardwareOverride {
+            gpu_vram_gb: Some(vec![24.0, 12.0]),
+            ..Default::default()
+        };
+        let multi = build_profile(vm.clone(), &two_cards);
+        assert_eq!(multi.vram_gb, 36.0);
+        assert_eq!(multi.gpu_count, 2);
+        assert_eq!(multi.gpu_vram_gb, [24.0, 12.0]);
+        assert_eq!(multi.gpu_backend, GpuBackend::Vulkan);
+
+        // A backend alone keeps the detected cards and leaves them to nvidia-smi
+        let cuda = HardwareOverride {
+            backend: Some(Backend::Cuda),
+            ..Default::default()
+        };
+        assert!(!cuda.sets_gpus());
+        assert!(two_cards.sets_gpus());
+        assert_eq!(llmfit_gpu_vram(&cuda.apply(vm.clone())), [4.0]);
+
+        let cpu = build_profile(
+            vm,
+            &HardwareOverride {
+                vram_gb: Some(0.0),
+                ..Default::default()
+            },
+        );
+        assert_eq!(cpu.vram_gb, 0.0);
+        assert_eq!(cpu.gpu_count, 0);
+        assert!(cpu.gpu_vram_gb.is_empty());
+        assert!(matches!(
+            cpu.gpu_backend,
+            GpuBackend::CpuX86 | GpuBackend::CpuArm
+        ));
+
+        // Flags win over the config file
+        let flags = HardwareOverride {
+            vram_gb: Some(8.0),
+            ..Default::default()
+        };
+        let merged = overrides.merged_with(&flags);
+        assert_eq!(merged.vram_gb, Some(8.0));
+        assert_eq!(merged.ram_gb, Some(64.0));
+        assert!(HardwareOverride::default().is_empty());
+    }
+
+    #[test]
+    fn test_profile_json() {
+        let overrides = HardwareOverride {
+            gpu_vram_gb: Some(vec![24.0, 12.0]),
+            ram_gb: Some(64.0),
+            cpu_cores: Some(16),
+            gpu_name: Some("RTX 4090".to_string()),
+            backend: Some(Backend::Cuda),
+            unified_memory: Some(false),
+            ..Default::default()
+        };
+        let profile = build_profile(SystemSpecs::detect(), &overrides);
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["gpu_name"], "RTX 4090");
+        assert_eq!(json["gpu_backend"], "Cuda");
+        assert_eq!(json["gpu_count"], 2);
+        assert_eq!(json["gpu_vram_gb"], serde_json::json!([24.0, 12.0]));
+        assert_eq!(json["cpu_cores"], 16);
+        assert_eq!(json["unified_memory"], false);
+
+        let first = &json["recommended_models"][0];
+        for key in [
+            "name",
+            "score",
+            "best_quant",
+            "memory_gb",
+            "params_b",
+            "is_autocomplete",
+        ] {
+            assert!(!first[key].is_null(), "missing {}", key);
+        }
+        assert!(!first["category"].as_str().unwrap().starts_with('"'));
+    }
+
+    #[test]
+    fn test_select_gpus() {
+        let mut profile = HardwareProfile {
+            ram_gb: 64.0,
+            gpu_count: 3,
+            gpu_vram_gb: vec![24.0, 8.0, 16.0],
+            ..HardwareProfile::test_gpu(48.0)
+        };
+        profile.select_gpus(&[]);
+        assert_eq!(profile.gpu_count, 3);
+        profile.select_gpus(&[2, 0, 7]);
+        assert_eq!(profile.gpu_vram_gb, [24.0, 16.0]);
+        assert_eq!(profile.gpu_count, 2);
+        assert_eq!(profile.vram_gb, 40.0);
+        assert_eq!(profile.available_memory_gb, 40.0);
+    }
+
     #[tokio::test]
     async fn test_profile_hardware_returns_models() {
         // Run the hardware profiling, which uses llmfit_core underneath.
-        let profile = profile_hardware()
+        let profile = profile_hardware(&HardwareOverride::default())
             .await
             .expect("Failed to profile hardware");
 
@@ -1,6 +1,8 @@
-use crate::ui::ModelSelection;
+use crate::download::{HubClient, RepoInfo, check_space};
+use crate::swap_config::{Filters, Group, LlamaSwapConfig, ModelEntry};
+use crate::ui::{LoraAdapter, ModelRole, ModelSelection};
 use anyhow::{Context, Result};
-use hf_hub::api::sync::ApiBuilder;
+use serde::{Deserialize, Serialize};
 use tokio::process::Command;
 
 /// Qwen-style Jinja chat template embedded at compile time.
@@ -13,6 +15,11 @@ pub async fn extract_hf_repo_and_file(
     model_name: &str,
     quant: &Option<String>,
 ) -> (String, Option<String>) {
+    hf_repo_and_file(model_name, quant)
+}
+
+/// Synchronous core of [`extract_hf_repo_and_file`], for callers outside async code.
+fn hf_repo_and_file(model_name: &str, quant: &Option<String>) -> (String, Option<String>) {
     if let Some(q) = quant {
         // Dynamic llmfit model — build_gguf_candidates will handle resolution.
         // Return the first candidate here; download_models tries all candidates.
@@ -32,7 +39,11 @@ pub async fn extract_hf_repo_and_file(
 
         // UD- (Unsloth Dynamic) quants are published by `unsloth`;
         // standard GGUF quants are published by `bartowski`.
-        let repo_org = if q.starts_with("UD-") { "unsloth" } else { "bartowski" };
+        let repo_org = if q.starts_with("UD-") {
+            "unsloth"
+        } else {
+            "bartowski"
+        };
         let repo = format!("{}/{}-GGUF", repo_org, base_name);
         let file = format!("{}-{}.gguf", base_name, q);
 
@@ -77,6 +88,14 @@ fn build_gguf_candidates(model_name: &str, quant: &str) -> Vec<(String, String)>
     let is_ud = quant.starts_with("UD-");
     let mut candidates = Vec::new();
 
+    // 0. An explicit GGUF repo (e.g. `localcode pull bartowski/X-GGUF`) is tried as given
+    if !org.is_empty()
+        && base_name.len() < parts[1].len()
+        && parts[1].to_lowercase().ends_with("-gguf")
+    {
+        candidates.push((model_name.to_string(), file.clone()));
+    }
+
     // 1. unsloth — publisher of UD (Unsloth Dynamic) quants; try first for UD-
     if is_ud {
         candidates.push((format!("unsloth/{}-GGUF", base_name), file.clone()));
@@ -98,23 +117,22 @@ fn build_gguf_candidates(model_name: &str, quant: &str) -> Vec<(String, String)>
     }
 
     // 5. lmstudio-community — another major GGUF publisher
-    candidates.push((
-        format!("lmstudio-community/{}-GGUF", base_name),
-        file,
-    ));
+    candidates.push((format!("lmstudio-community/{}-GGUF", base_name), file));
 
+    let mut seen = std::collections::HashSet::new();
+    candidates.retain(|(repo, _)| seen.insert(repo.clone()));
     candidates
 }
 
-/// Given a `RepoInfo` (from `hf_hub`), find the GGUF file that best matches
+/// Given a repository listing, find the GGUF file that best matches
 /// the requested quantization level.  Official repos often use simplified
 /// quant names (e.g. `q4` instead of `Q4_K_M`), so we try several heuristics.
-fn find_best_gguf_in_repo(info: &hf_hub::api::RepoInfo, quant: &str) -> Option<String> {
+fn find_best_gguf_in_repo(info: &RepoInfo, quant: &str) -> Option<String> {
     let gguf_files: Vec<&str> = info
         .siblings
         .iter()
         .map(|s| s.rfilename.as_str())
-        .filter(|f| f.ends_with(".gguf"))
+        .filter(|f| f.ends_with(".gguf") && !is_mmproj(f))
         .collect();
 
     if gguf_files.is_empty() {
@@ -136,8 +154,7 @@ fn find_best_gguf_in_repo(info: &hf_hub::api::RepoInfo, quant: &str) -> Option<S
     }
     // For UD- (Unsloth Dynamic) quants, also add tokens without the UD- prefix
     // so we can match standard quant files as a fallback.
-    if q_lower.starts_with("ud-") {
-        let stripped = &q_lower[3..];
+    if let Some(stripped) = q_lower.strip_prefix("ud-") {
         if !search_tokens.contains(&stripped.to_string()) {
             search_tokens.push(stripped.to_string());
         }
@@ -168,66 +185,343 @@ fn find_best_gguf_in_repo(info: &hf_hub::api::RepoInfo, quant: &str) -> Option<S
     Some(gguf_files[0].to_string())
 }
 
+/// Every model whose weights must be on disk: the configured ones plus their
+/// speculative-decoding drafts, each name once.
+pub fn with_drafts(models: &[ModelSelection]) -> Vec<ModelSelection> {
+    let mut all: Vec<ModelSelection> = Vec::new();
+    for m in models
+        .iter()
+        .flat_map(|m| std::iter::once(m).chain(m.draft.as_deref()))
+    {
+        if !all.iter().any(|seen| seen.name == m.name) {
+            all.push(m.clone());
+        }
+    }
+    all
+}
+
+/// `mmproj` value that turns projector auto-discovery off.
+const NO_MMPROJ: &str = "none";
+
+/// Whether a GGUF is a vision projector rather than model weights. Every
+/// publisher names them `mmproj-*.gguf` (or `mmproj.gguf`).
+pub fn is_mmproj(file: &str) -> bool {
+    let name = file
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(file)
+        .to_lowercase();
+    name.starts_with("mmproj") && name.ends_with(".gguf")
+}
+
+/// The projector to use when a repo or folder has several: F16 is what
+/// llama.cpp recommends, then BF16 and F32, then whatever comes first.
+fn pick_mmproj<'a>(files: &[&'a str]) -> Option<&'a str> {
+    let precision = |f: &str| {
+        f.to_lowercase()
+            .trim_end_matches(".gguf")
+            .rsplit(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    };
+    ["f16", "bf16", "f32"]
+        .iter()
+        .find_map(|p| files.iter().find(|f| precision(f) == *p))
+        .or(files.first())
+        .copied()
+}
+
+/// Projector to download with `model` from a repo listing: the configured
+/// `mmproj` file if the repo has it, otherwise the best `mmproj-*.gguf`.
+fn find_mmproj_in_repo(info: &RepoInfo, model: &ModelSelection) -> Option<String> {
+    let projectors: Vec<&str> = info
+        .siblings
+        .iter()
+        .map(|s| s.rfilename.as_str())
+        .filter(|f| is_mmproj(f))
+        .collect();
+    match model.mmproj.as_deref() {
+        Some(NO_MMPROJ) => None,
+        Some(wanted) => projectors
+            .into_iter()
+            .find(|f| {
+                f.rsplit('/')
+                    .next()
+                    .unwrap_or(f)
+                    .eq_ignore_ascii_case(wanted)
+            })
+            .map(str::to_string),
+        None => pick_mmproj(&projectors).map(str::to_string),
+    }
+}
+
+/// The vision projector for `model`, whose weights are at `model_path`.
+///
+/// An explicit `mmproj` is a path, or a file name looked up next to the
+/// weights and then anywhere in `models_dir`. Otherwise a projector is picked
+/// up automatically only where it can't belong to another model: anywhere in
+/// the same Hugging Face repo (every snapshot commit), the same Ollama
+/// manifest, or next to the weights when its name contains theirs.
+pub fn find_projector_file(
+    model: &ModelSelection,
+    model_path: &std::path::Path,
+    models_dir: &std::path::Path,
+) -> Option<std::path::PathBuf> {
+    let dir = model_path.parent()?;
+    match model.mmproj.as_deref() {
+        Some(NO_MMPROJ) => None,
+        Some(wanted) => {
+            let path = std::path::PathBuf::from(shellexpand::tilde(wanted).to_string());
+            if path.components().count() > 1 {
+                return path.is_file().then_some(path);
+            }
+            let beside = dir.join(&path);
+            if beside.is_file() {
+                return Some(beside);
+            }
+            find_local_gguf(models_dir, wanted)
+                .map(|rel| models_dir.join(rel.replace('/', std::path::MAIN_SEPARATOR_STR)))
+        }
+        None if dir.file_name().is_some_and(|n| n == "blobs") => {
+            // Ollama: the projector is another layer of the same manifest
+            let ollama = crate::models::scan_ollama_cache(dir.parent()?);
+            let name = ollama.iter().find(|m| m.path == model_path)?.name.clone();
+            ollama
+                .into_iter()
+                .find(|m| m.name == name && m.kind == crate::models::FileKind::Projector)
+                .map(|m| m.path)
+        }
+        None => {
+            let candidates: Vec<std::path::PathBuf> = match hf_snapshots_dir(model_path) {
+                Some(snapshots) => walkdir::WalkDir::new(snapshots)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| !e.file_type().is_dir())
+                    .map(|e| e.into_path())
+                    .collect(),
+                // A plain folder can hold several models
+                None => {
+                    let base = weights_base_name(model, model_path)?;
+                    std::fs::read_dir(dir)
+                        .ok()?
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.path())
+                        .filter(|p| {
+                            p.file_name()
+                                .is_some_and(|n| n.to_string_lossy().to_lowercase().contains(&base))
+                        })
+                        .collect()
+                }
+            };
+            let mut candidates = candidates;
+            // The weights' own snapshot commit first
+            candidates.sort_by_key(|p| !p.starts_with(dir));
+            let names: Vec<String> = candidates
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .filter(|p| is_mmproj(p))
+                .collect();
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            pick_mmproj(&names).map(std::path::PathBuf::from)
+        }
+    }
+}
+
+/// `models--org--repo/snapshots` when `model_path` is inside a Hugging Face
+/// cache snapshot.
+fn hf_snapshots_dir(model_path: &std::path::Path) -> Option<&std::path::Path> {
+    model_path.ancestors().find(|a| {
+        a.file_name().is_some_and(|n| n == "snapshots")
+            && a.parent()
+                .and_then(|r| r.file_name())
+                .is_some_and(|n| n.to_string_lossy().starts_with("models--"))
+    })
+}
+
+/// `org/repo` for a `models--org--repo/snapshots` directory.
+fn hf_repo_id(snapshots: &std::path::Path) -> Option<String> {
+    let dir = snapshots
+        .parent()?
+        .file_name()?
+        .to_string_lossy()
+        .to_string();
+    Some(dir.strip_prefix("models--")?.replacen("--", "/", 1))
+}
+
+/// Lowercase file name of the weights without extension and quant suffix,
+/// e.g. `qwen2.5-vl-7b-instruct` for `Qwen2.5-VL-7B-Instruct-Q4_K_M.gguf`.
+fn weights_base_name(model: &ModelSelection, model_path: &std::path::Path) -> Option<String> {
+    let stem = model_path.file_stem()?.to_string_lossy().to_lowercase();
+    let base = match model.quant.as_deref().map(str::to_lowercase) {
+        Some(quant) => stem
+            .strip_suffix(&quant)
+            .map(|b| b.trim_end_matches(['-', '_', '.']).to_string())
+            .unwrap_or(stem),
+        None => stem,
+    };
+    (!base.is_empty()).then_some(base)
+}
+
+/// Adapter GGUF to download from a repo listing: the configured `file`, or
+/// the first GGUF in the repo.
+fn find_adapter_in_repo(info: &RepoInfo, adapter: &LoraAdapter) -> Option<String> {
+    let mut ggufs = info
+        .siblings
+        .iter()
+        .map(|s| s.rfilename.as_str())
+        .filter(|f| f.ends_with(".gguf"));
+    match adapter.file.as_deref() {
+        Some(wanted) => ggufs
+            .find(|f| {
+                f.rsplit('/')
+                    .next()
+                    .unwrap_or(f)
+                    .eq_ignore_ascii_case(wanted)
+            })
+            .map(str::to_string),
+        None => ggufs.next().map(str::to_string),
+    }
+}
+
+/// The adapter GGUF on disk: a local `source` as given, or a repo's snapshot
+/// in `models_dir` (the configured `file`, else its first GGUF).
+pub fn find_adapter_file(
+    adapter: &LoraAdapter,
+    models_dir: &std::path::Path,
+) -> Option<std::path::PathBuf> {
+    if adapter.is_local() {
+        let path = std::path::PathBuf::from(shellexpand::tilde(&adapter.source).to_string());
+        return path.is_file().then_some(path);
+    }
+    let snapshots = models_dir
+        .join(format!("models--{}", adapter.source.replace('/', "--")))
+        .join("snapshots");
+    for commit in std::fs::read_dir(snapshots).ok()?.filter_map(|e| e.ok()) {
+        let found = walkdir::WalkDir::new(commit.path())
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| !e.file_type().is_dir())
+            .map(|e| e.into_path())
+            .find(|p| {
+                let name = p.file_name().unwrap_or_default().to_string_lossy();
+                match adapter.file.as_deref() {
+                    Some(wanted) => name.eq_ignore_ascii_case(wanted),
+                    None => name.ends_with(".gguf"),
+                }
+            });
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+/// Every file `model` needs that is already on disk: its weights, vision
+/// projector and LoRA adapters. Empty when the weights aren't there.
+pub fn model_files(
+    model: &ModelSelection,
+    models_dir: &std::path::Path,
+    downloaded_files: &std::collections::HashMap<String, std::path::PathBuf>,
+) -> Vec<std::path::PathBuf> {
+    let Some(path) = find_model_file(model, models_dir, downloaded_files) else {
+        return Vec::new();
+    };
+    let projector = find_projector_file(model, &path, models_dir);
+    let adapters = model
+        .adapters
+        .iter()
+        .filter_map(|a| find_adapter_file(a, models_dir));
+    std::iter::once(path)
+        .chain(projector)
+        .chain(adapters)
+        .collect()
+}
+
+/// Files to fetch from one repo for one configured model.
+struct PendingDownload {
+    model: String,
+    repo: String,
+    info: RepoInfo,
+    /// The weights, unless an existing copy is reused.
+    file: Option<String>,
+    mmproj: Option<String>,
+    adapter: Option<String>,
+}
+
+/// Resolve and download every model in `models` that isn't cached yet.
+///
+/// A model's vision projector is fetched alongside it: the configured
+/// `mmproj`, or any `mmproj-*.gguf` in the same repo. Weights downloaded
+/// earlier get a projector the repo has gained since. LoRA adapters that name
+/// a Hub repo are fetched from that repo.
+///
+/// All files are resolved first so their combined size can be checked against
+/// free space before any bytes are written. On a shortfall the user is asked
+/// whether to continue when `interactive`, otherwise this fails with
+/// [`InsufficientSpace`].
 pub async fn download_models(
     models: &[ModelSelection],
     models_dir: &std::path::Path,
+    extra_dirs: &[String],
+    interactive: bool,
 ) -> Result<std::collections::HashMap<String, std::path::PathBuf>> {
     use console::style;
     use indicatif::{ProgressBar, ProgressStyle};
 
-    // Maps model name → actual local path returned by hf_hub::get().
+    // Maps model name → actual local path returned by HubClient::download().
     // Used by start_llama_swap_docker to generate --model /models/... args
     // without needing to re-discover files via find_local_gguf.
-    let mut downloaded_files: std::collections::HashMap<String, std::path::PathBuf> = std::collections::HashMap::new();
+    let mut downloaded_files: std::collections::HashMap<String, std::path::PathBuf> =
+        std::collections::HashMap::new();
 
-    let api = ApiBuilder::new()
-        .with_cache_dir(models_dir.to_path_buf())
-        .with_token(std::env::var("HF_TOKEN").ok())
-        .build()?;
+    let client = HubClient::new(models_dir)?;
 
     // Pre-scan: gather all locally available models
-    let local_models = crate::models::find_all_local_models(models_dir);
+    let local_models = crate::models::find_all_local_models(models_dir, extra_dirs);
+    let mut pending: Vec<PendingDownload> = Vec::new();
 
-    for m in models {
+    for m in &with_drafts(models) {
         let (repo, file) = extract_hf_repo_and_file(&m.name, &m.quant).await;
 
-        if repo.is_empty() || file.is_none() {
+        // Models with an explicit local path are never downloaded
+        if repo.is_empty() || file.is_none() || m.path.is_some() {
             continue;
         }
 
         let file_name = file.unwrap();
 
-        // Check if this model file already exists locally (by filename match)
-        let already_exists = local_models
-            .iter()
-            .any(|lm| lm.name == file_name || lm.name.to_lowercase() == file_name.to_lowercase());
-
-        if already_exists {
-            // Record the path so config generation can use --model directly.
-            // Use the actual on-disk name (may differ in case from our constructed name).
-            let actual_name = local_models
-                .iter()
-                .find(|lm| lm.name.to_lowercase() == file_name.to_lowercase())
-                .map(|lm| lm.name.clone())
-                .unwrap_or_else(|| file_name.clone());
-            if let Some(rel) = find_local_gguf(models_dir, &actual_name) {
-                downloaded_files.insert(m.name.clone(), models_dir.join(rel.replace('/', std::path::MAIN_SEPARATOR_STR)));
-            }
+        // Already on disk, either in models_dir or in another app's cache.
+        // Record the path so config generation can use --model directly.
+        let mut need_weights = true;
+        let mut backfill_repo = None;
+        if let Some((path, source)) = find_existing_copy(m, models_dir, &local_models) {
             println!(
                 "{} {} {}",
                 style("✓").green().bold(),
                 style(&m.name).magenta(),
-                style("already cached locally, skipping download.").dim()
+                style(format!(
+                    "already available ({}), skipping download.",
+                    source
+                ))
+                .dim()
             );
-            continue;
+            // An explicitly configured projector may still need fetching, and
+            // so may one the repo got after these weights were downloaded
+            let projector_missing = m.mmproj.as_deref() != Some(NO_MMPROJ)
+                && find_projector_file(m, &path, models_dir).is_none();
+            let own_repo = hf_snapshots_dir(&path)
+                .filter(|_| path.starts_with(models_dir))
+                .and_then(hf_repo_id);
+            backfill_repo = own_repo.filter(|_| m.mmproj.is_none());
+            downloaded_files.insert(m.name.clone(), path);
+            if !projector_missing || (m.mmproj.is_none() && backfill_repo.is_none()) {
+                continue;
+            }
+            need_weights = false;
         }
 
-        println!(
-            "{} {}",
-            style("📥 Checking/Downloading").cyan(),
-            style(&m.name).bold().magenta()
-        );
-
         let pb = ProgressBar::new_spinner();
         pb.set_style(
             ProgressStyle::default_spinner()
@@ -235,99 +529,176 @@ pub async fn download_models(
                 .template("{spinner:.green} {msg}")
                 .unwrap(),
         );
+        pb.enable_steady_tick(std::time::Duration::from_millis(100));
 
         // For dynamic llmfit models (quant is set), try multiple GGUF sources
         // since HuggingFace repos are case-sensitive and different publishers
-        // use different naming conventions and quant labels.
-        let mut downloaded = false;
-        if m.quant.is_some() {
-            let quant_str = m.quant.as_deref().unwrap_or("Q4_K_M");
-            let candidates = build_gguf_candidates(&m.name, quant_str);
-            for (candidate_repo, candidate_file) in &candidates {
-                pb.set_message(format!("Trying {}/{}", candidate_repo, candidate_file));
-
-                // First, try the exact constructed filename
-                let repo_c = candidate_repo.clone();
-                let file_c = candidate_file.clone();
-                let api_c = api.clone();
-                let result = tokio::task::spawn_blocking(move || {
-                    let repo_api = api_c.model(repo_c);
-                    repo_api.get(&file_c)
-                })
-                .await?;
-
-                if let Ok(path) = result {
-                    downloaded_files.insert(m.name.clone(), path);
-                    pb.finish_with_message(format!(
-                        "✅ {} downloaded from {}",
-                        candidate_file, candidate_repo
-                    ));
-                    downloaded = true;
-                    break;
-                }
+        // use different naming conventions and quant labels. Legacy static
+        // models have a single known repo and file.
+        let candidates = match (&backfill_repo, m.quant.as_deref()) {
+            (Some(own), _) => vec![(own.clone(), file_name.clone())],
+            (None, Some(quant)) => build_gguf_candidates(&m.name, quant),
+            (None, None) => vec![(repo.clone(), file_name.clone())],
+        };
+        let mut found = None;
+        for (candidate_repo, candidate_file) in &candidates {
+            pb.set_message(format!(
+                "Searching {} for {}",
+                candidate_repo, candidate_file
+            ));
+            let Ok(info) = client.repo_info(candidate_repo).await else {
+                continue; // repo doesn't exist, skip
+            };
+
+            // Prefer the exact constructed filename; otherwise pick the best
+            // matching GGUF. Official repos often use different quant naming
+            // (e.g. "q4" instead of "Q4_K_M", or capital first letter).
+            let real_file = if info.file(candidate_file).is_some() {
+                Some(candidate_file.clone())
+            } else {
+                m.quant
+                    .as_deref()
+                    .and_then(|quant| find_best_gguf_in_repo(&info, quant))
+            };
+            if let Some(real_file) = real_file {
+                found = Some(PendingDownload {
+                    model: m.name.clone(),
+                    repo: candidate_repo.clone(),
+                    mmproj: find_mmproj_in_repo(&info, m),
+                    file: need_weights.then_some(real_file),
+                    adapter: None,
+                    info,
+                });
+                break;
+            }
+        }
 
-                // Exact filename failed — list repo files and find the best
-                // matching GGUF.  Official repos often use different quant
-                // naming (e.g. "q4" instead of "Q4_K_M", or capital first letter).
-                pb.set_message(format!("Searching {} for GGUF files...", candidate_repo));
-                let repo_c2 = candidate_repo.clone();
-                let quant_c = quant_str.to_string();
-                let api_c2 = api.clone();
-                let discovered = tokio::task::spawn_blocking(move || {
-                    let repo_api = api_c2.model(repo_c2);
-                    match repo_api.info() {
-                        Ok(info) => find_best_gguf_in_repo(&info, &quant_c),
-                        Err(_) => None, // repo doesn't exist, skip
-                    }
-                })
-                .await?;
-
-                if let Some(real_file) = discovered {
-                    pb.set_message(format!("Downloading {}", real_file));
-                    let repo_c3 = candidate_repo.clone();
-                    let file_c3 = real_file.clone();
-                    let api_c3 = api.clone();
-                    let result2 = tokio::task::spawn_blocking(move || {
-                        let repo_api = api_c3.model(repo_c3);
-                        repo_api.get(&file_c3)
-                    })
-                    .await?;
-
-                    if let Ok(path) = result2 {
-                        downloaded_files.insert(m.name.clone(), path);
-                        pb.finish_with_message(format!(
-                            "✅ {} downloaded from {}",
-                            real_file, candidate_repo
-                        ));
-                        downloaded = true;
-                        break;
-                    }
+        match found {
+            Some(found) => {
+                pb.finish_and_clear();
+                let wanted = m.mmproj.as_deref().filter(|p| *p != NO_MMPROJ);
+                if let Some(wanted) = wanted.filter(|_| found.mmproj.is_none()) {
+                    println!(
+                        "{} {} {}",
+                        style("⚠️ ").yellow().bold(),
+                        style(&m.name).magenta(),
+                        style(format!("projector {} not found in {}", wanted, found.repo)).dim()
+                    );
+                }
+                if found.file.is_some() || found.mmproj.is_some() {
+                    pending.push(found);
                 }
             }
-        } else {
-            // Legacy static model — single repo from extract_hf_repo_and_file
-            pb.set_message(format!("Downloading {}", file_name));
-            let repo_clone = repo.clone();
-            let file_name_clone = file_name.clone();
-            let api_clone = api.clone();
-            let result = tokio::task::spawn_blocking(move || {
-                let repo_api = api_clone.model(repo_clone);
-                repo_api.get(&file_name_clone)
-            })
-            .await?;
+            // Only looked for a projector the weights may not have
+            None if backfill_repo.is_some() => pb.finish_and_clear(),
+            None => pb.finish_with_message(format!(
+                "⚠️  Could not find GGUF for {} in any known repository",
+                m.name
+            )),
+        }
+    }
 
-            if let Ok(path) = result {
-                downloaded_files.insert(m.name.clone(), path);
-                pb.finish_with_message(format!("✅ {} downloaded.", file_name));
-                downloaded = true;
+    // LoRA adapters published on the Hub come from their own repos
+    for m in models {
+        for adapter in &m.adapters {
+            if adapter.is_local() || find_adapter_file(adapter, models_dir).is_some() {
+                continue;
+            }
+            let name = adapter.served_name(&m.name);
+            let found = match client.repo_info(&adapter.source).await {
+                Ok(info) => find_adapter_in_repo(&info, adapter).map(|file| (info, file)),
+                Err(_) => None,
+            };
+            match found {
+                Some((info, file)) => pending.push(PendingDownload {
+                    model: name,
+                    repo: adapter.source.clone(),
+                    info,
+                    file: None,
+                    mmproj: None,
+                    adapter: Some(file),
+                }),
+                None => println!(
+                    "{} {} {}",
+                    style("⚠️ ").yellow().bold(),
+                    style(&name).magenta(),
+                    style(format!(
+                        "adapter {} not found in {}",
+                        adapter.file.as_deref().unwrap_or("GGUF"),
+                        adapter.source
+                    ))
+                    .dim()
+                ),
             }
         }
+    }
 
-        if !downloaded {
-            pb.finish_with_message(format!(
-                "⚠️  Could not find GGUF for {} in any known repository",
-                m.name
-            ));
+    // Pre-flight: make sure everything fits before starting any download.
+    let needed: u64 = pending
+        .iter()
+        .flat_map(|p| {
+            [&p.file, &p.mmproj, &p.adapter]
+                .into_iter()
+                .flatten()
+                .map(|file| client.remaining_bytes(&p.repo, &p.info, file))
+        })
+        .sum();
+    if needed > 0 {
+        println!(
+            "{} {} to download for {} model(s)",
+            style("📦").cyan(),
+            style(human_bytes::human_bytes(needed as f64)).bold(),
+            pending.len()
+        );
+    }
+    if let Err(shortfall) = check_space(models_dir, needed) {
+        println!("{} {}", style("⚠️ ").yellow().bold(), shortfall);
+        let proceed = interactive
+            && inquire::Confirm::new("Download anyway?")
+                .with_default(false)
+                .with_help_message("Free up space or change models_dir in localcode.json")
+                .prompt()
+                .unwrap_or(false);
+        if !proceed {
+            return Err(shortfall.into());
+        }
+    }
+
+    for p in pending {
+        // Download failures are fatal: the repo exists and lists the file, so
+        // falling back to another publisher would only mask the real problem.
+        if let Some(file) = &p.file {
+            println!(
+                "{} {} {}",
+                style("📥 Downloading").cyan(),
+                style(&p.model).bold().magenta(),
+                style(format!("({}/{})", p.repo, file)).dim()
+            );
+            let path = client.download(&p.repo, &p.info, file).await?;
+            println!("  {} {} downloaded.", style("✅").green(), file);
+            downloaded_files.insert(p.model.clone(), path);
+        }
+        if let Some(mmproj) = &p.mmproj {
+            println!(
+                "{} {} {}",
+                style("📥 Downloading").cyan(),
+                style(format!("{} vision projector", p.model))
+                    .bold()
+                    .magenta(),
+                style(format!("({}/{})", p.repo, mmproj)).dim()
+            );
+            client.download(&p.repo, &p.info, mmproj).await?;
+            println!("  {} {} downloaded.", style("✅").green(), mmproj);
+        }
+        if let Some(adapter) = &p.adapter {
+            println!(
+                "{} {} {}",
+                style("📥 Downloading").cyan(),
+                style(format!("{} LoRA adapter", p.model)).bold().magenta(),
+                style(format!("({}/{})", p.repo, adapter)).dim()
+            );
+            client.download(&p.repo, &p.info, adapter).await?;
+            println!("  {} {} downloaded.", style("✅").green(), adapter);
         }
     }
 
@@ -358,98 +729,463 @@ fn find_local_gguf(models_dir: &std::path::Path, filename: &str) -> Option<Strin
     None
 }
 
-pub async fn start_llama_swap_docker(
+/// Locate the GGUF for a configured model on disk: the path recorded by
+/// `download_models` if there is one, otherwise a filename match in `models_dir`.
+pub fn find_model_file(
+    model: &ModelSelection,
+    models_dir: &std::path::Path,
+    downloaded_files: &std::collections::HashMap<String, std::path::PathBuf>,
+) -> Option<std::path::PathBuf> {
+    if let Some(path) = downloaded_files.get(&model.name) {
+        return Some(path.clone());
+    }
+    if let Some(ref path) = model.path {
+        return Some(std::path::PathBuf::from(
+            shellexpand::tilde(path).to_string(),
+        ));
+    }
+    let file = expected_file_name(model)?;
+    if let Some(rel) = find_local_gguf(models_dir, &file) {
+        return Some(models_dir.join(rel.replace('/', std::path::MAIN_SEPARATOR_STR)));
+    }
+    find_in_hf_cache(model, models_dir)
+}
+
+/// File name `download_models` would look for first (legacy models carry a full URL).
+fn expected_file_name(model: &ModelSelection) -> Option<String> {
+    let (_, file) = hf_repo_and_file(&model.name, &model.quant);
+    file.map(|f| f.rsplit('/').next().unwrap_or(&f).to_string())
+}
+
+/// A previous download from one of the candidate repos, which may have picked a
+/// differently named file (e.g. `q4` instead of `Q4_K_M`) via `find_best_gguf_in_repo`.
+/// Looks in `models_dir` and in `hub/`, where llama-server keeps `--hf-repo`
+/// downloads with `HF_HOME=/models`.
+fn find_in_hf_cache(
+    model: &ModelSelection,
+    models_dir: &std::path::Path,
+) -> Option<std::path::PathBuf> {
+    let quant = model.quant.as_deref()?;
+    let roots = [models_dir.to_path_buf(), models_dir.join("hub")];
+    for (repo, _) in build_gguf_candidates(&model.name, quant) {
+        let repo_dir = format!("models--{}", repo.replace('/', "--"));
+        let commits = roots
+            .iter()
+            .filter_map(|root| std::fs::read_dir(root.join(&repo_dir).join("snapshots")).ok())
+            .flatten();
+        for commit in commits.filter_map(|e| e.ok()) {
+            let siblings = walkdir::WalkDir::new(commit.path())
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| !e.file_type().is_dir())
+                .filter_map(|e| {
+                    let rel = e.path().strip_prefix(commit.path()).ok()?;
+                    Some(crate::download::RepoFile {
+                        rfilename: rel.to_string_lossy().replace('\\', "/"),
+                        size: None,
+                        blob_id: None,
+                        lfs: None,
+                    })
+                })
+                .collect();
+            let listing = RepoInfo {
+                sha: String::new(),
+                siblings,
+            };
+            if let Some(file) = find_best_gguf_in_repo(&listing, quant) {
+                return Some(commit.path().join(file));
+            }
+        }
+    }
+    None
+}
+
+/// An existing copy of `model` on this machine and where it was found:
+/// `models_dir` (by path or file name), then other apps' caches from
+/// `local_models` — LM Studio by file name, Ollama by GGUF header, since its
+/// blobs are named by digest.
+pub fn find_existing_copy(
+    model: &ModelSelection,
+    models_dir: &std::path::Path,
+    local_models: &[crate::models::DiscoveredModel],
+) -> Option<(std::path::PathBuf, String)> {
+    if let Some(path) = find_model_file(model, models_dir, &std::collections::HashMap::new())
+        .filter(|p| p.is_file())
+    {
+        let source = if model.path.is_some() {
+            "configured path"
+        } else {
+            "models dir"
+        };
+        return Some((path, source.to_string()));
+    }
+    if model.path.is_some() {
+        return None;
+    }
+
+    let file = expected_file_name(model)?;
+    if let Some(lm) = local_models
+        .iter()
+        .find(|lm| lm.name.eq_ignore_ascii_case(&file))
+    {
+        return Some((lm.path.clone(), lm.source.clone()));
+    }
+
+    let quant = model.quant.as_deref()?;
+    local_models
+        .iter()
+        .filter(|lm| lm.source == "Ollama" && lm.kind == crate::models::FileKind::Model)
+        .find(|lm| {
+            crate::gguf::read_metadata(&lm.path)
+                .map(|meta| gguf_matches(&meta, &model.name, quant))
+                .unwrap_or(false)
+        })
+        .map(|lm| (lm.path.clone(), lm.source.clone()))
+}
+
+/// Whether a GGUF header describes `model_name` at quantization `quant`:
+/// `general.name` equals the repo's model name ignoring case and punctuation
+/// ("Qwen2.5 Coder 7B Instruct" ~ "Qwen/Qwen2.5-Coder-7B-Instruct"), and the
+/// file type is that quant.
+fn gguf_matches(meta: &crate::gguf::GgufMetadata, model_name: &str, quant: &str) -> bool {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let base = model_name.rsplit('/').next().unwrap_or(model_name);
+    let base = base
+        .strip_suffix("-GGUF")
+        .or_else(|| base.strip_suffix("-AWQ"))
+        .or_else(|| base.strip_suffix("-GPTQ"))
+        .unwrap_or(base);
+    let name_matches = meta
+        .name
+        .as_deref()
+        .is_some_and(|n| normalize(n) == normalize(base));
+    let quant = quant.strip_prefix("UD-").unwrap_or(quant);
+    name_matches && meta.file_type.is_some() && meta.file_type == crate::gguf::file_type_for(quant)
+}
+
+/// Container path for a host path outside `models_dir`. Derived from the host
+/// path itself, so the mount and every `--model` argument agree without
+/// sharing state: `/home/me/.ollama/models/blobs` → `/external/home/me/.ollama/models/blobs`.
+fn external_container_path(host_path: &std::path::Path) -> String {
+    let host = host_path
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace(':', "");
+    format!("/external/{}", host.trim_start_matches('/'))
+}
+
+/// Host directories holding configured models (and their projectors and
+/// adapters) that live outside `models_dir` (Ollama blobs, LM Studio,
+/// explicit paths). Each is mounted read-only so existing weights are served
+/// in place instead of being downloaded again.
+pub fn external_model_dirs(
     models: &[ModelSelection],
     models_dir: &std::path::Path,
-    port: u16,
-    llama_server_args: Option<&crate::ui::LlamaServerArgs>,
+    downloaded_files: &std::collections::HashMap<String, std::path::PathBuf>,
+) -> Vec<std::path::PathBuf> {
+    let mut dirs: Vec<std::path::PathBuf> = Vec::new();
+    for m in &with_drafts(models) {
+        for file in model_files(m, models_dir, downloaded_files) {
+            if file.starts_with(models_dir) {
+                continue;
+            }
+            let real = file.canonicalize().unwrap_or(file);
+            if let Some(dir) = real.parent()
+                && !dirs.iter().any(|d| d == dir)
+            {
+                dirs.push(dir.to_path_buf());
+            }
+        }
+    }
+    dirs
+}
+
+/// Offline counterpart of `download_models`: resolve every configured model to
+/// a GGUF already on this machine (models dir, `extra_dirs` or any cache
+/// [`crate::models::default_scanners`] knows) without any network access. Fails listing every
+/// missing model so they can all be copied over in one go.
+pub fn resolve_local_models(
+    models: &[ModelSelection],
+    models_dir: &std::path::Path,
+    extra_dirs: &[String],
+) -> Result<std::collections::HashMap<String, std::path::PathBuf>> {
+    let local_models = crate::models::find_all_local_models(models_dir, extra_dirs);
+    let mut resolved = std::collections::HashMap::new();
+    let mut missing = Vec::new();
+
+    for m in &with_drafts(models) {
+        match find_existing_copy(m, models_dir, &local_models) {
+            Some((path, _)) => {
+                resolved.insert(m.name.clone(), path);
+            }
+            None => missing.push(match (&m.path, expected_file_name(m)) {
+                (Some(path), _) => format!("  - {} ({})", m.name, path),
+                (None, Some(file)) => format!("  - {} (expected {})", m.name, file),
+                (None, None) => format!("  - {}", m.name),
+            }),
+        }
+    }
+    for m in models {
+        for adapter in m
+            .adapters
+            .iter()
+            .filter(|a| find_adapter_file(a, models_dir).is_none())
+        {
+            missing.push(format!(
+                "  - {} (adapter {})",
+                adapter.served_name(&m.name),
+                adapter.source
+            ));
+        }
+    }
+
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Offline mode: {} model(s) are not available locally:\n{}\nCopy the GGUF files into {} or run `localcode init --offline` to choose from local models.",
+            missing.len(),
+            missing.join("\n"),
+            models_dir.display()
+        );
+    }
+    Ok(resolved)
+}
+
+/// Read GGUF headers for every configured model that is already on disk.
+/// Models that aren't downloaded yet (or fail to parse) are simply absent, and
+/// callers fall back to name-based heuristics for them.
+pub fn local_model_metadata(
+    models: &[ModelSelection],
+    models_dir: &std::path::Path,
+    downloaded_files: &std::collections::HashMap<String, std::path::PathBuf>,
+) -> std::collections::HashMap<String, crate::gguf::GgufMetadata> {
+    with_drafts(models)
+        .iter()
+        .filter_map(|m| {
+            let path = find_model_file(m, models_dir, downloaded_files)?;
+            let meta = crate::gguf::read_metadata(&path).ok()?;
+            Some((m.name.clone(), meta))
+        })
+        .collect()
+}
+
+/// `llama_server_args` sized again for the files `start` resolved, for this
+/// run only. `init` estimates models that aren't on disk yet, so values still
+/// at that estimate take the size from the GGUF headers; anything the user
+/// edited or added is kept. `None` when the args are pinned, nothing changes,
+/// or there is no hardware profile.
+pub fn resized_server_args(
+    config: &crate::ui::InitConfig,
+    models_dir: &std::path::Path,
     downloaded_files: &std::collections::HashMap<String, std::path::PathBuf>,
     profile: Option<&crate::profiling::HardwareProfile>,
-) -> Result<()> {
-    println!("📦 Launching localcode container...");
+) -> Option<crate::ui::LlamaServerArgs> {
+    if config.pin_server_args {
+        return None;
+    }
+    let profile = profile?;
+    let metadata = local_model_metadata(&config.models, models_dir, downloaded_files);
+    let sized = crate::ui::LlamaServerArgs::from_hardware(profile, &config.models, &metadata);
+    let Some(saved) = config.llama_server_args.as_ref() else {
+        return Some(sized);
+    };
+    let estimate = crate::ui::LlamaServerArgs::from_hardware(
+        profile,
+        &config.models,
+        &std::collections::HashMap::new(),
+    );
+    let resized = saved.rebased(&estimate, &sized);
+    (resized.to_cli_args() != saved.to_cli_args()).then_some(resized)
+}
 
-    // First, verify docker is installed
-    let docker_check = Command::new("docker")
-        .arg("--version")
-        .output()
-        .await
-        .context("Failed to execute docker command. Is docker installed?")?;
+/// Where a generated llama-swap config is going to run. Decides how model paths,
+/// the llama-server binary and the bind host are spelled in each `cmd:` line.
+pub enum ServerTarget {
+    /// Inside the localcode container, with `models_dir` mounted at `/models`.
+    Docker,
+    /// Directly on the host, using a native llama-server binary. `gpus` are
+    /// the host GPUs selected in `localcode.json`, which a model's `gpu`
+    /// indexes into.
+    Native {
+        llama_server: std::path::PathBuf,
+        gpus: Vec<u32>,
+    },
+}
 
-    if !docker_check.status.success() {
-        return Err(anyhow::anyhow!(
-            "Docker is not running or not installed correctly: {}",
-            String::from_utf8_lossy(&docker_check.stderr)
-        ));
+impl ServerTarget {
+    fn server_bin(&self) -> String {
+        match self {
+            ServerTarget::Docker => "llama-server".to_string(),
+            ServerTarget::Native { llama_server, .. } => llama_server.to_string_lossy().to_string(),
+        }
     }
 
-    // Attempt to forcefully remove any existing container with the same name to avoid conflicts
-    let _ = Command::new("docker")
-        .args(["rm", "-f", "localcode-llm"])
-        .output()
-        .await;
+    fn bind_host(&self) -> &'static str {
+        match self {
+            ServerTarget::Docker => "0.0.0.0",
+            // llama-swap is the only client of each upstream, keep them off the network.
+            ServerTarget::Native { .. } => "127.0.0.1",
+        }
+    }
+
+    /// `CUDA_VISIBLE_DEVICES` value for the `gpu`th usable GPU. A container
+    /// numbers its selected GPUs from 0; on the host it's the real index.
+    fn gpu_device(&self, gpu: u32) -> String {
+        match self {
+            ServerTarget::Docker => gpu.to_string(),
+            ServerTarget::Native { gpus, .. } => {
+                gpus.get(gpu as usize).copied().unwrap_or(gpu).to_string()
+            }
+        }
+    }
+
+    /// Spell a host-side model path the way llama-server will see it.
+    fn model_path(&self, models_dir: &std::path::Path, host_path: &std::path::Path) -> String {
+        match self {
+            ServerTarget::Docker => match host_path.strip_prefix(models_dir) {
+                // Convert host path to Docker-relative /models/... path
+                Ok(rel) => format!("/models/{}", rel.to_string_lossy().replace('\\', "/")),
+                // Mounted read-only by `start_llama_swap_docker`, see `external_model_dirs`
+                Err(_) => {
+                    let real = host_path
+                        .canonicalize()
+                        .unwrap_or_else(|_| host_path.to_path_buf());
+                    external_container_path(&real)
+                }
+            },
+            ServerTarget::Native { .. } => host_path.to_string_lossy().to_string(),
+        }
+    }
+}
 
-    // Generate config.yaml for llama-swap
-    let mut yaml_content =
-        String::from("includeAliasesInList: true\nsendLoadingState: true\n\nmodels:\n");
+/// Claude sonnet/opus model IDs, routed to the primary model.
+const PRIMARY_ALIASES: &[&str] = &[
+    // Claude 3.5 series
+    "claude-3-5-sonnet-20241022",
+    "claude-3-5-sonnet-latest",
+    "claude-3-opus-20240229",
+    "claude-3-sonnet-20240229",
+    // Claude 4 series
+    "claude-sonnet-4-5",
+    "claude-sonnet-4-5-20250929",
+    "claude-sonnet-4-6",
+    "claude-sonnet-4-latest",
+    "claude-opus-4-5",
+    "claude-opus-4-5-20251101",
+];
+
+/// Claude haiku model IDs, routed to the small model for subagents when there
+/// is one, otherwise to the primary model.
+const HAIKU_ALIASES: &[&str] = &[
+    "claude-3-5-haiku-20241022",
+    "claude-3-5-haiku-latest",
+    "claude-3-haiku-20240307",
+    "claude-haiku-4-5",
+    "claude-haiku-4-5-20251001",
+];
+
+/// Build the llama-swap config for the configured models.
+///
+/// The same config drives both Docker and native mode; `target` only changes
+/// how paths and the llama-server binary are written.
+pub async fn build_llama_swap_config(
+    models: &[ModelSelection],
+    models_dir: &std::path::Path,
+    llama_server_args: Option<&crate::ui::LlamaServerArgs>,
+    downloaded_files: &std::collections::HashMap<String, std::path::PathBuf>,
+    profile: Option<&crate::profiling::HardwareProfile>,
+    target: &ServerTarget,
+) -> LlamaSwapConfig {
+    let mut config = LlamaSwapConfig {
+        include_aliases_in_list: true,
+        send_loading_state: true,
+        ..Default::default()
+    };
     let mut autocomplete_models = Vec::new();
-    let mut autocomplete_model_name: Option<String> = None;
+    let mut retrieval_models = Vec::new();
 
     // Pre-scan: detect if any non-primary model qualifies as autocomplete/small.
-    // We need this BEFORE generating YAML so the primary model knows whether to
+    // We need this BEFORE building entries so the primary model knows whether to
     // claim haiku aliases or leave them for the small model.
-    let has_small_model = models.iter().skip(1).any(|m| is_autocomplete_model(&m.name));
-
-    let mut assigned_aliases = false;
-    for m in models {
+    let metadata = local_model_metadata(models, models_dir, downloaded_files);
+    let primary = primary_index(models);
+    let has_small_model = client_models(models, &metadata).1.is_some();
+
+    for (i, m) in models.iter().enumerate() {
+        let is_primary = Some(i) == primary;
+        let role = model_role(is_primary, m, metadata.get(&m.name));
+        let is_retrieval = m.is_retrieval();
         let (repo, file) = extract_hf_repo_and_file(&m.name, &m.quant).await;
+        let is_autocomplete = role == ModelRole::Autocomplete;
 
-        // Quote the model name key to handle slashes or special chars safely in YAML
-        yaml_content.push_str(&format!("  \"{}\":\n", m.name));
-
-        let is_autocomplete = is_autocomplete_model(&m.name);
-
-        // Only add to autocomplete group if it's NOT the primary model (assigned_aliases is true after the first)
-        if assigned_aliases && is_autocomplete {
+        // Only the non-primary models go into the autocomplete group
+        if !is_primary && is_autocomplete {
             autocomplete_models.push(m.name.clone());
-            if autocomplete_model_name.is_none() {
-                autocomplete_model_name = Some(m.name.clone());
-            }
+        }
+        if is_retrieval {
+            retrieval_models.push(m.name.clone());
         }
 
-        // Prefer the actual path returned by download_models (hf_hub::get()).
+        // Prefer the actual path returned by download_models.
         // This avoids filename mismatch issues from fuzzy matching (e.g.
         // "Phi-3-mini-4k-instruct-q4.gguf" vs "phi-3-...-Q4_K_M.gguf").
-        let source_args = if let Some(local_path) = downloaded_files.get(&m.name) {
-            // Convert host path to Docker-relative /models/... path
-            let rel = local_path
-                .strip_prefix(models_dir)
-                .unwrap_or(local_path.as_path());
-            let docker_path = rel.to_string_lossy().replace('\\', "/");
-            format!("--model /models/{}", docker_path)
+        let source_args = if let Some(local_path) = find_model_file(m, models_dir, downloaded_files)
+        {
+            match find_projector_file(m, &local_path, models_dir) {
+                // Vision: image inputs go through the projector
+                Some(projector) => format!(
+                    "--model {} --mmproj {}",
+                    target.model_path(models_dir, &local_path),
+                    target.model_path(models_dir, &projector)
+                ),
+                None => format!("--model {}", target.model_path(models_dir, &local_path)),
+            }
         } else if let Some(ref f) = file {
-            // Fallback: try to find the canonical filename locally
-            if let Some(rel_path) = find_local_gguf(models_dir, f) {
-                format!("--model /models/{}", rel_path)
+            // Last resort: HF download at runtime (requires SSL in llama-server)
+            let repo_part = if !repo.is_empty() {
+                format!("--hf-repo {} ", repo)
             } else {
-                // Last resort: HF download at runtime (requires SSL in llama-server)
-                let repo_part = if !repo.is_empty() {
-                    format!("--hf-repo {}", repo)
-                } else {
-                    String::new()
-                };
-                format!("{} --hf-file {}", repo_part, f)
-            }
+                String::new()
+            };
+            format!("{}--hf-file {}", repo_part, f)
         } else if !repo.is_empty() {
             format!("--hf-repo {}", repo)
         } else {
             String::new()
         };
 
-        let mut custom_args = if assigned_aliases {
+        // Speculative decoding: llama-server loads the draft next to the
+        // primary. Only a draft already on disk is used.
+        let draft_args = m
+            .draft
+            .as_deref()
+            .filter(|_| is_primary)
+            .and_then(|d| find_model_file(d, models_dir, downloaded_files))
+            .map(|path| format!("--model-draft {}", target.model_path(models_dir, &path)))
+            .unwrap_or_default();
+
+        let mut custom_args = if is_retrieval {
+            crate::ui::LlamaServerArgs::for_retrieval_model(
+                llama_server_args,
+                m,
+                profile,
+                metadata.get(&m.name),
+            )
+            .to_cli_args()
+        } else if !is_primary {
             // Secondary/autocomplete model: compute lighter per-model args with
             // ctx_size capped to the model's native training context.
             if let (Some(primary), Some(prof)) = (llama_server_args, profile) {
-                let secondary_args = crate::ui::LlamaServerArgs::for_secondary_model(primary, m, prof);
+                let secondary_args = crate::ui::LlamaServerArgs::for_secondary_model(
+                    primary,
+                    m,
+                    prof,
+                    metadata.get(&m.name),
+                );
                 secondary_args.to_cli_args()
             } else {
                 llama_server_args
@@ -457,15 +1193,27 @@ pub async fn start_llama_swap_docker(
                     .unwrap_or_else(|| "--ctx-size 32768".to_string())
             }
         } else {
-            // Primary model: use the pre-computed args from from_hardware()
+            // Primary model: use the pre-computed args from from_hardware(),
+            // minus the draft tuning when there is no draft to load
             llama_server_args
-                .map(|a| a.to_cli_args())
+                .map(|a| {
+                    let mut a = a.clone();
+                    if draft_args.is_empty() {
+                        for key in crate::ui::LlamaServerArgs::DRAFT_ARGS {
+                            a.extra_args.remove(key);
+                        }
+                    }
+                    a.to_cli_args()
+                })
                 .unwrap_or_else(|| "--ctx-size 32768".to_string())
         };
 
         // Sanitize Windows absolute paths for Docker.
         // If an arg contains a path like C:\Users\..., rewrite it to use /models/ relative to the container.
-        if custom_args.contains(':') && custom_args.contains('\\') {
+        if matches!(target, ServerTarget::Docker)
+            && custom_args.contains(':')
+            && custom_args.contains('\\')
+        {
             let expanded_models_dir =
                 shellexpand::tilde(models_dir.to_str().unwrap_or("")).to_string();
             // This is a naive but effective replacement for common local paths mapped to /models
@@ -474,28 +1222,55 @@ pub async fn start_llama_swap_docker(
                 .replace('\\', "/");
         }
 
-        if !assigned_aliases {
-            assigned_aliases = true;
-            yaml_content.push_str(&format!(
-                // Let each model use its OWN built-in chat template for tool calling.
-                // The native template is stored inside the GGUF and llama.cpp knows how
-                // to parse its tool-call format (Qwen <tool_call>, Llama <|python_tag|>, etc.).
-                // Overriding with a custom template breaks non-Qwen models because they
-                // don't generate the Qwen-specific <tool_call> XML tags.
-                //
-                // --reasoning-format none: prevents llama-server from incorrectly
-                // auto-detecting a reasoning format (e.g. "deepseek" for Qwen) which
-                // disrupts grammar-constrained tool-call generation.
-                //
-                // Context is capped at the model's native training length
-                // (e.g. 32768 for Qwen2.5-7B).  YaRN rope scaling was removed
-                // because extending context beyond the training window causes
-                // attention degradation that produces gibberish — especially on
-                // ≤14B models doing structured tool-call generation.
-                "    cmd: llama-server --port ${{PORT}} {} --host 0.0.0.0 --jinja --reasoning-format none {}\n",
-                source_args,
-                custom_args
-            ));
+        // from_hardware() points the slot cache at the container mount; on the
+        // host that directory doesn't exist, so use the real models dir.
+        if matches!(target, ServerTarget::Native { .. }) {
+            custom_args = custom_args.replace(
+                "--slot-save-path /models",
+                &format!("--slot-save-path {}", models_dir.to_string_lossy()),
+            );
+        }
+
+        // Let each model use its OWN built-in chat template for tool calling.
+        // The native template is stored inside the GGUF and llama.cpp knows how
+        // to parse its tool-call format (Qwen <tool_call>, Llama <|python_tag|>, etc.).
+        // Overriding with a custom template breaks non-Qwen models because they
+        // don't generate the Qwen-specific <tool_call> XML tags.
+        //
+        // --reasoning-format none: prevents llama-server from incorrectly
+        // auto-detecting a reasoning format (e.g. "deepseek" for Qwen) which
+        // disrupts grammar-constrained tool-call generation.
+        //
+        // Context is capped at the model's native training length
+        // (e.g. 32768 for Qwen2.5-7B).  YaRN rope scaling was removed
+        // because extending context beyond the training window causes
+        // attention degradation that produces gibberish — especially on
+        // ≤14B models doing structured tool-call generation.
+        //
+        // Embedding and reranker models have no chat template to apply.
+        let chat_args = if is_retrieval {
+            ""
+        } else {
+            "--jinja --reasoning-format none"
+        };
+        let cmd = [
+            target.server_bin(),
+            "--port ${PORT}".to_string(),
+            source_args,
+            draft_args,
+            format!("--host {}", target.bind_host()),
+            chat_args.to_string(),
+            custom_args,
+        ]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+        let (filters, aliases) = if is_retrieval {
+            // No sampling to override, and no Claude model IDs to answer for
+            (Filters::default(), Vec::new())
+        } else if is_primary {
             // strip_params: prevent Claude Code from overriding local model's
             // sampling settings (temperature, top_k, etc.) which degrades quality.
             //
@@ -506,89 +1281,285 @@ pub async fn start_llama_swap_docker(
             // bypassing the grammar entirely and producing raw text instead of
             // structured tool_use blocks. The Anthropic-format object
             // `{"type":"any"}` is converted to OpenAI `"required"` internally.
-            yaml_content.push_str("    filters:\n");
-            yaml_content
-                .push_str("      strip_params: \"temperature, top_k, top_p, repeat_penalty\"\n");
-            yaml_content.push_str("      setParams:\n");
-            yaml_content.push_str("        tool_choice: \"auto\"\n");
-            yaml_content.push_str("    aliases:\n");
-            // Claude 3.5 series — sonnet/opus → primary model
-            yaml_content.push_str("      - \"claude-3-5-sonnet-20241022\"\n");
-            yaml_content.push_str("      - \"claude-3-5-sonnet-latest\"\n");
-            yaml_content.push_str("      - \"claude-3-opus-20240229\"\n");
-            yaml_content.push_str("      - \"claude-3-sonnet-20240229\"\n");
-            // Claude 4 series — sonnet/opus → primary model
-            yaml_content.push_str("      - \"claude-sonnet-4-5\"\n");
-            yaml_content.push_str("      - \"claude-sonnet-4-5-20250929\"\n");
-            yaml_content.push_str("      - \"claude-sonnet-4-6\"\n");
-            yaml_content.push_str("      - \"claude-sonnet-4-latest\"\n");
-            yaml_content.push_str("      - \"claude-opus-4-5\"\n");
-            yaml_content.push_str("      - \"claude-opus-4-5-20251101\"\n");
+            let filters = Filters {
+                strip_params: Some("temperature, top_k, top_p, repeat_penalty".to_string()),
+                set_params: [("tool_choice".to_string(), serde_json::json!("auto"))]
+                    .into_iter()
+                    .collect(),
+            };
+            let mut aliases: Vec<String> = PRIMARY_ALIASES.iter().map(|a| a.to_string()).collect();
             // If there's NO small model, haiku aliases also go to primary
             if !has_small_model {
-                yaml_content.push_str("      - \"claude-3-5-haiku-20241022\"\n");
-                yaml_content.push_str("      - \"claude-3-5-haiku-latest\"\n");
-                yaml_content.push_str("      - \"claude-3-haiku-20240307\"\n");
-                yaml_content.push_str("      - \"claude-haiku-4-5\"\n");
-                yaml_content.push_str("      - \"claude-haiku-4-5-20251001\"\n");
+                aliases.extend(HAIKU_ALIASES.iter().map(|a| a.to_string()));
             }
+            (filters, aliases)
         } else {
-            yaml_content.push_str(&format!(
-                // Secondary/autocomplete models.
-                "    cmd: llama-server --port ${{PORT}} {} --host 0.0.0.0 --jinja --reasoning-format none {}\n",
-                source_args,
-                custom_args
-            ));
-            // Same filters for the small model.
-            yaml_content.push_str("    filters:\n");
-            yaml_content
-                .push_str("      strip_params: \"temperature, top_k, top_p, repeat_penalty, frequency_penalty, presence_penalty\"\n");
-            yaml_content.push_str("      setParams:\n");
-            yaml_content.push_str("        max_tokens: 2048\n");
-            yaml_content.push_str("        temperature: 0\n");
-            yaml_content.push_str("        top_p: 1.0\n");
-            yaml_content.push_str("        repeat_penalty: 1.3\n");
-            yaml_content.push_str("        frequency_penalty: 0.5\n");
-            yaml_content.push_str("        presence_penalty: 0.3\n");
-            yaml_content.push_str("        tool_choice: \"auto\"\n");
+            // Secondary/autocomplete models: deterministic, short completions.
+            let filters = Filters {
+                strip_params: Some(
+                    "temperature, top_k, top_p, repeat_penalty, frequency_penalty, presence_penalty"
+                        .to_string(),
+                ),
+                set_params: [
+                    ("max_tokens", serde_json::json!(2048)),
+                    ("temperature", serde_json::json!(0)),
+                    ("top_p", serde_json::json!(1.0)),
+                    ("repeat_penalty", serde_json::json!(1.3)),
+                    ("frequency_penalty", serde_json::json!(0.5)),
+                    ("presence_penalty", serde_json::json!(0.3)),
+                    ("tool_choice", serde_json::json!("auto")),
+                ]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            };
             // Haiku aliases → small model for subagent routing
-            if is_autocomplete {
-                yaml_content.push_str("    aliases:\n");
-                yaml_content.push_str("      - \"claude-3-5-haiku-20241022\"\n");
-                yaml_content.push_str("      - \"claude-3-5-haiku-latest\"\n");
-                yaml_content.push_str("      - \"claude-3-haiku-20240307\"\n");
-                yaml_content.push_str("      - \"claude-haiku-4-5\"\n");
-                yaml_content.push_str("      - \"claude-haiku-4-5-20251001\"\n");
-            }
-        }
+            let aliases = if is_autocomplete {
+                HAIKU_ALIASES.iter().map(|a| a.to_string()).collect()
+            } else {
+                Vec::new()
+            };
+            (filters, aliases)
+        };
+
+        // A pinned model only sees its own GPU
+        let env: Vec<String> = m
+            .gpu
+            .map(|gpu| {
+                vec![
+                    CUDA_DEVICE_ORDER.to_string(),
+                    format!("CUDA_VISIBLE_DEVICES={}", target.gpu_device(gpu)),
+                ]
+            })
+            .unwrap_or_default();
+
+        // LoRA: each adapter is its own model running the same command with
+        // the adapter applied. It stays out of groups and preload, so asking
+        // for it swaps it in like any other model.
+        let adapter_entries: Vec<(String, ModelEntry)> = m
+            .adapters
+            .iter()
+            .filter_map(|adapter| {
+                let path = target.model_path(models_dir, &find_adapter_file(adapter, models_dir)?);
+                let lora_args = match adapter.scale {
+                    Some(scale) => format!("--lora-scaled {} {}", path, scale),
+                    None => format!("--lora {}", path),
+                };
+                let entry = ModelEntry {
+                    cmd: format!("{} {}", cmd, lora_args),
+                    env: env.clone(),
+                    filters: filters.clone(),
+                    ..Default::default()
+                };
+                Some((adapter.served_name(&m.name), entry))
+            })
+            .collect();
+
+        config.models.insert(
+            m.name.clone(),
+            ModelEntry {
+                cmd,
+                env,
+                filters,
+                aliases,
+                ..Default::default()
+            },
+        );
+        config.models.extend(adapter_entries);
     }
 
     if !autocomplete_models.is_empty() {
-        yaml_content.push_str("\ngroups:\n  autocomplete:\n    persistent: true\n    swap: false\n    exclusive: false\n    members:\n");
-        for model_name in autocomplete_models {
-            yaml_content.push_str(&format!("      - {}\n", model_name));
+        config.groups.insert(
+            "autocomplete".to_string(),
+            Group {
+                persistent: true,
+                swap: false,
+                exclusive: false,
+                members: autocomplete_models,
+            },
+        );
+    }
+
+    // Embedding and reranker models stay loaded next to the chat models, so
+    // RAG tooling can call /v1/embeddings and /v1/rerank on the same port.
+    if !retrieval_models.is_empty() {
+        config.groups.insert(
+            "retrieval".to_string(),
+            Group {
+                persistent: true,
+                swap: false,
+                exclusive: false,
+                members: retrieval_models,
+            },
+        );
+    }
+
+    config.hooks.on_startup.preload = models.iter().map(|m| m.name.clone()).collect();
+
+    config
+}
+
+/// Render the llama-swap YAML for the configured models.
+pub async fn generate_llama_swap_yaml(
+    models: &[ModelSelection],
+    models_dir: &std::path::Path,
+    llama_server_args: Option<&crate::ui::LlamaServerArgs>,
+    downloaded_files: &std::collections::HashMap<String, std::path::PathBuf>,
+    profile: Option<&crate::profiling::HardwareProfile>,
+    target: &ServerTarget,
+) -> Result<String> {
+    build_llama_swap_config(
+        models,
+        models_dir,
+        llama_server_args,
+        downloaded_files,
+        profile,
+        target,
+    )
+    .await
+    .to_yaml()
+}
+
+/// OCI runtime used to run the localcode container. All three accept the same
+/// `run` / `rm` / `logs` CLI; they differ only in GPU and SELinux mount flags.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    #[default]
+    Docker,
+    Podman,
+    Nerdctl,
+}
+
+impl ContainerRuntime {
+    pub fn binary(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+            ContainerRuntime::Nerdctl => "nerdctl",
         }
     }
 
-    yaml_content.push_str("\nhooks:\n  on_startup:\n    preload:\n");
-    for m in models {
-        yaml_content.push_str(&format!("      - {}\n", m.name));
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "Docker",
+            ContainerRuntime::Podman => "Podman",
+            ContainerRuntime::Nerdctl => "nerdctl",
+        }
     }
 
-    let config_path = models_dir.join("llama-swap.yaml");
-    tokio::fs::write(&config_path, yaml_content).await?;
+    /// GPU passthrough flags for `devices`, or every GPU when empty. Podman
+    /// doesn't implement `--gpus`; NVIDIA GPUs are exposed through the CDI
+    /// spec generated by `nvidia-ctk cdi generate`.
+    fn gpu_args(&self, devices: &[u32]) -> Vec<String> {
+        let ids: Vec<String> = devices.iter().map(u32::to_string).collect();
+        match self {
+            ContainerRuntime::Podman if ids.is_empty() => {
+                vec!["--device".to_string(), "nvidia.com/gpu=all".to_string()]
+            }
+            ContainerRuntime::Podman => ids
+                .iter()
+                .flat_map(|id| ["--device".to_string(), format!("nvidia.com/gpu={}", id)])
+                .collect(),
+            ContainerRuntime::Docker | ContainerRuntime::Nerdctl if ids.is_empty() => {
+                vec!["--gpus".to_string(), "all".to_string()]
+            }
+            // The value is CSV, so a list of devices has to be quoted
+            ContainerRuntime::Docker | ContainerRuntime::Nerdctl => {
+                vec![
+                    "--gpus".to_string(),
+                    format!("\"device={}\"", ids.join(",")),
+                ]
+            }
+        }
+    }
+
+    /// Bind-mount suffix. Rootless Podman on SELinux hosts (Fedora, RHEL) can't
+    /// read host paths unless they are relabeled with `:Z`.
+    fn mount_suffix(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Podman => ":Z",
+            ContainerRuntime::Docker | ContainerRuntime::Nerdctl => "",
+        }
+    }
 
+    /// Suffix for read-only mounts of other apps' model caches. Podman gets the
+    /// shared `z` label: a private `Z` relabel would lock Ollama out of its own blobs.
+    fn read_only_mount_suffix(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Podman => ":ro,z",
+            ContainerRuntime::Docker | ContainerRuntime::Nerdctl => ":ro",
+        }
+    }
+
+    /// Whether a failed `run` looks like missing GPU support rather than a real error.
+    fn is_gpu_error(&self, stderr: &str) -> bool {
+        stderr.contains("could not select device driver")
+            || stderr.contains("nvidia")
+            || stderr.contains("CDI")
+    }
+
+    /// First runtime found on this machine, preferring Docker, then Podman, then nerdctl.
+    pub fn detect() -> Option<ContainerRuntime> {
+        [
+            ContainerRuntime::Docker,
+            ContainerRuntime::Podman,
+            ContainerRuntime::Nerdctl,
+        ]
+        .into_iter()
+        .find(|rt| {
+            std::process::Command::new(rt.binary())
+                .arg("--version")
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// Name of the single container LocalCode manages.
+pub const CONTAINER_NAME: &str = "localcode-llm";
+
+/// CUDA numbers GPUs fastest first by default. localcode takes GPU indices
+/// from `nvidia-smi`, which lists them by PCI bus, so CUDA has to match.
+const CUDA_DEVICE_ORDER: &str = "CUDA_DEVICE_ORDER=PCI_BUS_ID";
+
+const CUDA_IMAGE: &str = "ghcr.io/thewulf7/localcode:cuda-latest";
+const CPU_IMAGE: &str = "ghcr.io/mostlygeek/llama-swap:cpu";
+
+/// Build the `run` arguments for the localcode container.
+fn container_run_args(
+    runtime: ContainerRuntime,
+    port: u16,
+    models_dir: &std::path::Path,
+    config_path: &std::path::Path,
+    external_dirs: &[std::path::PathBuf],
+    gpu: bool,
+    gpu_devices: &[u32],
+) -> Vec<String> {
     let port_mapping = format!("{}:8080", port);
-    let volume_mapping = format!("{}:/models", models_dir.to_string_lossy());
-    let config_mount = format!("{}:/app/config.yaml", config_path.to_string_lossy());
+    let volume_mapping = format!(
+        "{}:/models{}",
+        models_dir.to_string_lossy(),
+        runtime.mount_suffix()
+    );
+    let config_mount = format!(
+        "{}:/app/config.yaml{}",
+        config_path.to_string_lossy(),
+        runtime.mount_suffix()
+    );
 
     let mut args = vec![
         "run".to_string(),
         "-d".to_string(), // run completely detached in the background
         "--name".to_string(),
-        "localcode-llm".to_string(),
-        "--gpus".to_string(),
-        "all".to_string(),
+        CONTAINER_NAME.to_string(),
+    ];
+    if gpu {
+        args.extend(runtime.gpu_args(gpu_devices));
+        args.extend(["-e".to_string(), CUDA_DEVICE_ORDER.to_string()]);
+    }
+    args.extend([
         "-e".to_string(),
         "HF_HOME=/models".to_string(),
         "-p".to_string(),
@@ -597,16 +1568,89 @@ pub async fn start_llama_swap_docker(
         volume_mapping,
         "-v".to_string(),
         config_mount,
-        "ghcr.io/thewulf7/localcode:cuda-latest".to_string(),
-    ];
+    ]);
+    for dir in external_dirs {
+        args.push("-v".to_string());
+        args.push(format!(
+            "{}:{}{}",
+            dir.to_string_lossy(),
+            external_container_path(dir),
+            runtime.read_only_mount_suffix()
+        ));
+    }
+    args.push(if gpu { CUDA_IMAGE } else { CPU_IMAGE }.to_string());
+    args
+}
+
+pub async fn start_llama_swap_docker(
+    config: &crate::ui::InitConfig,
+    models_dir: &std::path::Path,
+    downloaded_files: &std::collections::HashMap<String, std::path::PathBuf>,
+    profile: Option<&crate::profiling::HardwareProfile>,
+) -> Result<()> {
+    let runtime = config.container_runtime;
+    let (models, port) = (&config.models, config.port);
+    let llama_server_args = config.llama_server_args.as_ref();
+    let bin = runtime.binary();
+    println!("📦 Launching localcode container...");
+
+    // First, verify the container runtime is installed
+    let runtime_check = Command::new(bin)
+        .arg("--version")
+        .output()
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to execute {} command. Is {} installed?",
+                bin,
+                runtime.label()
+            )
+        })?;
+
+    if !runtime_check.status.success() {
+        return Err(anyhow::anyhow!(
+            "{} is not running or not installed correctly: {}",
+            runtime.label(),
+            String::from_utf8_lossy(&runtime_check.stderr)
+        ));
+    }
+
+    // Attempt to forcefully remove any existing container with the same name to avoid conflicts
+    let _ = Command::new(bin)
+        .args(["rm", "-f", CONTAINER_NAME])
+        .output()
+        .await;
 
-    let mut output = Command::new("docker").args(&args).output().await?;
+    let yaml_content = generate_llama_swap_yaml(
+        models,
+        models_dir,
+        llama_server_args,
+        downloaded_files,
+        profile,
+        &ServerTarget::Docker,
+    )
+    .await?;
+
+    let config_path = models_dir.join("llama-swap.yaml");
+    tokio::fs::write(&config_path, yaml_content).await?;
+
+    let external_dirs = external_model_dirs(models, models_dir, downloaded_files);
+    let args = container_run_args(
+        runtime,
+        port,
+        models_dir,
+        &config_path,
+        &external_dirs,
+        true,
+        &config.gpu_devices(),
+    );
+    let mut output = Command::new(bin).args(&args).output().await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
 
         // Auto-Detect if the failure is just because they don't have Nvidia Container Toolkit or WSL GPU passthrough set up
-        if stderr.contains("could not select device driver") || stderr.contains("nvidia") {
+        if runtime.is_gpu_error(&stderr) {
             use console::style;
             println!(
                 "{} {}",
@@ -623,31 +1667,43 @@ pub async fn start_llama_swap_docker(
                 style("To enable GPU acceleration, install the NVIDIA Container Toolkit:").dim()
             );
             println!("  {}", style("https://docs.nvidia.com/datacenter/cloud-native/container-toolkit/latest/install-guide.html").dim().underlined());
-            println!();
-
-            // Re-run without --gpus all and use cpu image
-            if let Some(pos) = args.iter().position(|x| x == "--gpus") {
-                args.remove(pos); // remove "--gpus"
-                args.remove(pos); // remove "all"
-            }
-            if let Some(pos) = args
-                .iter()
-                .position(|x| x == "ghcr.io/thewulf7/localcode:cuda-latest")
-            {
-                args[pos] = "ghcr.io/mostlygeek/llama-swap:cpu".to_string();
+            if runtime == ContainerRuntime::Podman {
+                println!(
+                    "  {}",
+                    style("Podman also needs a CDI spec: `sudo nvidia-ctk cdi generate --output=/etc/cdi/nvidia.yaml`").dim()
+                );
             }
+            println!();
 
-            output = Command::new("docker").args(&args).output().await?;
+            // The failed run may have left a created-but-not-started container behind.
+            let _ = Command::new(bin)
+                .args(["rm", "-f", CONTAINER_NAME])
+                .output()
+                .await;
+
+            // Re-run without GPU flags on the cpu image
+            let cpu_args = container_run_args(
+                runtime,
+                port,
+                models_dir,
+                &config_path,
+                &external_dirs,
+                false,
+                &[],
+            );
+            output = Command::new(bin).args(&cpu_args).output().await?;
 
             if !output.status.success() {
                 return Err(anyhow::anyhow!(
-                    "Docker failed to start container on CPU fallback. Ensure ports are not in use.\nError: {}",
+                    "{} failed to start container on CPU fallback. Ensure ports are not in use.\nError: {}",
+                    runtime.label(),
                     String::from_utf8_lossy(&output.stderr)
                 ));
             }
         } else {
             return Err(anyhow::anyhow!(
-                "Docker failed to start container. Ensure ports are not in use.\nError: {}",
+                "{} failed to start container. Ensure ports are not in use.\nError: {}",
+                runtime.label(),
                 stderr
             ));
         }
@@ -656,30 +1712,39 @@ pub async fn start_llama_swap_docker(
     Ok(())
 }
 
-pub async fn show_status() -> Result<()> {
-    use console::style;
-
-    println!(
-        "{}",
-        style("Streaming live logs from localcode-llm container... (Press Ctrl+C to stop)").cyan()
-    );
-
-    // We use `--tail 50` so we don't stream gigantic past histories immediately
-    let mut child = Command::new("docker")
-        .args(["logs", "-f", "--tail", "50", "localcode-llm"])
-        .spawn()?;
+/// Lifecycle state of the localcode container as reported by `inspect`.
+pub struct ContainerState {
+    /// `running`, `exited`, `restarting`, ...
+    pub status: String,
+    /// Raw `StartedAt` timestamp; format varies between runtimes.
+    pub started_at: String,
+}
 
-    tokio::select! {
-        _ = child.wait() => {}
-        _ = tokio::signal::ctrl_c() => {
-            let _ = child.kill().await;
-        }
+/// Inspect the localcode container. `None` when it doesn't exist (or the
+/// runtime itself isn't available).
+pub async fn inspect_container(runtime: ContainerRuntime) -> Option<ContainerState> {
+    let output = Command::new(runtime.binary())
+        .args([
+            "inspect",
+            "--format",
+            "{{.State.Status}}|{{.State.StartedAt}}",
+            CONTAINER_NAME,
+        ])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
     }
-
-    Ok(())
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (status, started_at) = stdout.trim().split_once('|')?;
+    Some(ContainerState {
+        status: status.to_string(),
+        started_at: started_at.to_string(),
+    })
 }
 
-pub async fn stop_server() -> Result<()> {
+pub async fn stop_server(runtime: ContainerRuntime) -> Result<()> {
     use console::style;
 
     println!(
@@ -688,8 +1753,8 @@ pub async fn stop_server() -> Result<()> {
             .yellow()
     );
 
-    let status = Command::new("docker")
-        .args(["rm", "-f", "localcode-llm"])
+    let status = Command::new(runtime.binary())
+        .args(["rm", "-f", CONTAINER_NAME])
         .output()
         .await?;
 
@@ -704,6 +1769,60 @@ pub async fn stop_server() -> Result<()> {
     Ok(())
 }
 
+/// Models below this size are treated as autocomplete/subagent models when the
+/// real parameter count is known. Set just above phi-3-mini (3.8B), which the
+/// name heuristic has always classified as autocomplete.
+const AUTOCOMPLETE_MAX_PARAMS_B: f64 = 4.0;
+
+/// Classify a model as autocomplete-sized, trusting the GGUF parameter count
+/// when the file has been read and falling back to the name heuristic otherwise.
+pub fn is_autocomplete(model_name: &str, metadata: Option<&crate::gguf::GgufMetadata>) -> bool {
+    match metadata.and_then(|m| m.params_b()) {
+        Some(params_b) => params_b < AUTOCOMPLETE_MAX_PARAMS_B,
+        None => is_autocomplete_model(model_name),
+    }
+}
+
+/// Index of the primary model: the first that isn't an embedding, reranker
+/// or explicit autocomplete model, falling back to the first chat-capable one.
+pub fn primary_index(models: &[ModelSelection]) -> Option<usize> {
+    models
+        .iter()
+        .position(|m| !m.is_retrieval() && m.role != Some(ModelRole::Autocomplete))
+        .or_else(|| models.iter().position(|m| !m.is_retrieval()))
+}
+
+/// The model clients should chat with and the small model for subagents and
+/// autocomplete, picked the way the llama-swap config routes them. `metadata`
+/// holds GGUF headers of models on disk (see [`local_model_metadata`]).
+pub fn client_models<'a>(
+    models: &'a [ModelSelection],
+    metadata: &std::collections::HashMap<String, crate::gguf::GgufMetadata>,
+) -> (Option<&'a ModelSelection>, Option<&'a ModelSelection>) {
+    let primary = primary_index(models);
+    let small = models
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != primary)
+        .map(|(_, m)| m)
+        .find(|m| model_role(false, m, metadata.get(&m.name)) == ModelRole::Autocomplete);
+    (primary.map(|i| &models[i]), small)
+}
+
+/// Role of a configured model: its explicit `role`, otherwise chat for the
+/// primary and autocomplete for small ones besides it.
+pub fn model_role(
+    is_primary: bool,
+    model: &ModelSelection,
+    metadata: Option<&crate::gguf::GgufMetadata>,
+) -> ModelRole {
+    match model.role {
+        Some(role) => role,
+        None if !is_primary && is_autocomplete(&model.name, metadata) => ModelRole::Autocomplete,
+        None => ModelRole::Chat,
+    }
+}
+
 // Ensure the helper grouping heuristic is standalone so we can cleanly test it
 pub fn is_autocomplete_model(model_name: &str) -> bool {
     let lower = model_name.to_lowercase();
@@ -747,7 +1866,10 @@ mod tests {
         assert_eq!(candidates[1].0, "microsoft/phi-3-mini-4k-instruct-GGUF");
         assert_eq!(candidates[2].0, "microsoft/phi-3-mini-4k-instruct-gguf");
         assert_eq!(candidates[3].0, "unsloth/phi-3-mini-4k-instruct-GGUF");
-        assert_eq!(candidates[4].0, "lmstudio-community/phi-3-mini-4k-instruct-GGUF");
+        assert_eq!(
+            candidates[4].0,
+            "lmstudio-community/phi-3-mini-4k-instruct-GGUF"
+        );
         for (_, f) in &candidates {
             assert_eq!(f, "phi-3-mini-4k-instruct-Q4_K_M.gguf");
         }
@@ -760,6 +1882,23 @@ mod tests {
             assert_eq!(f, "phi-3-mini-4k-instruct-UD-Q4_K_XL.gguf");
         }
 
+        // Explicit GGUF repo: tried first, and not repeated later
+        let explicit =
+            build_gguf_candidates("lmstudio-community/phi-3-mini-4k-instruct-GGUF", "Q4_K_M");
+        assert_eq!(
+            explicit[0].0,
+            "lmstudio-community/phi-3-mini-4k-instruct-GGUF"
+        );
+        assert_eq!(explicit[1].0, "bartowski/phi-3-mini-4k-instruct-GGUF");
+        assert_eq!(
+            explicit
+                .iter()
+                .filter(|(r, _)| r == "lmstudio-community/phi-3-mini-4k-instruct-GGUF")
+                .count(),
+            1
+        );
+        assert_eq!(explicit[0].1, "phi-3-mini-4k-instruct-Q4_K_M.gguf");
+
         // Without org prefix — bartowski, unsloth, lmstudio-community
         let candidates2 = build_gguf_candidates("some-model", "Q8_0");
         assert_eq!(candidates2.len(), 3);
@@ -772,16 +1911,331 @@ mod tests {
     async fn test_extract_hf_repo_and_file_ud_quant() {
         // UD quants should route to unsloth repo
         let quant = Some("UD-Q4_K_XL".to_string());
-        let (repo, file) = extract_hf_repo_and_file("Qwen/Qwen2.5-Coder-14B-Instruct", &quant).await;
+        let (repo, file) =
+            extract_hf_repo_and_file("Qwen/Qwen2.5-Coder-14B-Instruct", &quant).await;
         assert_eq!(repo, "unsloth/Qwen2.5-Coder-14B-Instruct-GGUF");
-        assert_eq!(file, Some("Qwen2.5-Coder-14B-Instruct-UD-Q4_K_XL.gguf".to_string()));
+        assert_eq!(
+            file,
+            Some("Qwen2.5-Coder-14B-Instruct-UD-Q4_K_XL.gguf".to_string())
+        );
 
         // Standard quant should still use bartowski
         let quant_std = Some("Q4_K_M".to_string());
-        let (repo2, _) = extract_hf_repo_and_file("Qwen/Qwen2.5-Coder-14B-Instruct", &quant_std).await;
+        let (repo2, _) =
+            extract_hf_repo_and_file("Qwen/Qwen2.5-Coder-14B-Instruct", &quant_std).await;
         assert_eq!(repo2, "bartowski/Qwen2.5-Coder-14B-Instruct-GGUF");
     }
 
+    #[tokio::test]
+    async fn test_generate_llama_swap_yaml_targets() {
+        let models_dir = std::path::PathBuf::from("/srv/models");
+        let models = vec![ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        }];
+        let mut downloaded = std::collections::HashMap::new();
+        downloaded.insert(
+            models[0].name.clone(),
+            models_dir.join("Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf"),
+        );
+        let mut extra_args = std::collections::HashMap::new();
+        extra_args.insert("slot-save-path".to_string(), serde_json::json!("/models"));
+        let args = crate::ui::LlamaServerArgs {
+            ctx_size: Some(8192),
+            n_gpu_layers: None,
+            flash_attn: None,
+            cache_type_k: None,
+            cache_type_v: None,
+            extra_args,
+        };
+
+        let docker = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            Some(&args),
+            &downloaded,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert!(docker.contains("cmd: llama-server --port ${PORT} --model /models/Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf --host 0.0.0.0"));
+        assert!(docker.contains("--slot-save-path /models"));
+
+        let native = ServerTarget::Native {
+            llama_server: std::path::PathBuf::from("/opt/llama/llama-server"),
+            gpus: Vec::new(),
+        };
+        let native_yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            Some(&args),
+            &downloaded,
+            None,
+            &native,
+        )
+        .await
+        .unwrap();
+        assert!(native_yaml.contains("cmd: /opt/llama/llama-server --port ${PORT} --model /srv/models/Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf --host 127.0.0.1"));
+        assert!(native_yaml.contains("--slot-save-path /srv/models"));
+        assert!(!native_yaml.contains("--model /models/"));
+    }
+
+    /// Compare rendered YAML against `src/testdata/<name>`. Run with
+    /// `UPDATE_GOLDEN=1` to rewrite the file after an intentional change.
+    fn assert_golden(name: &str, actual: &str) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/testdata")
+            .join(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            actual, expected,
+            "{} is out of date (rerun with UPDATE_GOLDEN=1)",
+            name
+        );
+    }
+
+    fn golden_args() -> crate::ui::LlamaServerArgs {
+        let mut extra_args = std::collections::HashMap::new();
+        extra_args.insert("slot-save-path".to_string(), serde_json::json!("/models"));
+        extra_args.insert("mlock".to_string(), serde_json::json!(true));
+        extra_args.insert("parallel".to_string(), serde_json::json!(2));
+        extra_args.insert("threads".to_string(), serde_json::json!(8));
+        crate::ui::LlamaServerArgs {
+            ctx_size: Some(32768),
+            n_gpu_layers: Some(999),
+            flash_attn: Some("on".to_string()),
+            cache_type_k: Some("q8_0".to_string()),
+            cache_type_v: Some("q8_0".to_string()),
+            extra_args,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llama_swap_yaml_golden_single() {
+        let models_dir = std::path::PathBuf::from("/srv/models");
+        let models = vec![ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        }];
+        let mut downloaded = std::collections::HashMap::new();
+        downloaded.insert(
+            models[0].name.clone(),
+            models_dir.join("Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf"),
+        );
+
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            Some(&golden_args()),
+            &downloaded,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert_golden("llama-swap-single.yaml", &yaml);
+    }
+
+    #[tokio::test]
+    async fn test_llama_swap_yaml_golden_dual() {
+        let models_dir = std::path::PathBuf::from("/srv/models");
+        let models = vec![
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+        ];
+        // Only the primary is on disk; the small model falls back to --hf-repo.
+        let mut downloaded = std::collections::HashMap::new();
+        downloaded.insert(
+            models[0].name.clone(),
+            models_dir.join("Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf"),
+        );
+        let profile = crate::profiling::HardwareProfile::test_gpu(16.0);
+
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            Some(&golden_args()),
+            &downloaded,
+            Some(&profile),
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert_golden("llama-swap-dual.yaml", &yaml);
+    }
+
+    #[test]
+    fn test_container_run_args_per_runtime() {
+        let models_dir = std::path::Path::new("/srv/models");
+        let config_path = models_dir.join("llama-swap.yaml");
+
+        let docker = container_run_args(
+            ContainerRuntime::Docker,
+            8080,
+            models_dir,
+            &config_path,
+            &[],
+            true,
+            &[],
+        );
+        assert!(docker.windows(2).any(|w| w == ["--gpus", "all"]));
+        assert!(docker.contains(&"/srv/models:/models".to_string()));
+        assert_eq!(docker.last().unwrap(), CUDA_IMAGE);
+
+        let podman = container_run_args(
+            ContainerRuntime::Podman,
+            8080,
+            models_dir,
+            &config_path,
+            &[],
+            true,
+            &[],
+        );
+        assert!(
+            podman
+                .windows(2)
+                .any(|w| w == ["--device", "nvidia.com/gpu=all"])
+        );
+        assert!(!podman.contains(&"--gpus".to_string()));
+        assert!(podman.contains(&"/srv/models:/models:Z".to_string()));
+        assert!(podman.contains(&"/srv/models/llama-swap.yaml:/app/config.yaml:Z".to_string()));
+
+        // CPU fallback drops GPU flags and swaps the image, for every runtime
+        let cpu = container_run_args(
+            ContainerRuntime::Podman,
+            9090,
+            models_dir,
+            &config_path,
+            &[],
+            false,
+            &[],
+        );
+        assert!(!cpu.contains(&"--device".to_string()));
+        assert!(!cpu.contains(&CUDA_DEVICE_ORDER.to_string()));
+        assert!(cpu.contains(&"9090:8080".to_string()));
+        assert_eq!(cpu.last().unwrap(), CPU_IMAGE);
+
+        // Selected GPUs only
+        let docker = container_run_args(
+            ContainerRuntime::Docker,
+            8080,
+            models_dir,
+            &config_path,
+            &[],
+            true,
+            &[0, 2],
+        );
+        assert!(docker.windows(2).any(|w| w == ["--gpus", "\"device=0,2\""]));
+        assert!(docker.windows(2).any(|w| w == ["-e", CUDA_DEVICE_ORDER]));
+        let podman = container_run_args(
+            ContainerRuntime::Podman,
+            8080,
+            models_dir,
+            &config_path,
+            &[],
+            true,
+            &[1],
+        );
+        assert!(
+            podman
+                .windows(2)
+                .any(|w| w == ["--device", "nvidia.com/gpu=1"])
+        );
+        assert!(!podman.contains(&"nvidia.com/gpu=all".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_pinned_model_gets_cuda_visible_devices() {
+        let models = vec![
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-32B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string(),
+                quant: Some("Q8_0".to_string()),
+                gpu: Some(1),
+                ..Default::default()
+            },
+        ];
+        let files = std::collections::HashMap::new();
+        let models_dir = std::path::Path::new("/nonexistent");
+        let docker = build_llama_swap_config(
+            &models,
+            models_dir,
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await;
+        assert!(
+            docker.models["Qwen/Qwen2.5-Coder-32B-Instruct"]
+                .env
+                .is_empty()
+        );
+        assert_eq!(
+            docker.models["Qwen/Qwen2.5-Coder-1.5B-Instruct"].env,
+            [CUDA_DEVICE_ORDER, "CUDA_VISIBLE_DEVICES=1"]
+        );
+
+        // On the host, the index counts within the selected GPUs
+        let native = ServerTarget::Native {
+            llama_server: std::path::PathBuf::from("llama-server"),
+            gpus: vec![2, 3],
+        };
+        let native =
+            build_llama_swap_config(&models, models_dir, None, &files, None, &native).await;
+        assert_eq!(
+            native.models["Qwen/Qwen2.5-Coder-1.5B-Instruct"].env,
+            [CUDA_DEVICE_ORDER, "CUDA_VISIBLE_DEVICES=3"]
+        );
+    }
+
+    #[test]
+    fn test_container_runtime_serde() {
+        let rt: ContainerRuntime = serde_json::from_str("\"podman\"").unwrap();
+        assert_eq!(rt, ContainerRuntime::Podman);
+        assert_eq!(
+            serde_json::to_string(&ContainerRuntime::Nerdctl).unwrap(),
+            "\"nerdctl\""
+        );
+    }
+
+    #[test]
+    fn test_is_autocomplete_prefers_metadata() {
+        let meta = |params: u64| crate::gguf::GgufMetadata {
+            parameter_count: params,
+            ..Default::default()
+        };
+        // Name says nothing about size, metadata says 1.5B
+        assert!(is_autocomplete("my-finetune", Some(&meta(1_540_000_000))));
+        // Name contains "mini" but the file is a 14B model
+        assert!(!is_autocomplete(
+            "gemini-distill-14b",
+            Some(&meta(14_700_000_000))
+        ));
+        // No metadata → name heuristic
+        assert!(is_autocomplete("qwen2.5-coder-1.5b-instruct", None));
+        assert!(!is_autocomplete("llama3-8b-instruct", Some(&meta(0))));
+    }
+
     #[test]
     fn test_is_autocomplete_model() {
         assert!(is_autocomplete_model("phi3-mini"));
@@ -792,4 +2246,730 @@ mod tests {
         assert!(!is_autocomplete_model("mixtral-8x7b-instruct"));
         assert!(!is_autocomplete_model("llama3-70b-instruct"));
     }
+
+    #[tokio::test]
+    async fn test_resolve_local_models_offline() {
+        let models_dir =
+            std::env::temp_dir().join(format!("localcode-offline-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&models_dir);
+
+        // Downloaded earlier under the publisher's own quant naming
+        let snapshot =
+            models_dir.join("models--unsloth--Qwen2.5-Coder-7B-Instruct-GGUF/snapshots/abc123");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        std::fs::write(snapshot.join("Qwen2.5-Coder-7B-Instruct-q4.gguf"), b"GGUF").unwrap();
+        // Picked by `init --offline` from another directory
+        let picked = models_dir.join("elsewhere/tiny.gguf");
+        std::fs::create_dir_all(picked.parent().unwrap()).unwrap();
+        std::fs::write(&picked, b"GGUF").unwrap();
+
+        let mut models = vec![
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+            ModelSelection {
+                name: "tiny".to_string(),
+                path: Some(picked.to_string_lossy().to_string()),
+                ..Default::default()
+            },
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let err = resolve_local_models(&models, &models_dir, &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("1 model(s) are not available locally"));
+        assert!(err.contains(
+            "Qwen/Qwen2.5-Coder-1.5B-Instruct (expected Qwen2.5-Coder-1.5B-Instruct-Q4_K_M.gguf)"
+        ));
+
+        models.pop();
+        let resolved = resolve_local_models(&models, &models_dir, &[]).unwrap();
+        assert_eq!(resolved["tiny"], picked);
+        assert_eq!(
+            resolved["Qwen/Qwen2.5-Coder-7B-Instruct"],
+            snapshot.join("Qwen2.5-Coder-7B-Instruct-q4.gguf")
+        );
+
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            None,
+            &resolved,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert!(!yaml.contains("--hf-"));
+        assert!(yaml.contains("--model /models/models--unsloth--Qwen2.5-Coder-7B-Instruct-GGUF/snapshots/abc123/Qwen2.5-Coder-7B-Instruct-q4.gguf"));
+
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ollama_weights_are_reused_and_mounted() {
+        let root = std::env::temp_dir().join(format!("localcode-external-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let models_dir = root.join("models");
+        let blobs = root.join("ollama/models/blobs");
+        std::fs::create_dir_all(&blobs).unwrap();
+        let blob = blobs.join("sha256-abc");
+        let header = crate::gguf::tests::GgufBuilder::new()
+            .string("general.architecture", "qwen2")
+            .string("general.name", "Qwen2.5 Coder 7B Instruct")
+            .u32("general.file_type", 15)
+            .build();
+        std::fs::write(&blob, header).unwrap();
+        let local = vec![crate::models::DiscoveredModel {
+            name: "qwen2.5-coder:7b-latest".to_string(),
+            path: blob.clone(),
+            size_bytes: 0,
+            source: "Ollama".to_string(),
+            ..Default::default()
+        }];
+
+        let mut model = ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        };
+        let (found, source) = find_existing_copy(&model, &models_dir, &local).unwrap();
+        assert_eq!(
+            (found.as_path(), source.as_str()),
+            (blob.as_path(), "Ollama")
+        );
+
+        // Header names the model but a different quant: not a match
+        model.quant = Some("Q8_0".to_string());
+        assert!(find_existing_copy(&model, &models_dir, &local).is_none());
+        model.quant = Some("Q4_K_M".to_string());
+
+        let models = vec![model];
+        let mut files = std::collections::HashMap::new();
+        files.insert(models[0].name.clone(), blob.clone());
+        let real_blobs = blobs.canonicalize().unwrap();
+        assert_eq!(
+            external_model_dirs(&models, &models_dir, &files),
+            vec![real_blobs.clone()]
+        );
+
+        let mount_point = external_container_path(&real_blobs);
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert!(yaml.contains(&format!("--model {}/sha256-abc", mount_point)));
+
+        let config_path = models_dir.join("llama-swap.yaml");
+        let podman = container_run_args(
+            ContainerRuntime::Podman,
+            8080,
+            &models_dir,
+            &config_path,
+            std::slice::from_ref(&real_blobs),
+            true,
+            &[],
+        );
+        assert!(podman.contains(&format!("{}:{}:ro,z", real_blobs.display(), mount_point)));
+        assert_eq!(podman.last().unwrap(), CUDA_IMAGE);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_resized_server_args() {
+        let models_dir =
+            std::env::temp_dir().join(format!("localcode-resize-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&models_dir);
+        std::fs::create_dir_all(&models_dir).unwrap();
+        let weights = models_dir.join("Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf");
+        let header = crate::gguf::tests::GgufBuilder::new()
+            .string("general.architecture", "qwen2")
+            .u32("qwen2.context_length", 131072)
+            .u32("qwen2.block_count", 28)
+            .u32("qwen2.attention.head_count", 28)
+            .u32("qwen2.attention.head_count_kv", 4)
+            .u32("qwen2.embedding_length", 3584)
+            .build();
+        std::fs::write(&weights, header).unwrap();
+
+        let models = vec![ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        }];
+        let mut files = std::collections::HashMap::new();
+        files.insert(models[0].name.clone(), weights);
+        let profile = crate::profiling::HardwareProfile::test_gpu(16.0);
+        // What init saves before the weights are downloaded
+        let mut config = crate::ui::InitConfig {
+            models: models.clone(),
+            llama_server_args: Some(crate::ui::LlamaServerArgs::from_hardware(
+                &profile,
+                &models,
+                &std::collections::HashMap::new(),
+            )),
+            ..Default::default()
+        };
+
+        let resized = resized_server_args(&config, &models_dir, &files, Some(&profile)).unwrap();
+        assert_ne!(
+            resized.ctx_size,
+            config.llama_server_args.as_ref().unwrap().ctx_size
+        );
+        assert!(resized_server_args(&config, &models_dir, &files, None).is_none());
+
+        config.pin_server_args = true;
+        assert!(resized_server_args(&config, &models_dir, &files, Some(&profile)).is_none());
+
+        // Values the user edited and keys they added stay as written
+        config.pin_server_args = false;
+        let mut edited = config.llama_server_args.clone().unwrap();
+        edited.ctx_size = Some(4096);
+        edited
+            .extra_args
+            .insert("temp".to_string(), serde_json::json!(0.2));
+        config.llama_server_args = Some(edited);
+        let kept = resized_server_args(&config, &models_dir, &files, Some(&profile)).unwrap();
+        assert_eq!(kept.ctx_size, Some(4096));
+        assert_eq!(kept.extra_args["temp"], serde_json::json!(0.2));
+
+        // Args that already match the headers are left alone
+        config.llama_server_args = Some(resized);
+        assert!(resized_server_args(&config, &models_dir, &files, Some(&profile)).is_none());
+
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_mmproj_in_repo() {
+        let listing = |files: &[&str]| RepoInfo {
+            sha: String::new(),
+            siblings: files
+                .iter()
+                .map(|f| crate::download::RepoFile {
+                    rfilename: f.to_string(),
+                    size: None,
+                    blob_id: None,
+                    lfs: None,
+                })
+                .collect(),
+        };
+        let info = listing(&[
+            "gemma-3-12b-it-F16.gguf",
+            "gemma-3-12b-it-Q4_K_M.gguf",
+            "mmproj-gemma-3-12b-it-bf16.gguf",
+            "mmproj-gemma-3-12b-it-f16.gguf",
+        ]);
+        let mut model = ModelSelection {
+            name: "google/gemma-3-12b-it".to_string(),
+            quant: Some("F16".to_string()),
+            ..Default::default()
+        };
+
+        // The projector is never mistaken for the weights
+        assert_eq!(
+            find_best_gguf_in_repo(&info, "F16").unwrap(),
+            "gemma-3-12b-it-F16.gguf"
+        );
+        assert_eq!(
+            find_mmproj_in_repo(&info, &model).unwrap(),
+            "mmproj-gemma-3-12b-it-f16.gguf"
+        );
+
+        model.mmproj = Some("MMPROJ-gemma-3-12b-it-bf16.gguf".to_string());
+        assert_eq!(
+            find_mmproj_in_repo(&info, &model).unwrap(),
+            "mmproj-gemma-3-12b-it-bf16.gguf"
+        );
+        model.mmproj = Some("none".to_string());
+        assert!(find_mmproj_in_repo(&info, &model).is_none());
+
+        model.mmproj = None;
+        assert!(find_mmproj_in_repo(&listing(&["gemma-3-12b-it-Q4_K_M.gguf"]), &model).is_none());
+    }
+
+    #[test]
+    fn test_find_projector_file() {
+        let models_dir =
+            std::env::temp_dir().join(format!("localcode-projector-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&models_dir);
+        let touch = |path: &std::path::Path| {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"GGUF").unwrap();
+        };
+        let vl = ModelSelection {
+            name: "Qwen/Qwen2.5-VL-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        };
+
+        // Same repo, but the projector came with another snapshot commit
+        let repo = models_dir.join("models--bartowski--Qwen2.5-VL-7B-Instruct-GGUF");
+        let weights = repo.join("snapshots/abc123/Qwen2.5-VL-7B-Instruct-Q4_K_M.gguf");
+        let projector = repo.join("snapshots/def456/mmproj-model-f16.gguf");
+        touch(&weights);
+        touch(&projector);
+        assert_eq!(
+            hf_snapshots_dir(&weights).and_then(hf_repo_id).as_deref(),
+            Some("bartowski/Qwen2.5-VL-7B-Instruct-GGUF")
+        );
+        assert_eq!(
+            find_projector_file(&vl, &weights, &models_dir),
+            Some(projector)
+        );
+
+        // A flat folder: only a projector named after the weights
+        let flat = models_dir.join("vision");
+        let weights = flat.join("Qwen2.5-VL-7B-Instruct-Q4_K_M.gguf");
+        touch(&weights);
+        touch(&flat.join("mmproj-model-f16.gguf"));
+        touch(&flat.join("mmproj-gemma-3-4b-it-f16.gguf"));
+        assert_eq!(find_projector_file(&vl, &weights, &models_dir), None);
+        let named = flat.join("mmproj-Qwen2.5-VL-7B-Instruct-f16.gguf");
+        touch(&named);
+        assert_eq!(find_projector_file(&vl, &weights, &models_dir), Some(named));
+
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mmproj_is_passed_to_llama_server() {
+        let models_dir =
+            std::env::temp_dir().join(format!("localcode-mmproj-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&models_dir);
+        let snapshot =
+            models_dir.join("models--bartowski--Qwen2.5-VL-7B-Instruct-GGUF/snapshots/abc123");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        for file in [
+            "Qwen2.5-VL-7B-Instruct-Q4_K_M.gguf",
+            "mmproj-Qwen2.5-VL-7B-Instruct-f16.gguf",
+        ] {
+            std::fs::write(snapshot.join(file), b"GGUF").unwrap();
+        }
+        // Loose files at the top of models_dir are never paired automatically
+        std::fs::write(
+            models_dir.join("Qwen2.5-Coder-1.5B-Instruct-Q4_K_M.gguf"),
+            b"GGUF",
+        )
+        .unwrap();
+        std::fs::write(models_dir.join("mmproj-other-f16.gguf"), b"GGUF").unwrap();
+
+        let mut models = vec![
+            ModelSelection {
+                name: "Qwen/Qwen2.5-VL-7B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+        ];
+        let files = std::collections::HashMap::new();
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        let snapshot_in_container =
+            "/models/models--bartowski--Qwen2.5-VL-7B-Instruct-GGUF/snapshots/abc123";
+        assert!(yaml.contains(&format!(
+            "--model {0}/Qwen2.5-VL-7B-Instruct-Q4_K_M.gguf --mmproj {0}/mmproj-Qwen2.5-VL-7B-Instruct-f16.gguf",
+            snapshot_in_container
+        )));
+        assert_eq!(yaml.matches("--mmproj").count(), 1);
+
+        // Explicit file name, found anywhere in models_dir; "none" opts out
+        models[1].mmproj = Some("mmproj-other-f16.gguf".to_string());
+        models[0].mmproj = Some("none".to_string());
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert!(yaml.contains("--mmproj /models/mmproj-other-f16.gguf"));
+        assert_eq!(yaml.matches("--mmproj").count(), 1);
+
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_draft_model_is_passed_to_llama_server() {
+        let models_dir =
+            std::env::temp_dir().join(format!("localcode-draft-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&models_dir);
+        std::fs::create_dir_all(&models_dir).unwrap();
+        for file in [
+            "Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf",
+            "Qwen2.5-Coder-0.5B-Instruct-Q8_0.gguf",
+        ] {
+            std::fs::write(models_dir.join(file), b"GGUF").unwrap();
+        }
+        let models = vec![ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            draft: Some(Box::new(ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-0.5B-Instruct".to_string(),
+                quant: Some("Q8_0".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }];
+        assert_eq!(with_drafts(&models).len(), 2);
+
+        let files = std::collections::HashMap::new();
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert!(yaml.contains(
+            "--model /models/Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf --model-draft /models/Qwen2.5-Coder-0.5B-Instruct-Q8_0.gguf --host"
+        ));
+        // The draft is not served as a model of its own
+        assert!(!yaml.contains("Qwen/Qwen2.5-Coder-0.5B-Instruct:"));
+
+        // Saved args tune the draft; without its file those flags are dropped
+        let args = crate::ui::LlamaServerArgs {
+            ctx_size: Some(8192),
+            n_gpu_layers: None,
+            flash_attn: None,
+            cache_type_k: None,
+            cache_type_v: None,
+            extra_args: [("draft-max".to_string(), serde_json::json!(16))].into(),
+        };
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            Some(&args),
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert!(yaml.contains("--draft-max 16"));
+        std::fs::remove_file(models_dir.join("Qwen2.5-Coder-0.5B-Instruct-Q8_0.gguf")).unwrap();
+        let yaml = generate_llama_swap_yaml(
+            &models,
+            &models_dir,
+            Some(&args),
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await
+        .unwrap();
+        assert!(!yaml.contains("--model-draft"));
+        assert!(!yaml.contains("--draft-max"));
+
+        std::fs::remove_dir_all(&models_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_roles_in_llama_swap_config() {
+        let models = vec![
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+            // Small enough to be taken for an autocomplete model without a role
+            ModelSelection {
+                name: "Qwen/Qwen3-Embedding-0.6B".to_string(),
+                quant: Some("Q8_0".to_string()),
+                role: Some(ModelRole::Embedding),
+                ..Default::default()
+            },
+            ModelSelection {
+                name: "BAAI/bge-reranker-v2-m3".to_string(),
+                quant: Some("Q8_0".to_string()),
+                role: Some(ModelRole::Reranker),
+                ..Default::default()
+            },
+        ];
+        let files = std::collections::HashMap::new();
+        let config = build_llama_swap_config(
+            &models,
+            std::path::Path::new("/nonexistent"),
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await;
+
+        let embed = &config.models["Qwen/Qwen3-Embedding-0.6B"];
+        assert!(embed.cmd.contains("--embedding --pooling mean"));
+        assert!(!embed.cmd.contains("--jinja"));
+        assert!(embed.aliases.is_empty());
+        assert_eq!(embed.filters, Filters::default());
+        let rerank = &config.models["BAAI/bge-reranker-v2-m3"];
+        assert!(rerank.cmd.contains("--pooling rank --reranking"));
+
+        // Haiku stays on the primary: neither model is an autocomplete model
+        let primary = &config.models["Qwen/Qwen2.5-Coder-7B-Instruct"];
+        assert!(primary.aliases.iter().any(|a| a.contains("haiku")));
+        assert!(!config.groups.contains_key("autocomplete"));
+        assert_eq!(
+            config.groups["retrieval"].members,
+            ["Qwen/Qwen3-Embedding-0.6B", "BAAI/bge-reranker-v2-m3"]
+        );
+        assert!(config.groups["retrieval"].persistent);
+        assert_eq!(config.hooks.on_startup.preload.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_primary_is_first_chat_model() {
+        let mut models = vec![
+            ModelSelection {
+                name: "Qwen/Qwen3-Embedding-0.6B".to_string(),
+                quant: Some("Q8_0".to_string()),
+                role: Some(ModelRole::Embedding),
+                ..Default::default()
+            },
+            // Small enough to be taken for an autocomplete model if it weren't primary
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string(),
+                quant: Some("Q8_0".to_string()),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(primary_index(&models), Some(1));
+        let files = std::collections::HashMap::new();
+        let models_dir = std::path::Path::new("/nonexistent");
+        let config = build_llama_swap_config(
+            &models,
+            models_dir,
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await;
+        let chat = &config.models["Qwen/Qwen2.5-Coder-1.5B-Instruct"];
+        assert!(chat.aliases.iter().any(|a| a == "claude-sonnet-4-5"));
+        assert!(chat.aliases.iter().any(|a| a.contains("haiku")));
+        assert!(!config.groups.contains_key("autocomplete"));
+        let names = |models: &[ModelSelection]| {
+            let (chat, small) = client_models(models, &std::collections::HashMap::new());
+            (chat.map(|m| m.name.clone()), small.map(|m| m.name.clone()))
+        };
+        assert_eq!(
+            names(&models),
+            (Some("Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string()), None)
+        );
+
+        // With a larger chat model after it, the small one is autocomplete again
+        models.insert(
+            1,
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+        );
+        let config = build_llama_swap_config(
+            &models,
+            models_dir,
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await;
+        let primary = &config.models["Qwen/Qwen2.5-Coder-7B-Instruct"];
+        assert!(primary.aliases.iter().any(|a| a == "claude-sonnet-4-5"));
+        assert!(!primary.aliases.iter().any(|a| a.contains("haiku")));
+        assert_eq!(
+            config.groups["autocomplete"].members,
+            ["Qwen/Qwen2.5-Coder-1.5B-Instruct"]
+        );
+        assert_eq!(
+            names(&models),
+            (
+                Some("Qwen/Qwen2.5-Coder-7B-Instruct".to_string()),
+                Some("Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lora_adapters_are_served_as_models() {
+        let root = std::env::temp_dir().join(format!("localcode-lora-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let models_dir = root.join("models");
+        let snapshot = models_dir.join("models--acme--sql-lora-GGUF/snapshots/abc");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        std::fs::write(
+            models_dir.join("Qwen2.5-Coder-7B-Instruct-Q4_K_M.gguf"),
+            b"GGUF",
+        )
+        .unwrap();
+        std::fs::write(snapshot.join("sql-lora-f16.gguf"), b"GGUF").unwrap();
+        let local = root.join("loras/house-style.gguf");
+        std::fs::create_dir_all(local.parent().unwrap()).unwrap();
+        std::fs::write(&local, b"GGUF").unwrap();
+
+        let models = vec![ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            adapters: vec![
+                LoraAdapter {
+                    source: "acme/sql-lora-GGUF".to_string(),
+                    ..Default::default()
+                },
+                LoraAdapter {
+                    source: local.to_string_lossy().to_string(),
+                    scale: Some(0.5),
+                    alias: Some("house-coder".to_string()),
+                    ..Default::default()
+                },
+                LoraAdapter {
+                    source: "acme/not-downloaded".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }];
+        let files = std::collections::HashMap::new();
+        assert_eq!(
+            find_adapter_file(&models[0].adapters[0], &models_dir),
+            Some(snapshot.join("sql-lora-f16.gguf"))
+        );
+        assert_eq!(model_files(&models[0], &models_dir, &files).len(), 3);
+        let real_loras = local.parent().unwrap().canonicalize().unwrap();
+        assert_eq!(
+            external_model_dirs(&models, &models_dir, &files),
+            vec![real_loras.clone()]
+        );
+
+        let config = build_llama_swap_config(
+            &models,
+            &models_dir,
+            None,
+            &files,
+            None,
+            &ServerTarget::Docker,
+        )
+        .await;
+        let names: Vec<&str> = config.models.keys().map(String::as_str).collect();
+        assert_eq!(
+            names,
+            [
+                "Qwen/Qwen2.5-Coder-7B-Instruct",
+                "Qwen/Qwen2.5-Coder-7B-Instruct+sql-lora-GGUF",
+                "house-coder"
+            ]
+        );
+        let base = &config.models["Qwen/Qwen2.5-Coder-7B-Instruct"];
+        assert!(!base.cmd.contains("--lora"));
+        assert!(
+            config.models["Qwen/Qwen2.5-Coder-7B-Instruct+sql-lora-GGUF"]
+                .cmd
+                .ends_with(
+                    "--lora /models/models--acme--sql-lora-GGUF/snapshots/abc/sql-lora-f16.gguf"
+                )
+        );
+        let house = &config.models["house-coder"];
+        assert!(house.cmd.starts_with(&base.cmd));
+        assert!(house.cmd.ends_with(&format!(
+            "--lora-scaled {} 0.5",
+            external_container_path(&real_loras.join("house-style.gguf"))
+        )));
+        assert_eq!(house.filters, base.filters);
+        assert!(house.aliases.is_empty());
+        assert_eq!(
+            config.hooks.on_startup.preload,
+            ["Qwen/Qwen2.5-Coder-7B-Instruct"]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_models_outside_models_dir_are_mounted() {
+        let root = std::env::temp_dir().join(format!("localcode-outside-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let models_dir = root.join("models");
+        let elsewhere = root.join("lm-studio/qwen");
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        let gguf = elsewhere.join("tiny.gguf");
+        std::fs::write(&gguf, b"GGUF").unwrap();
+
+        // Offline `start` with a model picked from another app's cache
+        let models = vec![ModelSelection {
+            name: "tiny".to_string(),
+            path: Some(gguf.to_string_lossy().to_string()),
+            ..Default::default()
+        }];
+        let mut files = std::collections::HashMap::new();
+        files.insert("tiny".to_string(), gguf.clone());
+        let real_dir = elsewhere.canonicalize().unwrap();
+        assert_eq!(
+            external_model_dirs(&models, &models_dir, &files),
+            vec![real_dir.clone()]
+        );
+
+        let mount_point = external_container_path(&real_dir);
+        assert_eq!(
+            ServerTarget::Docker.model_path(&models_dir, &gguf),
+            format!("{}/tiny.gguf", mount_point)
+        );
+        let config_path = models_dir.join("llama-swap.yaml");
+        let docker = container_run_args(
+            ContainerRuntime::Docker,
+            8080,
+            &models_dir,
+            &config_path,
+            std::slice::from_ref(&real_dir),
+            true,
+            &[],
+        );
+        assert!(docker.contains(&format!("{}:{}:ro", real_dir.display(), mount_point)));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_external_container_path() {
+        assert_eq!(
+            external_container_path(std::path::Path::new("/home/me/.ollama/models/blobs")),
+            "/external/home/me/.ollama/models/blobs"
+        );
+        assert_eq!(
+            external_container_path(std::path::Path::new("C:\\Users\\me\\lm-studio")),
+            "/external/C/Users/me/lm-studio"
+        );
+    }
 }
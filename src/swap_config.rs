@@ -0,0 +1,117 @@
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+/// Typed model of llama-swap's `config.yaml`.
+///
+/// Only the keys LocalCode writes are modelled. Maps are `IndexMap` so the
+/// rendered file keeps the order models were configured in, which keeps
+/// `localcode config render` output stable and diffable.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LlamaSwapConfig {
+    pub include_aliases_in_list: bool,
+    pub send_loading_state: bool,
+    pub models: IndexMap<String, ModelEntry>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub groups: IndexMap<String, Group>,
+    #[serde(skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+}
+
+impl LlamaSwapConfig {
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+}
+
+/// One upstream llama-server that llama-swap can start on demand.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct ModelEntry {
+    /// Full command line; llama-swap substitutes `${PORT}`.
+    pub cmd: String,
+    /// Extra `KEY=value` environment for the upstream process.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+    /// Seconds of inactivity before the model is unloaded. Unset keeps it loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
+    #[serde(skip_serializing_if = "Filters::is_empty")]
+    pub filters: Filters,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+/// Request rewriting applied by llama-swap before proxying to the upstream.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Filters {
+    /// Comma-separated request parameters to drop.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip_params: Option<String>,
+    /// Parameters forced onto every request.
+    #[serde(rename = "setParams", skip_serializing_if = "IndexMap::is_empty")]
+    pub set_params: IndexMap<String, serde_json::Value>,
+}
+
+impl Filters {
+    fn is_empty(&self) -> bool {
+        self.strip_params.is_none() && self.set_params.is_empty()
+    }
+}
+
+/// Models that are swapped (or kept resident) together.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Group {
+    pub persistent: bool,
+    pub swap: bool,
+    pub exclusive: bool,
+    pub members: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Hooks {
+    pub on_startup: StartupHooks,
+}
+
+impl Hooks {
+    fn is_empty(&self) -> bool {
+        self.on_startup.preload.is_empty()
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct StartupHooks {
+    /// Models to load as soon as llama-swap starts.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub preload: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_names_are_quoted_when_needed() {
+        let mut config = LlamaSwapConfig::default();
+        config.models.insert(
+            "llama3:8b \"q4\"".to_string(),
+            ModelEntry {
+                cmd: "llama-server --port ${PORT}".to_string(),
+                ..Default::default()
+            },
+        );
+        let yaml = config.to_yaml().unwrap();
+
+        // Round-trips through a YAML parser with the key intact
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let models = parsed["models"].as_mapping().unwrap();
+        let (key, entry) = models.iter().next().unwrap();
+        assert_eq!(key.as_str(), Some("llama3:8b \"q4\""));
+        assert_eq!(entry["cmd"].as_str(), Some("llama-server --port ${PORT}"));
+
+        // Empty sections are omitted entirely
+        assert!(!yaml.contains("groups"));
+        assert!(!yaml.contains("hooks"));
+        assert!(!yaml.contains("filters"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::gguf::GgufMetadata;
 use crate::profiling::HardwareProfile;
 use anyhow::Result;
 use inquire::Confirm;
@@ -19,10 +20,143 @@ where
     })
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct ModelSelection {
     pub name: String,
     pub quant: Option<String>,
+    /// Explicit GGUF on disk (supports ~ expansion). Set by `init --offline`
+    /// for models picked from local caches; such models are never downloaded.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub path: Option<String>,
+    /// Vision projector: a GGUF path, or a file name in the model's repo or
+    /// next to its weights. Unset picks up any `mmproj-*.gguf` automatically;
+    /// `"none"` turns vision off.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mmproj: Option<String>,
+    /// Small model from the same family for speculative decoding
+    /// (`--model-draft`). Only read on the primary model.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub draft: Option<Box<ModelSelection>>,
+    /// LoRA adapters, each served as its own `base+adapter` model.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub adapters: Vec<LoraAdapter>,
+    /// What this model serves. Unset means the first model is the chat model
+    /// and smaller ones after it autocomplete.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub role: Option<ModelRole>,
+    /// Run this model on one GPU only (`CUDA_VISIBLE_DEVICES`), counted
+    /// among the GPUs llama-server can see. Unset spreads it over all of them.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub gpu: Option<u32>,
+}
+
+impl ModelSelection {
+    /// Embedding and reranker models serve `/v1/embeddings` and `/v1/rerank`
+    /// instead of chat completions.
+    pub fn is_retrieval(&self) -> bool {
+        matches!(self.role, Some(ModelRole::Embedding | ModelRole::Reranker))
+    }
+}
+
+/// Endpoint a configured model is served for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelRole {
+    Chat,
+    Autocomplete,
+    Embedding,
+    Reranker,
+}
+
+/// A LoRA adapter applied on top of a configured model.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LoraAdapter {
+    /// Hugging Face repo (`org/name`) or a local GGUF path (supports ~ expansion).
+    pub source: String,
+    /// GGUF to use from a repo with several. Unset picks the first one.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub file: Option<String>,
+    /// Adapter strength (`--lora-scaled`). Unset applies it at full strength.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scale: Option<f32>,
+    /// Model name clients request. Defaults to `<model>+<adapter>`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub alias: Option<String>,
+}
+
+impl LoraAdapter {
+    /// Whether `source` names a file rather than a Hugging Face repo.
+    pub fn is_local(&self) -> bool {
+        let s = self.source.as_str();
+        s.starts_with(['/', '~', '.']) || s.contains('\\') || s.to_lowercase().ends_with(".gguf")
+    }
+
+    /// Model name llama-swap serves `base` with this adapter under.
+    pub fn served_name(&self, base: &str) -> String {
+        if let Some(alias) = &self.alias {
+            return alias.clone();
+        }
+        let short = self
+            .source
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(&self.source);
+        let short = short.strip_suffix(".gguf").unwrap_or(short);
+        format!("{}+{}", base, short)
+    }
+}
+
+/// Attention shape of a model, which fully determines its KV cache size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KvGeometry {
+    pub n_layers: u32,
+    pub n_kv_heads: u32,
+    pub head_dim_k: u32,
+    pub head_dim_v: u32,
+}
+
+impl KvGeometry {
+    /// Geometry from a GGUF header. Needs the block count and head count;
+    /// head width defaults to `embedding_length / head_count` and a missing
+    /// `head_count_kv` means plain multi-head attention.
+    ///
+    /// llmfit's model database doesn't carry layer or head counts, so models
+    /// that aren't on disk yet have no geometry.
+    pub fn from_metadata(meta: &GgufMetadata) -> Option<Self> {
+        let n_layers = meta.block_count.filter(|&n| n > 0)?;
+        let n_heads = meta.head_count.filter(|&n| n > 0)?;
+        let n_kv_heads = meta.head_count_kv.filter(|&n| n > 0).unwrap_or(n_heads);
+        let default_dim = meta.embedding_length.map(|e| e / n_heads);
+        Some(KvGeometry {
+            n_layers,
+            n_kv_heads,
+            head_dim_k: meta.key_length.or(default_dim).filter(|&d| d > 0)?,
+            head_dim_v: meta.value_length.or(default_dim).filter(|&d| d > 0)?,
+        })
+    }
+
+    /// Bytes of K + V cache stored per token of context, across all layers.
+    pub fn bytes_per_token(&self, cache_type_k: &str, cache_type_v: &str) -> f64 {
+        let per_layer = self.n_kv_heads as f64
+            * (self.head_dim_k as f64 * Self::bytes_per_element(cache_type_k)
+                + self.head_dim_v as f64 * Self::bytes_per_element(cache_type_v));
+        self.n_layers as f64 * per_layer
+    }
+
+    /// Storage cost of one cache element. Block quants store 32 values plus a
+    /// per-block f16 scale (and offset for the `_1` variants).
+    fn bytes_per_element(cache_type: &str) -> f64 {
+        match cache_type {
+            "f32" => 4.0,
+            "q8_0" => 34.0 / 32.0,
+            "q5_1" => 24.0 / 32.0,
+            "q5_0" => 22.0 / 32.0,
+            "q4_1" => 20.0 / 32.0,
+            "q4_0" | "iq4_nl" => 18.0 / 32.0,
+            // f16, bf16 and anything unrecognised
+            _ => 2.0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -46,6 +180,9 @@ pub struct LlamaServerArgs {
 }
 
 impl LlamaServerArgs {
+    /// Speculative decoding flags, which mean nothing without `--model-draft`.
+    pub const DRAFT_ARGS: [&str; 3] = ["draft-max", "draft-min", "gpu-layers-draft"];
+
     /// Parse parameter count in billions from a model name (e.g. "Qwen2.5-Coder-7B" → 7.0).
     fn parse_params_b(name: &str) -> Option<f64> {
         let re = regex::Regex::new(r"(?i)[-_](\d+\.?\d*)[Bb](?:[-_]|$)").ok()?;
@@ -64,15 +201,14 @@ impl LlamaServerArgs {
         // the *average* bpp significantly vs. the same base quant.
         let stripped = quant.strip_prefix("UD-").unwrap_or(quant);
 
-        if stripped.ends_with("_XL") {
+        if let Some(base) = stripped.strip_suffix("_XL") {
             // _XL: more important layers quantized at higher precision.
             // Map Q4_K_XL → Q4_K_M's bpp × 1.12  (≈ 5.25 bpw for Q4 tier)
-            let base = &stripped[..stripped.len() - 3]; // "Q4_K"
+            // base = "Q4_K"
             let base_quant = format!("{}_M", base);
             llmfit_core::models::quant_bpp(&base_quant) * 1.12
-        } else if stripped.ends_with("_XS") {
+        } else if let Some(base) = stripped.strip_suffix("_XS") {
             // _XS: more aggressive quantization for smaller files.
-            let base = &stripped[..stripped.len() - 3];
             let base_quant = format!("{}_M", base);
             llmfit_core::models::quant_bpp(&base_quant) * 0.90
         } else {
@@ -81,6 +217,30 @@ impl LlamaServerArgs {
         }
     }
 
+    /// KV cache memory per token of context, in GB.
+    ///
+    /// With the model's attention geometry this is exact: every layer stores
+    /// one K and one V vector per KV head, so GQA models with few KV heads get
+    /// a much smaller cache than MHA models of the same size. Without geometry
+    /// (file not downloaded yet) fall back to llmfit's flat
+    /// `0.000008 × params_b` GB/token f16 estimate.
+    pub fn kv_gb_per_token(
+        params_b: f64,
+        cache_type_k: &str,
+        cache_type_v: &str,
+        geometry: Option<&KvGeometry>,
+    ) -> f64 {
+        match geometry {
+            Some(g) => g.bytes_per_token(cache_type_k, cache_type_v) / 1e9,
+            None => {
+                let kv_mult = (Self::kv_cache_multiplier(cache_type_k)
+                    + Self::kv_cache_multiplier(cache_type_v))
+                    / 2.0;
+                0.000008 * params_b * kv_mult
+            }
+        }
+    }
+
     /// KV cache memory multiplier relative to the f16 baseline used by llmfit.
     fn kv_cache_multiplier(cache_type: &str) -> f64 {
         match cache_type {
@@ -92,11 +252,28 @@ impl LlamaServerArgs {
         }
     }
 
+    /// Native context length: the GGUF `*.context_length` when the file is on
+    /// disk, otherwise the per-family guess from [`Self::native_ctx_length`].
+    fn resolve_native_ctx(model_name: &str, metadata: Option<&GgufMetadata>) -> u32 {
+        metadata
+            .and_then(|m| m.context_length)
+            .unwrap_or_else(|| Self::native_ctx_length(model_name))
+    }
+
+    /// Parameter count in billions: the GGUF tensor total when the file is on
+    /// disk, otherwise parsed from the model name.
+    fn resolve_params_b(model_name: &str, metadata: Option<&GgufMetadata>) -> Option<f64> {
+        metadata
+            .and_then(|m| m.params_b())
+            .or_else(|| Self::parse_params_b(model_name))
+    }
+
     /// Infer the native (training) context length from a model name.
     ///
     /// Most open-weights models embed context length in their GGUF metadata,
-    /// but at `init` time we only have the HuggingFace name. This gives us a
-    /// conservative default per model family.
+    /// but at `init` time the weights usually aren't downloaded yet and we only
+    /// have the HuggingFace name. This gives us a conservative default per
+    /// model family.
     fn native_ctx_length(model_name: &str) -> u32 {
         let lower = model_name.to_lowercase();
         // Qwen 2.5 series: 32768 (7B/14B) or 131072 (32B/72B)
@@ -110,7 +287,10 @@ impl LlamaServerArgs {
         } else if lower.contains("qwen3") || lower.contains("qwen-3") {
             40960
         // Llama 3.1/3.2/3.3 series: 131072
-        } else if lower.contains("llama-3.1") || lower.contains("llama-3.2") || lower.contains("llama-3.3") {
+        } else if lower.contains("llama-3.1")
+            || lower.contains("llama-3.2")
+            || lower.contains("llama-3.3")
+        {
             131072
         // Llama 3 (original): 8192
         } else if lower.contains("llama-3") || lower.contains("llama3") {
@@ -123,11 +303,7 @@ impl LlamaServerArgs {
             8192
         // Phi-3/3.5: 131072 (long) or 4096 (mini default)
         } else if lower.contains("phi-3") || lower.contains("phi3") {
-            if lower.contains("mini") {
-                4096
-            } else {
-                131072
-            }
+            if lower.contains("mini") { 4096 } else { 131072 }
         // Mistral / Codestral: 32768
         } else if lower.contains("mistral") || lower.contains("codestral") {
             32768
@@ -143,11 +319,13 @@ impl LlamaServerArgs {
     /// Calculate the maximum context size that fits in the given VRAM budget,
     /// capped by the model's native training context length.
     ///
-    /// Uses the llmfit-core memory model:
-    ///   `total = params_b × bpp + 0.000008 × params_b × ctx + 0.5`
+    /// Memory model:
+    ///   `total = params_b × bpp + kv_gb_per_token × ctx + 0.5`
     ///
     /// Rearranged for VRAM-limited max_ctx:
-    ///   `max_ctx = (vram × 0.90 - params_b × bpp - 0.5) / (0.000008 × params_b × kv_mult)`
+    ///   `max_ctx = (vram × 0.90 - params_b × bpp - 0.5) / kv_gb_per_token`
+    ///
+    /// `kv_gb_per_token` comes from [`Self::kv_gb_per_token`].
     ///
     /// Context is NEVER extended beyond native training length. YaRN rope
     /// scaling is disabled because it causes gibberish on small models
@@ -156,18 +334,17 @@ impl LlamaServerArgs {
         vram_gb: f64,
         params_b: f64,
         model_quant: &str,
-        kv_cache_type: &str,
-        model_name: &str,
+        kv_gb_per_token: f64,
+        native_ctx: u32,
     ) -> u32 {
         let bpp = Self::normalize_quant_bpp(model_quant);
         let model_mem = params_b * bpp;
         let overhead = 0.5_f64; // CUDA/Metal context + compute buffers
         let usable_vram = vram_gb * 0.90; // 10% safety margin
 
-        // Hard ceiling: never exceed the model's native training context.
-        // Extending beyond this requires YaRN which causes gibberish on
+        // Hard ceiling (`native_ctx`): never exceed the model's native training
+        // context. Extending beyond this requires YaRN which causes gibberish on
         // small models during structured tool-call generation.
-        let native_ctx = Self::native_ctx_length(model_name);
 
         let free_for_kv = usable_vram - model_mem - overhead;
         if free_for_kv <= 0.0 {
@@ -175,13 +352,11 @@ impl LlamaServerArgs {
             return native_ctx.min(8192);
         }
 
-        let kv_mult = Self::kv_cache_multiplier(kv_cache_type);
-        let kv_per_token_gb = 0.000008 * params_b * kv_mult;
-        if kv_per_token_gb <= 0.0 {
+        if kv_gb_per_token <= 0.0 {
             return native_ctx;
         }
 
-        let vram_ctx = (free_for_kv / kv_per_token_gb) as u32;
+        let vram_ctx = (free_for_kv / kv_gb_per_token) as u32;
 
         // Cap at native training context — no YaRN extension.
         let effective = vram_ctx.min(native_ctx);
@@ -191,40 +366,128 @@ impl LlamaServerArgs {
         rounded.max(8192)
     }
 
-    pub fn from_hardware(profile: &HardwareProfile, models: &[ModelSelection]) -> Self {
+    /// Compute llama-server args for the primary model from the hardware profile.
+    ///
+    /// `metadata` holds GGUF headers for models already on disk (see
+    /// `runner::local_model_metadata`); missing entries fall back to guesses
+    /// from the model name.
+    pub fn from_hardware(
+        profile: &HardwareProfile,
+        models: &[ModelSelection],
+        metadata: &HashMap<String, GgufMetadata>,
+    ) -> Self {
         use llmfit_core::hardware::GpuBackend;
 
         let has_gpu = profile.vram_gb >= 1.0;
         let vram = profile.vram_gb as f64;
 
         // Extract primary model metadata
-        let model_name = models.first().map(|m| m.name.as_str()).unwrap_or("");
-        let params_b = models
-            .first()
-            .and_then(|m| Self::parse_params_b(&m.name))
-            .unwrap_or(7.0);
-        let model_quant = models
-            .first()
-            .and_then(|m| m.quant.as_deref())
-            .unwrap_or("Q4_K_M");
+        let primary_index = crate::runner::primary_index(models);
+        let primary = primary_index.map(|i| &models[i]);
+        let model_name = primary.map(|m| m.name.as_str()).unwrap_or("");
+        let primary_meta = metadata.get(model_name);
+        let params_b = Self::resolve_params_b(model_name, primary_meta).unwrap_or(7.0);
+        let geometry = primary_meta.and_then(KvGeometry::from_metadata);
+        let model_quant = primary.and_then(|m| m.quant.as_deref()).unwrap_or("Q4_K_M");
         let bpp = Self::normalize_quant_bpp(model_quant);
         let model_mem = params_b * bpp;
 
         // Calculate VRAM reserved by secondary/autocomplete models (loaded simultaneously).
         // llama-swap keeps them persistent, so we must subtract their footprint from
         // the VRAM budget available for the primary model's KV cache / context.
-        let secondary_vram: f64 = models
+        let secondary_vram: Vec<(Option<u32>, f64)> = models
             .iter()
-            .skip(1) // skip primary
-            .map(|m| {
-                let p = Self::parse_params_b(&m.name).unwrap_or(1.5);
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != primary_index)
+            .map(|(_, m)| {
+                let meta = metadata.get(&m.name);
                 let q = m.quant.as_deref().unwrap_or("Q4_K_M");
                 let b = Self::normalize_quant_bpp(q);
-                // Model weights + ~0.3 GB overhead for its KV cache / compute buffers
-                p * b + 0.3
+                if m.is_retrieval() {
+                    // One f16 KV cache at for_retrieval_model()'s context
+                    // (encoder-only models don't allocate it, so this errs
+                    // on the safe side), plus compute buffers.
+                    let p = Self::resolve_params_b(&m.name, meta).unwrap_or(0.6);
+                    let ctx = Self::retrieval_ctx(&m.name, meta) as f64;
+                    let kv = Self::kv_gb_per_token(
+                        p,
+                        "f16",
+                        "f16",
+                        meta.and_then(KvGeometry::from_metadata).as_ref(),
+                    );
+                    return (m.gpu, p * b + kv * ctx + 0.3);
+                }
+                let p = Self::resolve_params_b(&m.name, meta).unwrap_or(1.5);
+                let mem = match meta.and_then(KvGeometry::from_metadata) {
+                    // Exact KV at the context/cache type for_secondary_model() will
+                    // use, plus ~0.15 GB of compute buffers.
+                    Some(g) => {
+                        let kv_type = Self::secondary_kv_type(has_gpu);
+                        let ctx = Self::secondary_ctx(&m.name, meta) as f64;
+                        p * b + g.bytes_per_token(kv_type, kv_type) * ctx / 1e9 + 0.15
+                    }
+                    // Model weights + ~0.3 GB overhead for its KV cache / compute buffers
+                    None => p * b + 0.3,
+                };
+                (m.gpu, mem)
             })
-            .sum();
-        let effective_vram = vram - secondary_vram;
+            .collect();
+
+        // A draft model for speculative decoding shares the primary's server:
+        // its weights take VRAM up front, and llama-server gives it a KV cache
+        // as long as the primary's (f16, the draft default). Only a draft that
+        // is on disk gets loaded, so one not downloaded yet costs nothing.
+        let draft = primary
+            .and_then(|m| m.draft.as_deref())
+            .filter(|d| metadata.contains_key(&d.name));
+        let (draft_mem, draft_kv_gb_per_token) = match draft {
+            Some(d) => {
+                let meta = metadata.get(&d.name);
+                let p = Self::resolve_params_b(&d.name, meta).unwrap_or(0.5);
+                let b = Self::normalize_quant_bpp(d.quant.as_deref().unwrap_or("Q4_K_M"));
+                let geometry = meta.and_then(KvGeometry::from_metadata);
+                (
+                    p * b + 0.3,
+                    Self::kv_gb_per_token(p, "f16", "f16", geometry.as_ref()),
+                )
+            }
+            None => (0.0, 0.0),
+        };
+
+        // ── Multi-GPU ──────────────────────────────────────────────────────
+        // Memory each GPU has left for the primary. A pinned model takes its
+        // footprint from its own GPU (the draft runs in the primary's
+        // process); anything unpinned is split over all GPUs like the
+        // primary's layers, in proportion to their size.
+        let mut free: Vec<f64> = if profile.unified_memory || profile.gpu_vram_gb.len() < 2 {
+            vec![vram]
+        } else {
+            profile.gpu_vram_gb.iter().map(|&v| v as f64).collect()
+        };
+        let sizes = free.clone();
+        let pool: f64 = sizes.iter().sum();
+        let on_gpu = |gpu: Option<u32>| {
+            gpu.map(|g| g as usize)
+                .filter(|&g| sizes.len() > 1 && g < sizes.len())
+        };
+        let primary_pin = primary.and_then(|m| m.gpu);
+        let primary_gpu = on_gpu(primary_pin);
+        for (gpu, mem) in secondary_vram.into_iter().chain([(primary_pin, draft_mem)]) {
+            match on_gpu(gpu) {
+                Some(g) => free[g] -= mem,
+                None if sizes.len() == 1 => free[0] -= mem,
+                None => {
+                    for (f, size) in free.iter_mut().zip(&sizes) {
+                        *f -= mem * size / pool;
+                    }
+                }
+            }
+        }
+        let effective_vram = match primary_gpu {
+            Some(g) => free[g],
+            // Every GPU the primary spans keeps its own compute buffers
+            None => free.iter().sum::<f64>() - 0.3 * (free.len() - 1) as f64,
+        };
 
         // ── KV cache quantization ──────────────────────────────────────────
         // Context stays within native training length (no YaRN), so q4_0
@@ -242,9 +505,18 @@ impl LlamaServerArgs {
 
         // ── Context size ───────────────────────────────────────────────────
         // Cap at native training context regardless of GPU/CPU.
-        let native_ctx = Self::native_ctx_length(model_name);
+        let native_ctx = Self::resolve_native_ctx(model_name, primary_meta);
+        let kv_gb_per_token =
+            Self::kv_gb_per_token(params_b, &kv_quant, &kv_quant, geometry.as_ref())
+                + draft_kv_gb_per_token;
         let ctx_size = if has_gpu {
-            Self::calculate_max_ctx(effective_vram, params_b, model_quant, &kv_quant, model_name)
+            Self::calculate_max_ctx(
+                effective_vram,
+                params_b,
+                model_quant,
+                kv_gb_per_token,
+                native_ctx,
+            )
         } else {
             // CPU-only: use native context (RAM is plentiful but extending
             // beyond training length still causes gibberish).
@@ -260,8 +532,12 @@ impl LlamaServerArgs {
                 999 // Full GPU offload — model fits with headroom
             } else {
                 // Partial offload: estimate layers from available VRAM fraction.
-                // Most transformer models have ~(params_b × 4) layers.
-                let total_layers = (params_b * 4.0).round() as i32;
+                // The GGUF block count (+1 for the output layer) is exact; without
+                // it, assume most transformer models have ~(params_b × 4) layers.
+                let total_layers = primary_meta
+                    .and_then(|m| m.block_count)
+                    .map(|b| b as i32 + 1)
+                    .unwrap_or_else(|| (params_b * 4.0).round() as i32);
                 let frac = (effective_vram / model_mem).min(1.0);
                 ((total_layers as f64 * frac) as i32).max(0)
             }
@@ -289,7 +565,7 @@ impl LlamaServerArgs {
         let physical_cores = (profile.cpu_cores / 2).max(1);
         let threads = if has_gpu {
             // GPU inference: CPU does tokenization + HTTP; don't starve the OS
-            physical_cores.min(8).max(2)
+            physical_cores.clamp(2, 8)
         } else {
             // CPU inference: use most physical cores, leave 2 for OS
             (physical_cores - 1).max(2)
@@ -299,12 +575,11 @@ impl LlamaServerArgs {
         // ── Parallel slots ─────────────────────────────────────────────────
         // Number of concurrent request slots. Each slot reserves ctx_size
         // tokens of KV cache. More slots = more VRAM.
-        //   slot_kv_gb = 0.000008 × params_b × kv_mult × ctx_size
+        //   slot_kv_gb = kv_gb_per_token × ctx_size
         //   max_slots = min(floor(free_vram / slot_kv_gb), 4)
         // Default to 1 slot for safety, up to 4 for large VRAM systems.
         let parallel = if has_gpu && effective_vram >= model_mem + 2.0 {
-            let kv_mult = Self::kv_cache_multiplier(&kv_quant);
-            let slot_kv_gb = 0.000008 * params_b * kv_mult * ctx_size as f64;
+            let slot_kv_gb = kv_gb_per_token * ctx_size as f64;
             if slot_kv_gb > 0.0 {
                 let free_for_slots = (effective_vram * 0.85) - model_mem - 0.5;
                 let max_slots = (free_for_slots / slot_kv_gb).floor() as u32;
@@ -329,6 +604,35 @@ impl LlamaServerArgs {
             }
         }
 
+        // ── Tensor split ───────────────────────────────────────────────────
+        // Spread the primary's layers over the GPUs in proportion to what
+        // each has left, with the roomiest one as the main GPU.
+        if has_gpu && primary_gpu.is_none() && free.len() > 1 {
+            let split: Vec<String> = free.iter().map(|f| format!("{:.1}", f.max(0.0))).collect();
+            let main_gpu = (0..free.len())
+                .max_by(|&a, &b| free[a].total_cmp(&free[b]))
+                .unwrap_or(0);
+            extra_args.insert("split-mode".to_string(), serde_json::json!("layer"));
+            extra_args.insert(
+                "tensor-split".to_string(),
+                serde_json::json!(split.join(",")),
+            );
+            extra_args.insert("main-gpu".to_string(), serde_json::json!(main_gpu));
+        }
+
+        // ── Speculative decoding ───────────────────────────────────────────
+        // Draft up to 16 tokens per step; the draft goes on the GPU whenever
+        // the primary is fully offloaded, otherwise it would only slow things down.
+        if draft.is_some() {
+            extra_args.insert("draft-max".to_string(), serde_json::json!(16));
+            extra_args.insert("draft-min".to_string(), serde_json::json!(1));
+            let draft_layers = if n_gpu_layers == 999 { 999 } else { 0 };
+            extra_args.insert(
+                "gpu-layers-draft".to_string(),
+                serde_json::json!(draft_layers),
+            );
+        }
+
         extra_args.insert("slot-save-path".to_string(), serde_json::json!("/models"));
 
         LlamaServerArgs {
@@ -341,6 +645,66 @@ impl LlamaServerArgs {
         }
     }
 
+    /// Secondary model context: native training length, but at least 8192
+    /// for the subagent system prompt + tool defs.
+    fn secondary_ctx(model_name: &str, metadata: Option<&GgufMetadata>) -> u32 {
+        Self::resolve_native_ctx(model_name, metadata).max(8192)
+    }
+
+    /// Embedding/reranker context: native length up to 8192. Retrieval
+    /// chunks are short, and the whole input must fit in one micro-batch.
+    fn retrieval_ctx(model_name: &str, metadata: Option<&GgufMetadata>) -> u32 {
+        Self::resolve_native_ctx(model_name, metadata).min(8192)
+    }
+
+    /// Endpoint flags for an embedding or reranker model: `--embedding` with
+    /// the GGUF's pooling (mean when it declares none), or `--reranking` with
+    /// rank pooling. Batches span the whole context since non-causal models
+    /// must see each input in a single micro-batch.
+    pub fn for_retrieval_model(
+        primary_args: Option<&LlamaServerArgs>,
+        model: &ModelSelection,
+        profile: Option<&HardwareProfile>,
+        metadata: Option<&GgufMetadata>,
+    ) -> LlamaServerArgs {
+        let ctx_size = Self::retrieval_ctx(&model.name, metadata);
+        let mut extra_args = std::collections::HashMap::new();
+        if model.role == Some(ModelRole::Reranker) {
+            extra_args.insert("reranking".to_string(), serde_json::json!(true));
+            extra_args.insert("pooling".to_string(), serde_json::json!("rank"));
+        } else {
+            // llama.cpp LLAMA_POOLING_TYPE_* values
+            let pooling = match metadata.and_then(|m| m.pooling_type) {
+                Some(2) => "cls",
+                Some(3) => "last",
+                _ => "mean",
+            };
+            extra_args.insert("embedding".to_string(), serde_json::json!(true));
+            extra_args.insert("pooling".to_string(), serde_json::json!(pooling));
+        }
+        extra_args.insert("batch-size".to_string(), serde_json::json!(ctx_size));
+        extra_args.insert("ubatch-size".to_string(), serde_json::json!(ctx_size));
+        if let Some(threads) = primary_args.and_then(|a| a.extra_args.get("threads")) {
+            extra_args.insert("threads".to_string(), threads.clone());
+        }
+
+        // Small enough to always sit on the GPU when there is one
+        let n_gpu_layers = profile.map(|p| if p.vram_gb >= 1.0 { 999 } else { 0 });
+        LlamaServerArgs {
+            ctx_size: Some(ctx_size),
+            n_gpu_layers,
+            flash_attn: primary_args.and_then(|a| a.flash_attn.clone()),
+            cache_type_k: None,
+            cache_type_v: None,
+            extra_args,
+        }
+    }
+
+    /// KV cache: q4_0 for secondary (conserve VRAM, no YaRN needed)
+    fn secondary_kv_type(has_gpu: bool) -> &'static str {
+        if has_gpu { "q4_0" } else { "f16" }
+    }
+
     /// Build lighter CLI args for a secondary/autocomplete model.
     ///
     /// The secondary model serves subagent requests (haiku aliases) which carry
@@ -351,14 +715,11 @@ impl LlamaServerArgs {
         primary_args: &LlamaServerArgs,
         model: &ModelSelection,
         profile: &HardwareProfile,
+        metadata: Option<&GgufMetadata>,
     ) -> LlamaServerArgs {
         let model_name = &model.name;
-        let params_b = Self::parse_params_b(model_name).unwrap_or(1.5);
-        let native_ctx = Self::native_ctx_length(model_name);
-
-        // Cap context to native training length, but at least 8192
-        // for the subagent system prompt + tool defs.
-        let ctx_size = native_ctx.max(8192);
+        let params_b = Self::resolve_params_b(model_name, metadata).unwrap_or(1.5);
+        let ctx_size = Self::secondary_ctx(model_name, metadata);
 
         let has_gpu = profile.vram_gb >= 1.0;
         let model_quant = model.quant.as_deref().unwrap_or("Q4_K_M");
@@ -366,14 +727,19 @@ impl LlamaServerArgs {
         // n_gpu_layers: same strategy as primary — if it fits, go full offload
         let bpp = Self::normalize_quant_bpp(model_quant);
         let model_mem = params_b * bpp;
-        let n_gpu_layers = if has_gpu && (model_mem + 0.5) <= profile.vram_gb as f64 {
+        // A pinned model has only its own GPU
+        let gpu_vram = model
+            .gpu
+            .and_then(|g| profile.gpu_vram_gb.get(g as usize))
+            .copied()
+            .unwrap_or(profile.vram_gb);
+        let n_gpu_layers = if has_gpu && (model_mem + 0.5) <= gpu_vram as f64 {
             999
         } else {
             0
         };
 
-        // KV cache: q4_0 for secondary (conserve VRAM, no YaRN needed)
-        let kv_quant = if has_gpu { "q4_0" } else { "f16" };
+        let kv_quant = Self::secondary_kv_type(has_gpu);
 
         let mut extra_args = std::collections::HashMap::new();
 
@@ -400,6 +766,31 @@ impl LlamaServerArgs {
         }
     }
 
+    /// These args with every value still at its `old` default moved to the
+    /// `new` one. Values the user changed, and keys they added, are kept.
+    pub fn rebased(&self, old: &Self, new: &Self) -> Self {
+        fn pick<T: PartialEq + Clone>(mine: &T, old: &T, new: &T) -> T {
+            if mine == old { new } else { mine }.clone()
+        }
+        let mut extra_args = self.extra_args.clone();
+        for key in old.extra_args.keys().chain(new.extra_args.keys()) {
+            if self.extra_args.get(key) == old.extra_args.get(key) {
+                match new.extra_args.get(key) {
+                    Some(value) => extra_args.insert(key.clone(), value.clone()),
+                    None => extra_args.remove(key),
+                };
+            }
+        }
+        LlamaServerArgs {
+            ctx_size: pick(&self.ctx_size, &old.ctx_size, &new.ctx_size),
+            n_gpu_layers: pick(&self.n_gpu_layers, &old.n_gpu_layers, &new.n_gpu_layers),
+            flash_attn: pick(&self.flash_attn, &old.flash_attn, &new.flash_attn),
+            cache_type_k: pick(&self.cache_type_k, &old.cache_type_k, &new.cache_type_k),
+            cache_type_v: pick(&self.cache_type_v, &old.cache_type_v, &new.cache_type_v),
+            extra_args,
+        }
+    }
+
     pub fn to_cli_args(&self) -> String {
         let mut args = String::new();
 
@@ -421,7 +812,10 @@ impl LlamaServerArgs {
             args.push_str(&format!(" --cache-type-v {}", v));
         }
 
-        for (key, value) in &self.extra_args {
+        // Sorted so the rendered llama-swap config is stable between runs.
+        let mut extra: Vec<_> = self.extra_args.iter().collect();
+        extra.sort_by_key(|(key, _)| key.as_str());
+        for (key, value) in extra {
             if let Some(b) = value.as_bool() {
                 if b {
                     args.push_str(&format!(" --{}", key));
@@ -442,10 +836,53 @@ impl LlamaServerArgs {
 pub struct InitConfig {
     pub models: Vec<ModelSelection>,
     pub run_in_docker: bool,
+    /// Which OCI runtime runs the container when `run_in_docker` is set.
+    #[serde(default)]
+    pub container_runtime: crate::runner::ContainerRuntime,
     pub models_dir: String,
     pub port: u16,
     #[serde(default)]
     pub llama_server_args: Option<LlamaServerArgs>,
+    /// Use `llama_server_args` exactly as written. Otherwise `start` re-sizes
+    /// values still at `init`'s estimate from the GGUF headers, for that run.
+    #[serde(default)]
+    pub pin_server_args: bool,
+    /// Native mode only: explicit `llama-swap` binary. Looked up on PATH when unset.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub llama_swap_path: Option<String>,
+    /// Native mode only: explicit `llama-server` binary. Looked up on PATH when unset.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub llama_server_path: Option<String>,
+    /// Keep server logs in `<models_dir>/logs/`, rotated on every start.
+    #[serde(default)]
+    pub persist_logs: bool,
+    /// Never contact Hugging Face: `start` uses only local GGUFs and fails if any are missing.
+    #[serde(default)]
+    pub offline: bool,
+    /// More directories of GGUFs to scan (besides `models_dir` and the known app caches).
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub extra_model_dirs: Vec<String>,
+    /// GPUs the container may use (`--gpus "device=0,1"`), by host index.
+    /// Empty gives it every GPU.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub gpus: Vec<u32>,
+    /// Replaces detected hardware, field by field.
+    #[serde(
+        skip_serializing_if = "crate::profiling::HardwareOverride::is_empty",
+        default
+    )]
+    pub hardware: crate::profiling::HardwareOverride,
+}
+
+impl InitConfig {
+    /// `gpus` in host order, which is how a container (or CUDA, through
+    /// `CUDA_VISIBLE_DEVICES`) numbers them.
+    pub fn gpu_devices(&self) -> Vec<u32> {
+        let mut devices = self.gpus.clone();
+        devices.sort_unstable();
+        devices.dedup();
+        devices
+    }
 }
 
 impl Default for InitConfig {
@@ -453,9 +890,18 @@ impl Default for InitConfig {
         Self {
             models: Vec::new(),
             run_in_docker: true,
+            container_runtime: crate::runner::ContainerRuntime::default(),
             models_dir: "~/.opencode/models".to_string(),
             port: 8080,
             llama_server_args: None,
+            pin_server_args: false,
+            llama_swap_path: None,
+            llama_server_path: None,
+            persist_logs: false,
+            offline: false,
+            extra_model_dirs: Vec::new(),
+            gpus: Vec::new(),
+            hardware: Default::default(),
         }
     }
 }
@@ -470,6 +916,63 @@ const AVAILABLE_MODELS: &[&str] = &[
     "mistral-7b-instruct",
 ];
 
+/// GGUF metadata for whichever selected models are already in `models_dir`
+/// (e.g. when re-running `init`), so sizing uses real values for them.
+fn local_metadata_for(
+    models: &[ModelSelection],
+    models_dir: &str,
+) -> HashMap<String, GgufMetadata> {
+    let dir = std::path::PathBuf::from(shellexpand::tilde(models_dir).to_string());
+    crate::runner::local_model_metadata(models, &dir, &HashMap::new())
+}
+
+/// Family key for draft matching: the repo name without org, parameter
+/// count or quant-format suffix, so `Qwen/Qwen2.5-Coder-32B-Instruct` and
+/// `Qwen/Qwen2.5-Coder-0.5B-Instruct` compare equal. Models of one family
+/// share a tokenizer, which speculative decoding requires.
+fn model_family(name: &str) -> String {
+    let base = name.rsplit('/').next().unwrap_or(name).to_lowercase();
+    let base = ["-gguf", "-awq", "-gptq"]
+        .iter()
+        .find_map(|suffix| base.strip_suffix(suffix))
+        .unwrap_or(&base);
+    let size = regex::Regex::new(r"[-_]\d+(?:\.\d+)?b([-_]|$)").expect("valid regex");
+    size.replace_all(base, "$1").to_string()
+}
+
+/// Draft model for `primary` from llmfit's recommendations: same family, at
+/// most a quarter of its size and within `budget_gb`. The smallest wins,
+/// since drafting speed is the whole point.
+fn pick_draft<'a>(
+    profile: &'a HardwareProfile,
+    primary: &crate::profiling::RecommendedModel,
+    budget_gb: f32,
+) -> Option<&'a crate::profiling::RecommendedModel> {
+    let family = model_family(&primary.name);
+    profile
+        .recommended_models
+        .iter()
+        .filter(|m| {
+            m.name != primary.name
+                && model_family(&m.name) == family
+                && m.params_b > 0.0
+                && m.params_b * 4.0 <= primary.params_b
+                && m.memory_gb <= budget_gb
+        })
+        .min_by(|a, b| {
+            a.params_b
+                .partial_cmp(&b.params_b)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+/// Container runtime from `--runtime`, otherwise whichever one is installed.
+fn resolve_container_runtime(args: &crate::InitArgs) -> crate::runner::ContainerRuntime {
+    args.runtime
+        .or_else(crate::runner::ContainerRuntime::detect)
+        .unwrap_or_default()
+}
+
 pub fn prompt_user(
     args: &crate::InitArgs,
     profile: &HardwareProfile,
@@ -491,6 +994,10 @@ pub fn prompt_user(
         scope_choice.starts_with("Locally")
     };
 
+    if args.offline {
+        return Ok((prompt_offline(args, profile)?, is_project_scoped));
+    }
+
     if args.yes {
         let models = if let Some(ref m_list) = args.models {
             m_list
@@ -498,6 +1005,7 @@ pub fn prompt_user(
                 .map(|name| ModelSelection {
                     name: name.clone(),
                     quant: None,
+                    ..Default::default()
                 })
                 .collect()
         } else {
@@ -510,25 +1018,45 @@ pub fn prompt_user(
                         && m.category.contains("Code")
                         && m.name.to_lowercase().contains("instruct")
                 })
-                .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal));
+                .max_by(|a, b| {
+                    a.score
+                        .partial_cmp(&b.score)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
 
             if let Some(p) = primary {
                 let mut models = vec![ModelSelection {
                     name: p.name.clone(),
                     quant: Some(p.best_quant.clone()),
+                    ..Default::default()
                 }];
 
-                let remaining = profile.available_memory_gb - p.memory_gb;
+                let mut remaining = profile.available_memory_gb - p.memory_gb;
                 if let Some(sec) = profile
                     .recommended_models
                     .iter()
                     .filter(|m| m.is_autocomplete && m.memory_gb <= remaining && m.name != p.name)
-                    .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal))
+                    .max_by(|a, b| {
+                        a.score
+                            .partial_cmp(&b.score)
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
                 {
                     models.push(ModelSelection {
                         name: sec.name.clone(),
                         quant: Some(sec.best_quant.clone()),
+                        ..Default::default()
                     });
+                    remaining -= sec.memory_gb;
+                }
+                if args.draft
+                    && let Some(d) = pick_draft(profile, p, remaining)
+                {
+                    models[0].draft = Some(Box::new(ModelSelection {
+                        name: d.name.clone(),
+                        quant: Some(d.best_quant.clone()),
+                        ..Default::default()
+                    }));
                 }
                 models
             } else {
@@ -538,22 +1066,30 @@ pub fn prompt_user(
                         .recommended_models
                         .first()
                         .map(|m| m.best_quant.clone()),
+                    ..Default::default()
                 }]
             }
         };
 
-        let llama_args = LlamaServerArgs::from_hardware(profile, &models);
+        let models_dir = args
+            .models_dir
+            .as_ref()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|| "~/.opencode/models".to_string());
+        let metadata = local_metadata_for(&models, &models_dir);
+        let llama_args = LlamaServerArgs::from_hardware(profile, &models, &metadata);
         return Ok((
             InitConfig {
                 models,
                 run_in_docker: !args.no_docker,
-                models_dir: args
-                    .models_dir
-                    .as_ref()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "~/.opencode/models".to_string()),
+                container_runtime: resolve_container_runtime(args),
+                models_dir,
                 port: args.port,
                 llama_server_args: Some(llama_args),
+                persist_logs: args.persist_logs,
+                offline: args.offline,
+                gpus: args.gpus.clone(),
+                ..Default::default()
             },
             is_project_scoped,
         ));
@@ -603,14 +1139,13 @@ pub fn prompt_user(
         .position(|x| x.contains(default_choice))
         .unwrap_or(0);
 
-    let primary_choice = inquire::Select::new(
-        "Select primary model:",
-        primary_options,
-    )
-    .with_starting_cursor(default_idx)
-    .with_help_message("This is the main reasoning/coding model. Arrow keys to navigate, Enter to select.")
-    .with_page_size(10)
-    .prompt()?;
+    let primary_choice = inquire::Select::new("Select primary model:", primary_options)
+        .with_starting_cursor(default_idx)
+        .with_help_message(
+            "This is the main reasoning/coding model. Arrow keys to navigate, Enter to select.",
+        )
+        .with_page_size(10)
+        .prompt()?;
 
     // Parse the selection back to a RecommendedModel
     let primary_name = primary_choice
@@ -628,19 +1163,19 @@ pub fn prompt_user(
         selected_models.push(ModelSelection {
             name: primary.name.clone(),
             quant: Some(primary.best_quant.clone()),
+            ..Default::default()
         });
 
         // ── Step 2: Select the secondary model (dynamic based on remaining VRAM) ──
         let remaining_vram = profile.available_memory_gb - primary.memory_gb;
+        let mut draft_budget = remaining_vram;
 
         // Filter autocomplete models that fit in remaining VRAM
         let secondary_candidates: Vec<&crate::profiling::RecommendedModel> = profile
             .recommended_models
             .iter()
             .filter(|m| {
-                m.is_autocomplete
-                    && m.memory_gb <= remaining_vram
-                    && m.name != primary.name
+                m.is_autocomplete && m.memory_gb <= remaining_vram && m.name != primary.name
             })
             .collect();
 
@@ -661,7 +1196,9 @@ pub fn prompt_user(
                 secondary_options,
             )
             .with_starting_cursor(1.min(secondary_candidates.len())) // default to first real model
-            .with_help_message("Smaller model for fast sub-agent/autocomplete tasks. 'Skip' for single model.")
+            .with_help_message(
+                "Smaller model for fast sub-agent/autocomplete tasks. 'Skip' for single model.",
+            )
             .with_page_size(10)
             .prompt()?;
 
@@ -679,7 +1216,9 @@ pub fn prompt_user(
                     selected_models.push(ModelSelection {
                         name: sec.name.clone(),
                         quant: Some(sec.best_quant.clone()),
+                        ..Default::default()
                     });
+                    draft_budget -= sec.memory_gb;
                 }
             }
         } else if remaining_vram > 0.5 {
@@ -689,18 +1228,48 @@ pub fn prompt_user(
                 remaining_vram
             );
         }
+
+        // ── Step 3: Offer a draft model for speculative decoding ──────────
+        if let Some(d) = pick_draft(profile, primary, draft_budget) {
+            let use_draft = Confirm::new(&format!(
+                "Speed up {} with {} as a draft model (speculative decoding)?",
+                primary.name, d.name
+            ))
+            .with_default(true)
+            .with_help_message(&format!(
+                "The small model proposes tokens the primary verifies in one pass; same output, faster generation. Uses {:.1} GB more.",
+                d.memory_gb
+            ))
+            .prompt()?;
+            if use_draft {
+                selected_models[0].draft = Some(Box::new(ModelSelection {
+                    name: d.name.clone(),
+                    quant: Some(d.best_quant.clone()),
+                    ..Default::default()
+                }));
+            }
+        }
     } else {
         // Fallback for non-dynamic (static model list)
         selected_models.push(ModelSelection {
             name: primary_name.to_string(),
             quant: None,
+            ..Default::default()
         });
     }
 
-    let run_in_docker = Confirm::new("Do you want to run this using llama.cpp via Docker?")
+    let run_in_docker = Confirm::new("Do you want to run this using llama.cpp in a container (Docker/Podman)?")
         .with_default(!args.no_docker)
         .with_help_message("This will automatically download and start the model without installing extra dependencies natively.")
         .prompt()?;
+    let container_runtime = resolve_container_runtime(args);
+    if run_in_docker {
+        println!(
+            "  {} Using {} as the container runtime.",
+            crate::style("ℹ").blue(),
+            crate::style(container_runtime.label()).yellow()
+        );
+    }
 
     let default_models_dir = args
         .models_dir
@@ -712,35 +1281,190 @@ pub fn prompt_user(
         .with_default(&default_models_dir)
         .prompt()?;
 
+    let metadata = local_metadata_for(&selected_models, &models_dir_str);
+    let llama_args = LlamaServerArgs::from_hardware(profile, &selected_models, &metadata);
+
     Ok((
         InitConfig {
-            models: selected_models.clone(),
+            models: selected_models,
             run_in_docker,
+            container_runtime,
             models_dir: models_dir_str,
             port: args.port,
-            llama_server_args: Some(LlamaServerArgs::from_hardware(profile, &selected_models)),
+            llama_server_args: Some(llama_args),
+            persist_logs: args.persist_logs,
+            offline: args.offline,
+            gpus: args.gpus.clone(),
+            ..Default::default()
         },
         is_project_scoped,
     ))
 }
 
+/// Model id for a GGUF found on disk: its file name without `.gguf`.
+fn local_model_name(model: &crate::models::DiscoveredModel) -> String {
+    model
+        .name
+        .strip_suffix(".gguf")
+        .unwrap_or(&model.name)
+        .to_string()
+}
+
+fn local_selection(model: &crate::models::DiscoveredModel) -> ModelSelection {
+    ModelSelection {
+        name: local_model_name(model),
+        path: Some(model.path.to_string_lossy().to_string()),
+        ..Default::default()
+    }
+}
+
+/// Pick models for `models` from what's already on disk: `--models` names
+/// (file name, with or without `.gguf`), otherwise the largest model that fits
+/// in memory, falling back to the smallest one.
+fn pick_local_models(
+    local: &[crate::models::DiscoveredModel],
+    requested: Option<&Vec<String>>,
+    available_memory_gb: f32,
+) -> Result<Vec<ModelSelection>> {
+    if let Some(names) = requested {
+        return names
+            .iter()
+            .map(|name| {
+                local
+                    .iter()
+                    .find(|m| {
+                        m.name.eq_ignore_ascii_case(name)
+                            || local_model_name(m).eq_ignore_ascii_case(name)
+                    })
+                    .map(local_selection)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "{} is not among the local models (see `localcode ls`)",
+                            name
+                        )
+                    })
+            })
+            .collect();
+    }
+
+    let fits = local
+        .iter()
+        .filter(|m| m.size_bytes as f64 / 1e9 <= available_memory_gb as f64)
+        .max_by_key(|m| m.size_bytes);
+    let model = fits
+        .or_else(|| local.iter().min_by_key(|m| m.size_bytes))
+        .ok_or_else(|| anyhow::anyhow!("No local models to choose from"))?;
+    Ok(vec![local_selection(model)])
+}
+
+/// `init --offline`: choose among GGUFs already on this machine rather than
+/// llmfit recommendations, which would need downloading.
+fn prompt_offline(args: &crate::InitArgs, profile: &HardwareProfile) -> Result<InitConfig> {
+    let default_models_dir = args
+        .models_dir
+        .as_ref()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|| "~/.opencode/models".to_string());
+    let models_dir = if args.yes {
+        default_models_dir
+    } else {
+        inquire::Text::new("Where are your models stored?")
+            .with_default(&default_models_dir)
+            .prompt()?
+    };
+
+    let dir = std::path::PathBuf::from(shellexpand::tilde(&models_dir).to_string());
+    // Projectors, adapters and llamafiles can't be served on their own
+    let local: Vec<_> = crate::models::find_all_local_models(&dir, &[])
+        .into_iter()
+        .filter(|m| m.kind == crate::models::FileKind::Model)
+        .collect();
+    if local.is_empty() {
+        anyhow::bail!(
+            "No GGUF models found in {} or the known app caches. Offline mode can only use models already on disk.",
+            dir.display()
+        );
+    }
+
+    let models = if args.yes || args.models.is_some() {
+        pick_local_models(&local, args.models.as_ref(), profile.available_memory_gb)?
+    } else {
+        let options: Vec<String> = local
+            .iter()
+            .map(|m| {
+                format!(
+                    "{} ({}, {})",
+                    local_model_name(m),
+                    human_bytes::human_bytes(m.size_bytes as f64),
+                    m.source
+                )
+            })
+            .collect();
+        let primary = inquire::Select::new("Select primary model (local):", options.clone())
+            .with_help_message("Only models already on disk are listed in offline mode.")
+            .with_page_size(10)
+            .raw_prompt()?;
+        let mut models = vec![local_selection(&local[primary.index])];
+
+        let others: Vec<usize> = (0..local.len()).filter(|&i| i != primary.index).collect();
+        if !others.is_empty() {
+            let mut secondary_options = vec!["Skip (single model only)".to_string()];
+            secondary_options.extend(others.iter().map(|&i| options[i].clone()));
+            let secondary = inquire::Select::new(
+                "Select secondary/autocomplete model (local):",
+                secondary_options,
+            )
+            .with_help_message(
+                "Smaller model for fast sub-agent/autocomplete tasks. 'Skip' for single model.",
+            )
+            .with_page_size(10)
+            .raw_prompt()?;
+            if secondary.index > 0 {
+                models.push(local_selection(&local[others[secondary.index - 1]]));
+            }
+        }
+        models
+    };
+
+    let run_in_docker = if args.yes {
+        !args.no_docker
+    } else {
+        Confirm::new("Do you want to run this using llama.cpp in a container (Docker/Podman)?")
+            .with_default(!args.no_docker)
+            .with_help_message(
+                "The container image must already be present; it can't be pulled offline.",
+            )
+            .prompt()?
+    };
+
+    let metadata = local_metadata_for(&models, &models_dir);
+    let llama_args = LlamaServerArgs::from_hardware(profile, &models, &metadata);
+    Ok(InitConfig {
+        models,
+        run_in_docker,
+        container_runtime: resolve_container_runtime(args),
+        models_dir,
+        port: args.port,
+        llama_server_args: Some(llama_args),
+        persist_logs: args.persist_logs,
+        offline: true,
+        gpus: args.gpus.clone(),
+        ..Default::default()
+    })
+}
+
 pub fn display_config_instructions(config: &InitConfig) {
     // OpenCode uses @ai-sdk/openai-compatible which expects the full /v1 base URL.
     let openai_base_url = format!("http://localhost:{}/v1", config.port);
     // Claude Code appends its own /v1/messages path, so we must NOT include /v1 here.
     let anthropic_base_url = format!("http://localhost:{}", config.port);
-    let standard_model = config
-        .models
-        .iter()
-        .find(|m| !crate::runner::is_autocomplete_model(&m.name))
-        .or_else(|| config.models.first())
-        .map(|m| m.name.clone())
-        .unwrap_or_else(|| "default".to_string());
-    let autocomplete_model = config
-        .models
-        .iter()
-        .find(|m| crate::runner::is_autocomplete_model(&m.name))
-        .map(|m| m.name.clone());
+    // Same choice as the OpenCode config written by init and the llama-swap routing
+    let models_dir = std::path::PathBuf::from(shellexpand::tilde(&config.models_dir).to_string());
+    let metadata =
+        crate::runner::local_model_metadata(&config.models, &models_dir, &HashMap::new());
+    let (standard, autocomplete) = crate::runner::client_models(&config.models, &metadata);
+    let standard_model = standard.map_or_else(|| "default".to_string(), |m| m.name.clone());
+    let autocomplete_model = autocomplete.map(|m| m.name.clone());
 
     println!(
         "\n{}",
@@ -775,10 +1499,16 @@ pub fn display_config_instructions(config: &InitConfig) {
     println!("      }},");
     println!("      \"name\": \"LocalCode\",");
     println!("      \"npm\": \"@ai-sdk/openai-compatible\",");
-    println!("      \"options\": {{");
-    println!("        \"provider\": \"openai\",");
-    println!("        \"baseURL\": \"{}\"", openai_base_url);
-    println!("      }}");
+    let options = crate::config::provider_options(
+        &openai_base_url,
+        config
+            .models
+            .iter()
+            .find(|m| m.role == Some(ModelRole::Embedding))
+            .map(|m| m.name.as_str()),
+    );
+    let options = serde_json::to_string_pretty(&options).unwrap_or_default();
+    println!("      \"options\": {}", options.replace('\n', "\n      "));
     println!("    }}");
     println!("  }}");
     println!("}}");
@@ -843,6 +1573,7 @@ mod tests {
         let selection = ModelSelection {
             name: "test-model".to_string(),
             quant: Some("Q4".to_string()),
+            ..Default::default()
         };
         let serialized = serde_json::to_string(&selection).unwrap();
         assert!(serialized.contains("test-model"));
@@ -863,11 +1594,13 @@ mod tests {
             models: vec![ModelSelection {
                 name: "test".to_string(),
                 quant: None,
+                ..Default::default()
             }],
             run_in_docker: true,
             models_dir: "/tmp/models".to_string(),
             port: 8080,
             llama_server_args: None,
+            ..Default::default()
         };
         let serialized = serde_json::to_string(&config).unwrap();
         assert!(serialized.contains("run_in_docker"));
@@ -876,22 +1609,13 @@ mod tests {
 
     #[test]
     fn test_llama_server_args_from_hardware_gpu() {
-        let profile = HardwareProfile {
-            vram_gb: 16.0,
-            ram_gb: 32.0,
-            cpu_cores: 16,
-            gpu_name: Some("NVIDIA GeForce RTX 5060 Ti".to_string()),
-            gpu_backend: llmfit_core::hardware::GpuBackend::Cuda,
-            gpu_count: 1,
-            unified_memory: false,
-            recommended_models: vec![],
-            available_memory_gb: 16.0,
-        };
+        let profile = HardwareProfile::test_gpu(16.0);
         let models = vec![ModelSelection {
             name: "Qwen2.5-Coder-7B-Instruct".to_string(),
             quant: Some("Q8_0".to_string()),
+            ..Default::default()
         }];
-        let args = LlamaServerArgs::from_hardware(&profile, &models);
+        let args = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
         // Qwen 7B: native 32768, capped at native (no YaRN extension).
         // KV q8_0 selected (VRAM headroom > 2 GB). VRAM fits 32768 easily.
         assert_eq!(args.ctx_size, Some(32768));
@@ -903,64 +1627,236 @@ mod tests {
             &serde_json::json!("/models")
         );
         // Threads: 16 logical cores → 8 physical, GPU mode caps at 8
-        assert_eq!(args.extra_args.get("threads").unwrap(), &serde_json::json!(8));
+        assert_eq!(
+            args.extra_args.get("threads").unwrap(),
+            &serde_json::json!(8)
+        );
         // Should have multiple parallel slots with the VRAM headroom
-        assert!(args.extra_args.get("parallel").is_some());
+        assert!(args.extra_args.contains_key("parallel"));
     }
 
     #[test]
     fn test_calculate_max_ctx_known_values() {
         // Qwen 7B Q8_0 on 16GB with q8_0 KV cache
         // Capped at native 32768 (no YaRN extension)
-        let ctx = LlamaServerArgs::calculate_max_ctx(16.0, 7.0, "Q8_0", "q8_0", "Qwen2.5-Coder-7B-Instruct");
+        let ctx = LlamaServerArgs::calculate_max_ctx(
+            16.0,
+            7.0,
+            "Q8_0",
+            LlamaServerArgs::kv_gb_per_token(7.0, "q8_0", "q8_0", None),
+            LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-7B-Instruct"),
+        );
         assert_eq!(ctx, 32768, "7B on 16GB: capped at native 32768");
 
         // 14B Q4_K_M on 12GB with q4_0 KV cache — capped at native 32768
-        let ctx = LlamaServerArgs::calculate_max_ctx(12.0, 14.0, "Q4_K_M", "q4_0", "Qwen2.5-Coder-14B-Instruct");
+        let ctx = LlamaServerArgs::calculate_max_ctx(
+            12.0,
+            14.0,
+            "Q4_K_M",
+            LlamaServerArgs::kv_gb_per_token(14.0, "q4_0", "q4_0", None),
+            LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-14B-Instruct"),
+        );
         assert!(ctx >= 8192, "Expected ≥8192, got {ctx}");
 
         // 7B Q4_K_M on 8GB with q4_0 KV
-        let ctx = LlamaServerArgs::calculate_max_ctx(8.0, 7.0, "Q4_K_M", "q4_0", "Qwen2.5-Coder-7B-Instruct");
+        let ctx = LlamaServerArgs::calculate_max_ctx(
+            8.0,
+            7.0,
+            "Q4_K_M",
+            LlamaServerArgs::kv_gb_per_token(7.0, "q4_0", "q4_0", None),
+            LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-7B-Instruct"),
+        );
         assert!(ctx >= 16384, "Expected ≥16384, got {ctx}");
 
         // Tiny VRAM — model doesn't fit, returns min(native, 8192)
-        let ctx = LlamaServerArgs::calculate_max_ctx(2.0, 7.0, "Q8_0", "q8_0", "Qwen2.5-Coder-7B-Instruct");
-        assert!(ctx >= 8192 && ctx <= 32768, "Tiny VRAM ctx should be reasonable, got {ctx}");
+        let ctx = LlamaServerArgs::calculate_max_ctx(
+            2.0,
+            7.0,
+            "Q8_0",
+            LlamaServerArgs::kv_gb_per_token(7.0, "q8_0", "q8_0", None),
+            LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-7B-Instruct"),
+        );
+        assert!(
+            (8192..=32768).contains(&ctx),
+            "Tiny VRAM ctx should be reasonable, got {ctx}"
+        );
 
         // DeepSeek 7B has 131072 native context — VRAM limits it below that
-        let ctx = LlamaServerArgs::calculate_max_ctx(16.0, 7.0, "Q8_0", "q8_0", "DeepSeek-Coder-V2-Lite-7B");
-        assert!(ctx >= 49152, "DeepSeek should allow large context, got {ctx}");
+        let ctx = LlamaServerArgs::calculate_max_ctx(
+            16.0,
+            7.0,
+            "Q8_0",
+            LlamaServerArgs::kv_gb_per_token(7.0, "q8_0", "q8_0", None),
+            LlamaServerArgs::native_ctx_length("DeepSeek-Coder-V2-Lite-7B"),
+        );
+        assert!(
+            ctx >= 49152,
+            "DeepSeek should allow large context, got {ctx}"
+        );
 
         // UD-Q4_K_XL quant — bpp is slightly higher than Q4_K_M so less ctx fits
-        let ctx_ud = LlamaServerArgs::calculate_max_ctx(16.0, 14.0, "UD-Q4_K_XL", "q4_0", "Qwen2.5-Coder-14B-Instruct");
-        let ctx_std = LlamaServerArgs::calculate_max_ctx(16.0, 14.0, "Q4_K_M", "q4_0", "Qwen2.5-Coder-14B-Instruct");
+        let ctx_ud = LlamaServerArgs::calculate_max_ctx(
+            16.0,
+            14.0,
+            "UD-Q4_K_XL",
+            LlamaServerArgs::kv_gb_per_token(14.0, "q4_0", "q4_0", None),
+            LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-14B-Instruct"),
+        );
+        let ctx_std = LlamaServerArgs::calculate_max_ctx(
+            16.0,
+            14.0,
+            "Q4_K_M",
+            LlamaServerArgs::kv_gb_per_token(14.0, "q4_0", "q4_0", None),
+            LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-14B-Instruct"),
+        );
         // UD-Q4_K_XL uses ~12% more memory for weights, so calculated ctx should be ≤ Q4_K_M's
-        assert!(ctx_ud <= ctx_std, "UD ctx {ctx_ud} should be ≤ standard ctx {ctx_std}");
+        assert!(
+            ctx_ud <= ctx_std,
+            "UD ctx {ctx_ud} should be ≤ standard ctx {ctx_std}"
+        );
         // Both capped at native 32768 for Qwen 14B
         assert!(ctx_ud >= 8192, "UD ctx {ctx_ud} should be at least 8192");
     }
 
+    #[test]
+    fn test_kv_geometry_bytes_per_token() {
+        // Qwen2.5-7B: 28 layers, GQA with 4 KV heads of width 128
+        let qwen = KvGeometry::from_metadata(&GgufMetadata {
+            block_count: Some(28),
+            head_count: Some(28),
+            head_count_kv: Some(4),
+            embedding_length: Some(3584),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(qwen.head_dim_k, 128);
+        assert_eq!(
+            qwen.bytes_per_token("f16", "f16"),
+            28.0 * 4.0 * 128.0 * 2.0 * 2.0
+        );
+        // q8_0 blocks: 34 bytes per 32 values
+        assert_eq!(
+            qwen.bytes_per_token("q8_0", "q8_0"),
+            28.0 * 4.0 * 256.0 * 34.0 / 32.0
+        );
+        // Mixed K/V types are summed per half
+        assert_eq!(
+            qwen.bytes_per_token("q8_0", "q4_0"),
+            28.0 * 4.0 * 128.0 * (34.0 + 18.0) / 32.0
+        );
+
+        // Llama-2-7B: MHA (no head_count_kv) → 8× the KV of the GQA model above
+        let llama2 = KvGeometry::from_metadata(&GgufMetadata {
+            block_count: Some(32),
+            head_count: Some(32),
+            embedding_length: Some(4096),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(llama2.n_kv_heads, 32);
+        assert_eq!(llama2.bytes_per_token("f16", "f16"), 524288.0);
+
+        // Explicit key/value widths win over embedding_length / head_count
+        let gemma = KvGeometry::from_metadata(&GgufMetadata {
+            block_count: Some(42),
+            head_count: Some(16),
+            head_count_kv: Some(8),
+            embedding_length: Some(3584),
+            key_length: Some(256),
+            value_length: Some(256),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(gemma.head_dim_v, 256);
+
+        // Missing block count → no geometry
+        assert!(KvGeometry::from_metadata(&GgufMetadata::default()).is_none());
+    }
+
+    #[test]
+    fn test_from_hardware_kv_geometry_sizes_ctx() {
+        let profile = HardwareProfile::test_gpu(16.0);
+        let models = vec![ModelSelection {
+            name: "some-long-context-13B".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        }];
+        let geometry = |n_kv_heads: u32| GgufMetadata {
+            context_length: Some(131072),
+            block_count: Some(40),
+            head_count: Some(40),
+            head_count_kv: Some(n_kv_heads),
+            embedding_length: Some(5120),
+            parameter_count: 13_000_000_000,
+            ..Default::default()
+        };
+
+        let mut gqa = HashMap::new();
+        gqa.insert(models[0].name.clone(), geometry(8));
+        let mut mha = HashMap::new();
+        mha.insert(models[0].name.clone(), geometry(40));
+
+        let gqa_ctx = LlamaServerArgs::from_hardware(&profile, &models, &gqa)
+            .ctx_size
+            .unwrap();
+        let mha_ctx = LlamaServerArgs::from_hardware(&profile, &models, &mha)
+            .ctx_size
+            .unwrap();
+        // 5× fewer KV heads → ~5× more context in the same VRAM
+        assert!(
+            gqa_ctx >= mha_ctx * 4,
+            "GQA ctx {gqa_ctx} should dwarf MHA ctx {mha_ctx}"
+        );
+        // MHA 13B with q8_0 KV: 40 × 40 × 256 × 34/32 ≈ 0.435 MB/token
+        //   free = 16 × 0.9 − 13 × 0.58 − 0.5 = 6.36 GB → ~14.6k tokens
+        assert_eq!(mha_ctx, 14336);
+    }
+
     #[test]
     fn test_quality_cap_per_model_family() {
         // Qwen 7B: native 32768 (capped here, no YaRN extension)
-        assert_eq!(LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-7B-Instruct"), 32768);
+        assert_eq!(
+            LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-7B-Instruct"),
+            32768
+        );
         // Qwen 72B: native 131072
-        assert_eq!(LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-72B-Instruct"), 131072);
+        assert_eq!(
+            LlamaServerArgs::native_ctx_length("Qwen2.5-Coder-72B-Instruct"),
+            131072
+        );
         // Llama 3.1: native 131072
-        assert_eq!(LlamaServerArgs::native_ctx_length("Meta-Llama-3.1-8B-Instruct"), 131072);
+        assert_eq!(
+            LlamaServerArgs::native_ctx_length("Meta-Llama-3.1-8B-Instruct"),
+            131072
+        );
         // Llama 3 (original): native 8192
-        assert_eq!(LlamaServerArgs::native_ctx_length("Meta-Llama-3-8B-Instruct"), 8192);
+        assert_eq!(
+            LlamaServerArgs::native_ctx_length("Meta-Llama-3-8B-Instruct"),
+            8192
+        );
         // Gemma 2: native 8192
         assert_eq!(LlamaServerArgs::native_ctx_length("gemma-2-9b-it"), 8192);
         // Unknown model → fallback 32768
-        assert_eq!(LlamaServerArgs::native_ctx_length("some-random-model"), 32768);
+        assert_eq!(
+            LlamaServerArgs::native_ctx_length("some-random-model"),
+            32768
+        );
     }
 
     #[test]
     fn test_parse_params_b() {
-        assert_eq!(LlamaServerArgs::parse_params_b("Qwen2.5-Coder-7B-Instruct"), Some(7.0));
-        assert_eq!(LlamaServerArgs::parse_params_b("Qwen2.5-Coder-14B-Instruct"), Some(14.0));
-        assert_eq!(LlamaServerArgs::parse_params_b("DeepSeek-Coder-V2-Lite-Instruct-1.5B-GGUF"), Some(1.5));
+        assert_eq!(
+            LlamaServerArgs::parse_params_b("Qwen2.5-Coder-7B-Instruct"),
+            Some(7.0)
+        );
+        assert_eq!(
+            LlamaServerArgs::parse_params_b("Qwen2.5-Coder-14B-Instruct"),
+            Some(14.0)
+        );
+        assert_eq!(
+            LlamaServerArgs::parse_params_b("DeepSeek-Coder-V2-Lite-Instruct-1.5B-GGUF"),
+            Some(1.5)
+        );
         assert_eq!(LlamaServerArgs::parse_params_b("some-model-no-size"), None);
     }
 
@@ -976,35 +1872,40 @@ mod tests {
 
         // _XL suffix → base_M × 1.12
         let ud_q4_xl = LlamaServerArgs::normalize_quant_bpp("UD-Q4_K_XL");
-        assert!((ud_q4_xl - 0.58 * 1.12).abs() < 0.001, "UD-Q4_K_XL ≈ 0.6496, got {ud_q4_xl}");
+        assert!(
+            (ud_q4_xl - 0.58 * 1.12).abs() < 0.001,
+            "UD-Q4_K_XL ≈ 0.6496, got {ud_q4_xl}"
+        );
 
         // _XS suffix → base_M × 0.90
         let ud_q4_xs = LlamaServerArgs::normalize_quant_bpp("UD-Q4_K_XS");
-        assert!((ud_q4_xs - 0.58 * 0.90).abs() < 0.001, "UD-Q4_K_XS ≈ 0.522, got {ud_q4_xs}");
+        assert!(
+            (ud_q4_xs - 0.58 * 0.90).abs() < 0.001,
+            "UD-Q4_K_XS ≈ 0.522, got {ud_q4_xs}"
+        );
 
         // Non-UD _XL (hypothetical) also works
         let q5_xl = LlamaServerArgs::normalize_quant_bpp("Q5_K_XL");
-        assert!((q5_xl - 0.68 * 1.12).abs() < 0.001, "Q5_K_XL ≈ 0.7616, got {q5_xl}");
+        assert!(
+            (q5_xl - 0.68 * 1.12).abs() < 0.001,
+            "Q5_K_XL ≈ 0.7616, got {q5_xl}"
+        );
     }
 
     #[test]
     fn test_from_hardware_vulkan_no_flash_attn() {
         let profile = HardwareProfile {
-            vram_gb: 8.0,
             ram_gb: 16.0,
             cpu_cores: 8,
-            gpu_name: Some("AMD Radeon RX 7600".to_string()),
             gpu_backend: llmfit_core::hardware::GpuBackend::Vulkan,
-            gpu_count: 1,
-            unified_memory: false,
-            recommended_models: vec![],
-            available_memory_gb: 8.0,
+            ..HardwareProfile::test_gpu(8.0)
         };
         let models = vec![ModelSelection {
             name: "Qwen2.5-Coder-7B-Instruct".to_string(),
             quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
         }];
-        let args = LlamaServerArgs::from_hardware(&profile, &models);
+        let args = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
         assert_eq!(args.flash_attn, Some("off".to_string())); // Vulkan → no flash
         assert_eq!(args.n_gpu_layers, Some(999)); // 7B Q4_K_M ≈ 4.06 GB fits in 8 GB
     }
@@ -1012,21 +1913,19 @@ mod tests {
     #[test]
     fn test_from_hardware_apple_silicon() {
         let profile = HardwareProfile {
-            vram_gb: 16.0, // unified memory reported as GPU
             ram_gb: 16.0,
             cpu_cores: 10,
-            gpu_name: Some("Apple M2 Pro".to_string()),
             gpu_backend: llmfit_core::hardware::GpuBackend::Metal,
-            gpu_count: 1,
             unified_memory: true,
-            recommended_models: vec![],
-            available_memory_gb: 16.0,
+            // unified memory reported as GPU
+            ..HardwareProfile::test_gpu(16.0)
         };
         let models = vec![ModelSelection {
             name: "Qwen2.5-Coder-7B-Instruct".to_string(),
             quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
         }];
-        let args = LlamaServerArgs::from_hardware(&profile, &models);
+        let args = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
         assert_eq!(args.n_gpu_layers, Some(999)); // Unified memory → always full offload
         assert_eq!(args.flash_attn, Some("on".to_string())); // Metal → flash on
     }
@@ -1034,27 +1933,53 @@ mod tests {
     #[test]
     fn test_from_hardware_partial_offload() {
         // 4GB VRAM with a 7B Q8_0 model (~7.35 GB) → can't fit all layers
-        let profile = HardwareProfile {
-            vram_gb: 4.0,
-            ram_gb: 32.0,
-            cpu_cores: 16,
-            gpu_name: Some("NVIDIA GeForce GTX 1650".to_string()),
-            gpu_backend: llmfit_core::hardware::GpuBackend::Cuda,
-            gpu_count: 1,
-            unified_memory: false,
-            recommended_models: vec![],
-            available_memory_gb: 4.0,
-        };
+        let profile = HardwareProfile::test_gpu(4.0);
         let models = vec![ModelSelection {
             name: "Qwen2.5-Coder-7B-Instruct".to_string(),
             quant: Some("Q8_0".to_string()),
+            ..Default::default()
         }];
-        let args = LlamaServerArgs::from_hardware(&profile, &models);
+        let args = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
         // 4.0 / 7.35 ≈ 54% → 28 layers × 0.54 ≈ 15
         assert!(args.n_gpu_layers.unwrap() > 0);
         assert!(args.n_gpu_layers.unwrap() < 999);
     }
 
+    #[test]
+    fn test_from_hardware_uses_gguf_metadata() {
+        let profile = HardwareProfile::test_gpu(4.0);
+        // The name heuristic thinks Phi-3.5-mini is a 4k model of unknown size
+        let models = vec![ModelSelection {
+            name: "microsoft/Phi-3.5-mini-instruct".to_string(),
+            quant: Some("Q8_0".to_string()),
+            ..Default::default()
+        }];
+        let guessed = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
+        assert_eq!(guessed.ctx_size, Some(4096));
+
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            models[0].name.clone(),
+            GgufMetadata {
+                context_length: Some(131072),
+                block_count: Some(32),
+                parameter_count: 3_820_000_000,
+                ..Default::default()
+            },
+        );
+        let args = LlamaServerArgs::from_hardware(&profile, &models, &metadata);
+        assert!(
+            args.ctx_size.unwrap() > 4096,
+            "GGUF context_length should lift the 4k guess"
+        );
+        // 3.82B × 1.05 ≈ 4.0 GB doesn't fit in 4 GB → partial offload over 33 layers
+        let layers = args.n_gpu_layers.unwrap();
+        assert!(
+            layers > 0 && layers <= 33,
+            "expected partial offload of ≤33 layers, got {layers}"
+        );
+    }
+
     #[test]
     fn test_llama_server_args_to_cli_gpu() {
         let mut extra_args = HashMap::new();
@@ -1096,17 +2021,7 @@ mod tests {
 
     #[test]
     fn test_for_secondary_model() {
-        let profile = HardwareProfile {
-            vram_gb: 16.0,
-            ram_gb: 32.0,
-            cpu_cores: 16,
-            gpu_name: Some("NVIDIA GeForce RTX 5060 Ti".to_string()),
-            gpu_backend: llmfit_core::hardware::GpuBackend::Cuda,
-            gpu_count: 1,
-            unified_memory: false,
-            recommended_models: vec![],
-            available_memory_gb: 16.0,
-        };
+        let profile = HardwareProfile::test_gpu(16.0);
         let primary = LlamaServerArgs {
             ctx_size: Some(65536),
             n_gpu_layers: Some(999),
@@ -1124,13 +2039,29 @@ mod tests {
         let mini = ModelSelection {
             name: "microsoft/phi-3-mini-4k-instruct".to_string(),
             quant: Some("Q8_0".to_string()),
+            ..Default::default()
         };
-        let secondary = LlamaServerArgs::for_secondary_model(&primary, &mini, &profile);
-        assert_eq!(secondary.ctx_size, Some(8192), "phi-3-mini should get 8192 (floor)");
-        assert_eq!(secondary.cache_type_k, Some("q4_0".to_string()), "secondary uses q4_0 KV to conserve VRAM");
-        assert_eq!(secondary.n_gpu_layers, Some(999), "small model fits in VRAM");
+        let secondary = LlamaServerArgs::for_secondary_model(&primary, &mini, &profile, None);
+        assert_eq!(
+            secondary.ctx_size,
+            Some(8192),
+            "phi-3-mini should get 8192 (floor)"
+        );
+        assert_eq!(
+            secondary.cache_type_k,
+            Some("q4_0".to_string()),
+            "secondary uses q4_0 KV to conserve VRAM"
+        );
+        assert_eq!(
+            secondary.n_gpu_layers,
+            Some(999),
+            "small model fits in VRAM"
+        );
         let cli = secondary.to_cli_args();
-        assert!(!cli.contains("--ctx-size 65536"), "Must NOT inherit primary's 65k ctx");
+        assert!(
+            !cli.contains("--ctx-size 65536"),
+            "Must NOT inherit primary's 65k ctx"
+        );
     }
     #[test]
     fn test_llama_server_args_deserialize_dynamic() {
@@ -1172,4 +2103,245 @@ mod tests {
         assert!(cli.contains("--threads 8"));
         assert!(!cli.contains("--ctx-size"));
     }
+
+    #[test]
+    fn test_pick_draft() {
+        let rec = |name: &str, params_b: f32, memory_gb: f32| crate::profiling::RecommendedModel {
+            name: name.to_string(),
+            category: "Coding".to_string(),
+            score: 80.0,
+            best_quant: "Q4_K_M".to_string(),
+            memory_gb,
+            params_b,
+            is_autocomplete: params_b <= 3.0,
+        };
+        let profile = HardwareProfile {
+            ram_gb: 64.0,
+            recommended_models: vec![
+                rec("Qwen/Qwen2.5-Coder-32B-Instruct", 32.0, 19.0),
+                rec("Qwen/Qwen2.5-Coder-7B-Instruct", 7.0, 4.7),
+                rec("Qwen/Qwen2.5-Coder-1.5B-Instruct", 1.5, 1.2),
+                rec("Qwen/Qwen2.5-Coder-0.5B-Instruct", 0.5, 0.5),
+                rec("meta-llama/Llama-3.2-1B-Instruct", 1.0, 0.8),
+            ],
+            ..HardwareProfile::test_gpu(24.0)
+        };
+        assert_eq!(
+            model_family("Qwen/Qwen2.5-Coder-32B-Instruct"),
+            model_family("bartowski/Qwen2.5-Coder-0.5B-Instruct-GGUF")
+        );
+
+        let primary = &profile.recommended_models[0];
+        assert_eq!(
+            pick_draft(&profile, primary, 5.0).unwrap().name,
+            "Qwen/Qwen2.5-Coder-0.5B-Instruct"
+        );
+        assert!(pick_draft(&profile, primary, 0.2).is_none());
+        // 1.5B is more than a quarter of 7B's size, so only 0.5B qualifies
+        let primary = &profile.recommended_models[1];
+        assert_eq!(
+            pick_draft(&profile, primary, 5.0).unwrap().name,
+            "Qwen/Qwen2.5-Coder-0.5B-Instruct"
+        );
+        // No smaller model of the same family
+        assert!(pick_draft(&profile, &profile.recommended_models[4], 5.0).is_none());
+    }
+
+    #[test]
+    fn test_from_hardware_budgets_draft_model() {
+        let profile = HardwareProfile::test_gpu(12.0);
+        let mut models = vec![ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        }];
+        let without = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
+        assert!(!without.to_cli_args().contains("draft"));
+
+        models[0].draft = Some(Box::new(ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-0.5B-Instruct".to_string(),
+            quant: Some("Q8_0".to_string()),
+            ..Default::default()
+        }));
+        // Not on disk yet: nothing to load, so no flags and no memory
+        let without_file = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
+        assert_eq!(without_file.to_cli_args(), without.to_cli_args());
+
+        let metadata: HashMap<String, GgufMetadata> = [(
+            "Qwen/Qwen2.5-Coder-0.5B-Instruct".to_string(),
+            GgufMetadata::default(),
+        )]
+        .into();
+        let with = LlamaServerArgs::from_hardware(&profile, &models, &metadata);
+        let cli = with.to_cli_args();
+        assert!(cli.contains("--draft-max 16"));
+        assert!(cli.contains("--draft-min 1"));
+        assert!(cli.contains("--gpu-layers-draft 999"));
+
+        // On a tight GPU the draft's weights and KV cache come out of the context budget
+        let tight = HardwareProfile {
+            vram_gb: 5.5,
+            available_memory_gb: 5.5,
+            ..profile
+        };
+        let draft = models[0].draft.take();
+        let without = LlamaServerArgs::from_hardware(&tight, &models, &metadata);
+        models[0].draft = draft;
+        let with = LlamaServerArgs::from_hardware(&tight, &models, &metadata);
+        assert!(with.ctx_size.unwrap() < without.ctx_size.unwrap());
+
+        // Round-trips through localcode.json
+        let json = serde_json::to_string(&models[0]).unwrap();
+        let back: ModelSelection = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.draft.unwrap().name, "Qwen/Qwen2.5-Coder-0.5B-Instruct");
+    }
+
+    #[test]
+    fn test_from_hardware_splits_over_gpus() {
+        let profile = HardwareProfile {
+            ram_gb: 64.0,
+            gpu_count: 2,
+            gpu_vram_gb: vec![12.0, 24.0],
+            ..HardwareProfile::test_gpu(36.0)
+        };
+        let mut models = vec![
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-32B-Instruct".to_string(),
+                quant: Some("Q4_K_M".to_string()),
+                ..Default::default()
+            },
+            ModelSelection {
+                name: "Qwen/Qwen2.5-Coder-1.5B-Instruct".to_string(),
+                quant: Some("Q8_0".to_string()),
+                ..Default::default()
+            },
+        ];
+        let spread = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
+        assert_eq!(spread.n_gpu_layers, Some(999));
+        assert_eq!(spread.extra_args["split-mode"], "layer");
+        assert_eq!(spread.extra_args["main-gpu"], 1);
+        let split = |args: &LlamaServerArgs| -> Vec<f64> {
+            args.extra_args["tensor-split"]
+                .as_str()
+                .unwrap()
+                .split(',')
+                .map(|v| v.parse().unwrap())
+                .collect()
+        };
+        // The unpinned autocomplete model spreads like the primary: 1:2
+        let [small, big] = split(&spread)[..] else {
+            panic!()
+        };
+        assert!((big / small - 2.0).abs() < 0.05);
+
+        // Pinned to the small card, the autocomplete model only comes out of its share
+        models[1].gpu = Some(0);
+        let pinned = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
+        let [small, big] = split(&pinned)[..] else {
+            panic!()
+        };
+        assert_eq!(big, 24.0);
+        assert!(small < 12.0);
+        let secondary = LlamaServerArgs::for_secondary_model(&pinned, &models[1], &profile, None);
+        assert_eq!(secondary.n_gpu_layers, Some(999));
+
+        // A pinned primary stays on its GPU: no split, and only that card's memory
+        models[0].gpu = Some(0);
+        models[1].gpu = Some(1);
+        let alone = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
+        assert!(!alone.extra_args.contains_key("tensor-split"));
+        assert!(
+            alone.n_gpu_layers.unwrap() < 999,
+            "32B Q4 doesn't fit in 12 GB"
+        );
+    }
+
+    #[test]
+    fn test_retrieval_models() {
+        let profile = HardwareProfile::test_gpu(5.5);
+        let mut models = vec![ModelSelection {
+            name: "Qwen/Qwen2.5-Coder-7B-Instruct".to_string(),
+            quant: Some("Q4_K_M".to_string()),
+            ..Default::default()
+        }];
+        let alone = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
+        models.push(ModelSelection {
+            name: "Qwen/Qwen3-Embedding-0.6B".to_string(),
+            quant: Some("Q8_0".to_string()),
+            role: Some(ModelRole::Embedding),
+            ..Default::default()
+        });
+        let with = LlamaServerArgs::from_hardware(&profile, &models, &HashMap::new());
+        assert!(with.ctx_size.unwrap() < alone.ctx_size.unwrap());
+        // Listed first, it is still sized as a secondary and not as the primary
+        let reversed: Vec<ModelSelection> = models.iter().rev().cloned().collect();
+        assert_eq!(
+            LlamaServerArgs::from_hardware(&profile, &reversed, &HashMap::new()).to_cli_args(),
+            with.to_cli_args()
+        );
+
+        // Pooling comes from the GGUF; the whole context fits in one micro-batch
+        let meta = GgufMetadata {
+            context_length: Some(32768),
+            pooling_type: Some(3),
+            ..Default::default()
+        };
+        let embed = LlamaServerArgs::for_retrieval_model(
+            Some(&with),
+            &models[1],
+            Some(&profile),
+            Some(&meta),
+        );
+        assert_eq!(
+            embed.to_cli_args(),
+            "--ctx-size 8192 --n-gpu-layers 999 --flash-attn on --batch-size 8192 --embedding --pooling last --threads 8 --ubatch-size 8192"
+        );
+
+        models[1].role = Some(ModelRole::Reranker);
+        let rerank =
+            LlamaServerArgs::for_retrieval_model(None, &models[1], None, None).to_cli_args();
+        assert!(rerank.contains("--pooling rank --reranking"));
+        assert!(!rerank.contains("--embedding"));
+
+        let json = serde_json::to_string(&models[1]).unwrap();
+        assert!(json.contains(r#""role":"reranker""#));
+        assert!(!serde_json::to_string(&models[0]).unwrap().contains("role"));
+    }
+
+    #[test]
+    fn test_pick_local_models() {
+        let local = |name: &str, gb: u64| crate::models::DiscoveredModel {
+            name: name.to_string(),
+            path: std::path::PathBuf::from(format!("/models/{}", name)),
+            size_bytes: gb * 1_000_000_000,
+            source: "LocalCode Config".to_string(),
+            ..Default::default()
+        };
+        let models = vec![
+            local("small-Q4_K_M.gguf", 2),
+            local("mid-Q4_K_M.gguf", 9),
+            local("huge-Q4_K_M.gguf", 40),
+        ];
+
+        // Largest that fits, with its path recorded
+        let picked = pick_local_models(&models, None, 12.0).unwrap();
+        assert_eq!(picked[0].name, "mid-Q4_K_M");
+        assert_eq!(picked[0].path.as_deref(), Some("/models/mid-Q4_K_M.gguf"));
+        assert!(picked[0].quant.is_none());
+
+        // Nothing fits: smallest
+        assert_eq!(
+            pick_local_models(&models, None, 1.0).unwrap()[0].name,
+            "small-Q4_K_M"
+        );
+
+        // --models by file name, with or without extension
+        let requested = vec!["HUGE-Q4_K_M".to_string(), "small-Q4_K_M.gguf".to_string()];
+        let picked = pick_local_models(&models, Some(&requested), 12.0).unwrap();
+        assert_eq!(picked.len(), 2);
+        assert_eq!(picked[0].name, "huge-Q4_K_M");
+
+        let unknown = vec!["llama3-8b-instruct".to_string()];
+        assert!(pick_local_models(&models, Some(&unknown), 12.0).is_err());
+    }
 }